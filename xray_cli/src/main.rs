@@ -17,11 +17,12 @@ const USAGE: &'static str = "
 Xray
 
 Usage:
-  xray [--socket-path=<path>] <path>...
+  xray [--socket-path=<path>] [--ignore=<glob>]... <path>...
   xray (-h | --help)
 
 Options:
-  -h --help     Show this screen.
+  -h --help          Show this screen.
+  --ignore=<glob>    Exclude paths matching a gitignore-style glob from the file finder.
 ";
 
 const DEFAULT_SOCKET_PATH: &'static str = "/tmp/xray.sock";
@@ -29,6 +30,7 @@ const DEFAULT_SOCKET_PATH: &'static str = "/tmp/xray.sock";
 #[derive(Debug, Deserialize)]
 struct Args {
     flag_socket_path: Option<String>,
+    flag_ignore: Vec<String>,
     arg_path: Vec<String>,
 }
 
@@ -39,7 +41,8 @@ fn main() {
 
     let message = json!({
         "type": "OpenWorkspace",
-        "paths": args.arg_path
+        "paths": args.arg_path,
+        "ignore_globs": args.flag_ignore
     });
 
     let socket_path = args.flag_socket_path
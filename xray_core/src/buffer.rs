@@ -1,22 +1,45 @@
 use std::cell::RefCell;
 use std::cmp;
 use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
 use std::iter;
 use std::ops::{Add, AddAssign, Range, Sub};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::result;
 use std::sync::Arc;
-use super::tree::{self, SeekBias, Tree};
+use std::sync::atomic::{self, AtomicUsize};
+use std::time::SystemTime;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use super::tree::{self, Item, SeekBias, Tree};
 use notify_cell::NotifyCell;
+use syntax::{self, Syntax, SyntaxTree};
+use tree_sitter::Language;
 
 pub type ReplicaId = usize;
 type LocalTimestamp = usize;
 type LamportTimestamp = usize;
 type Result<T> = result::Result<T, Error>;
 
+// Distinguishes the temporary files written by concurrent saves within this process.
+static SAVE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 #[derive(Eq, PartialEq, Debug)]
 pub enum Error {
     OffsetOutOfRange,
     InvalidAnchor,
+    InvalidOperation,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    NoPath,
+    ModifiedOnDisk,
+    IoError(io::Error),
 }
 
 #[derive(Debug)]
@@ -28,13 +51,109 @@ pub struct Buffer {
     insertions: HashMap<ChangeId, Tree<FragmentMapping>>,
     anchor_cache: RefCell<HashMap<Anchor, (usize, Point)>>,
     offset_cache: RefCell<HashMap<Point, usize>>,
+    deferred_ops: Vec<Operation>,
+    change_deletions: HashMap<ChangeId, Vec<TextDeletion>>,
+    undo_counts: HashMap<ChangeId, usize>,
+    history: History,
+    path: Option<PathBuf>,
+    line_ending: LineEnding,
+    disk_state: Option<DiskState>,
+    saved_version: Version,
+    // Every edit ever made, so that `edits_since` can answer for any version a display or
+    // highlight map last synced to. The buffer doesn't know which versions are still needed, so
+    // the list is never trimmed and grows with the number of edits over the buffer's lifetime.
+    edits: Vec<(Version, Edit)>,
+    pending_edits: Vec<Edit>,
+    syntax: RefCell<Option<Syntax>>,
+    indent_settings: IndentSettings,
     pub version: NotifyCell<Version>,
 }
 
-#[derive(Clone, Copy, Debug)]
+/// How the lines of a buffer are indented.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IndentSettings {
+    /// Whether indentation is inserted as spaces rather than tabs.
+    pub soft_tabs: bool,
+    /// The number of columns spanned by a level of indentation, which is also the width of a tab.
+    pub tab_length: u32,
+    /// Lines ending with one of these tokens indent the line that follows them.
+    pub increase_indent_tokens: Vec<String>,
+    /// Lines starting with one of these tokens are outdented relative to the line above them.
+    pub decrease_indent_tokens: Vec<String>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum LineEnding {
+    Verbatim,
+    CrLf,
+}
+
+#[derive(Debug)]
+struct DiskState {
+    modified: Option<SystemTime>,
+    content_hash: u64,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub struct Version(LocalTimestamp);
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Hash)]
+/// A change to the visible text, described by the range it replaced in the text as it was before
+/// the change and the range of the text that replaced it. Both ranges are given as points and as
+/// offsets.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Edit {
+    pub old_range: Range<Point>,
+    pub new_range: Range<Point>,
+    pub old_offset_range: Range<usize>,
+    pub new_offset_range: Range<usize>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub struct TransactionId(LocalTimestamp);
+
+#[derive(Debug)]
+struct History {
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+    current_transaction: Option<Transaction>,
+    transaction_depth: usize,
+}
+
+#[derive(Debug)]
+struct Transaction {
+    id: TransactionId,
+    changes: Vec<ChangeId>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Operation {
+    Edit {
+        id: ChangeId,
+        lamport_timestamp: LamportTimestamp,
+        insertion: Option<TextInsertion>,
+        deletions: Vec<TextDeletion>,
+    },
+    Undo {
+        lamport_timestamp: LamportTimestamp,
+        undo_counts: Vec<(ChangeId, usize)>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TextInsertion {
+    parent_id: ChangeId,
+    offset_in_parent: usize,
+    text: Text,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TextDeletion {
+    insertion_id: ChangeId,
+    start_offset: usize,
+    end_offset: usize,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, Hash)]
 pub struct Point {
     pub row: u32,
     pub column: u32,
@@ -60,6 +179,14 @@ enum AnchorBias {
     Right,
 }
 
+/// The text of a buffer at a particular version. Snapshots share their fragments with the buffer,
+/// so they are cheap to take and can be read on another thread while the buffer is being edited.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    fragments: Tree<Fragment>,
+    version: Version,
+}
+
 pub struct Iter<'a> {
     fragment_cursor: tree::Cursor<'a, Fragment>,
     fragment_offset: usize,
@@ -75,14 +202,14 @@ struct Insertion {
     text: Text,
 }
 
-#[derive(Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Text {
     code_units: Vec<u16>,
     newline_offsets: Vec<usize>,
 }
 
-#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug, Serialize)]
-struct ChangeId {
+#[derive(Hash, Eq, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ChangeId {
     replica_id: ReplicaId,
     local_timestamp: LocalTimestamp,
 }
@@ -130,38 +257,212 @@ impl Buffer {
     pub fn new(replica_id: ReplicaId) -> Self {
         assert!(replica_id > 0);
         let mut fragments = Tree::<Fragment>::new();
+        let mut insertions = HashMap::new();
+        let sentinel_id = ChangeId {
+            replica_id: 0,
+            local_timestamp: 0,
+        };
 
-        // Push start sentinel.
+        // Push start sentinel. Remote insertions at the start of the buffer are parented to it,
+        // so it needs an entry in the insertions map like any other insertion.
         fragments.push(Fragment::new(
             FragmentId::min_value(),
             Insertion {
-                id: ChangeId {
-                    replica_id: 0,
-                    local_timestamp: 0,
-                },
-                parent_id: ChangeId {
-                    replica_id: 0,
-                    local_timestamp: 0,
-                },
+                id: sentinel_id,
+                parent_id: sentinel_id,
                 offset_in_parent: 0,
                 replica_id: 0,
                 lamport_timestamp: 0,
                 text: Text::new(vec![]),
             },
         ));
+        let mut sentinel_split_tree = Tree::new();
+        sentinel_split_tree.push(FragmentMapping {
+            extent: 0,
+            fragment_id: FragmentId::min_value(),
+        });
+        insertions.insert(sentinel_id, sentinel_split_tree);
 
         Buffer {
             replica_id,
             local_clock: 0,
             lamport_clock: 0,
             fragments,
-            insertions: HashMap::new(),
+            insertions,
             anchor_cache: RefCell::new(HashMap::new()),
             offset_cache: RefCell::new(HashMap::new()),
+            deferred_ops: Vec::new(),
+            change_deletions: HashMap::new(),
+            undo_counts: HashMap::new(),
+            history: History::new(),
+            path: None,
+            line_ending: LineEnding::Verbatim,
+            disk_state: None,
+            saved_version: Version(0),
+            edits: Vec::new(),
+            pending_edits: Vec::new(),
+            syntax: RefCell::new(None),
+            indent_settings: IndentSettings::default(),
             version: NotifyCell::new(Version(0)),
         }
     }
 
+    /// Creates a buffer with the contents of the file at the given path. Files that consistently
+    /// use CRLF line endings are edited with LF line endings and converted back when saved. Any
+    /// other files are edited verbatim.
+    pub fn load<T: Into<PathBuf>>(replica_id: ReplicaId, path: T) -> io::Result<Self> {
+        let path = path.into();
+        let contents = read_file(&path)?;
+        let disk_state = DiskState::new(&fs::metadata(&path)?, &contents);
+        let mut contents = String::from_utf8_lossy(&contents).into_owned();
+
+        let newline_count = contents.matches('\n').count();
+        let line_ending = if newline_count > 0 && contents.matches("\r\n").count() == newline_count {
+            contents = contents.replace("\r\n", "\n");
+            LineEnding::CrLf
+        } else {
+            LineEnding::Verbatim
+        };
+
+        let mut buffer = Buffer::new(replica_id);
+        buffer.splice(0..0, contents.as_str());
+        buffer.history = History::new();
+        buffer.set_language(syntax::language_for_path(&path));
+        buffer.path = Some(path);
+        buffer.line_ending = line_ending;
+        buffer.disk_state = Some(disk_state);
+        buffer.saved_version = buffer.current_version();
+        Ok(buffer)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Sets the language the buffer is parsed with. Passing `None` discards the syntax tree.
+    pub fn set_language(&mut self, language: Option<Language>) {
+        *self.syntax.get_mut() = language.map(Syntax::new);
+    }
+
+    /// Returns the syntax tree for the current contents of the buffer, if it has a language. The
+    /// buffer is parsed again on demand, reusing the parts of the previous tree that weren't
+    /// edited.
+    pub fn syntax_tree(&self) -> Option<SyntaxTree> {
+        self.syntax
+            .borrow_mut()
+            .as_mut()
+            .and_then(|syntax| syntax.tree(|| self.to_u16_chars()))
+    }
+
+    pub fn indent_settings(&self) -> &IndentSettings {
+        &self.indent_settings
+    }
+
+    pub fn set_indent_settings(&mut self, mut indent_settings: IndentSettings) {
+        indent_settings.tab_length = cmp::max(1, indent_settings.tab_length);
+        self.indent_settings = indent_settings;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.current_version() != self.saved_version
+    }
+
+    /// Writes the buffer to its path, refusing to do so if the file has been modified by another
+    /// process since it was loaded or last saved.
+    pub fn save(&mut self) -> result::Result<(), SaveError> {
+        if self.is_modified_on_disk()? {
+            Err(SaveError::ModifiedOnDisk)
+        } else {
+            self.save_overwriting()
+        }
+    }
+
+    /// Writes the buffer to its path, discarding any changes made to the file by other processes.
+    /// The contents are written to a temporary file that is then moved into place, so the file is
+    /// never observed in a partially-written state.
+    pub fn save_overwriting(&mut self) -> result::Result<(), SaveError> {
+        let path = self.path.clone().ok_or(SaveError::NoPath)?;
+        let mut contents = String::from_utf16_lossy(&self.to_u16_chars());
+        if self.line_ending == LineEnding::CrLf {
+            contents = contents.replace('\n', "\r\n");
+        }
+
+        // Renaming onto a symlink would replace the link, so write to the file it points to.
+        let path = fs::canonicalize(&path).unwrap_or(path);
+        let file_name = path.file_name().ok_or(SaveError::NoPath)?.to_string_lossy();
+        let (temp_path, mut temp_file) = loop {
+            let temp_path = path.with_file_name(format!(
+                ".{}.{}.{}.xray-save",
+                file_name,
+                process::id(),
+                SAVE_COUNT.fetch_add(1, atomic::Ordering::SeqCst)
+            ));
+            match fs::OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+                Ok(file) => break (temp_path, file),
+                Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(SaveError::IoError(error)),
+            }
+        };
+        let write_result = temp_file
+            .write_all(contents.as_bytes())
+            .and_then(|_| temp_file.sync_all());
+        drop(temp_file);
+        let write_result = write_result
+            .and_then(|_| match fs::metadata(&path) {
+                Ok(metadata) => fs::set_permissions(&temp_path, metadata.permissions()),
+                Err(_) => Ok(()),
+            })
+            .and_then(|_| fs::rename(&temp_path, &path));
+        if let Err(error) = write_result {
+            let _ = fs::remove_file(&temp_path);
+            return Err(SaveError::IoError(error));
+        }
+
+        self.disk_state = Some(DiskState::new(&fs::metadata(&path)?, contents.as_bytes()));
+        self.saved_version = self.current_version();
+        Ok(())
+    }
+
+    fn is_modified_on_disk(&self) -> result::Result<bool, SaveError> {
+        let path = self.path.as_ref().ok_or(SaveError::NoPath)?;
+        let disk_state = match self.disk_state {
+            Some(ref disk_state) => disk_state,
+            None => return Ok(false),
+        };
+
+        match fs::metadata(path) {
+            Ok(metadata) => {
+                if metadata.modified().ok() == disk_state.modified {
+                    Ok(false)
+                } else {
+                    // The modification time can change without the contents changing, so only
+                    // report a conflict if the contents differ from what we last read or wrote.
+                    let contents = read_file(path)?;
+                    Ok(hash_contents(&contents) != disk_state.content_hash)
+                }
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(SaveError::IoError(error)),
+        }
+    }
+
+    pub fn current_version(&self) -> Version {
+        Version(self.local_clock)
+    }
+
+    /// Returns the edits made since the given version, in the order they were applied. Each edit
+    /// is expressed in the coordinates of the text as it was right before it.
+    pub fn edits_since(&self, version: Version) -> Vec<Edit> {
+        let mut edits = self.edits
+            .iter()
+            .rev()
+            .take_while(|&&(edit_version, _)| edit_version > version)
+            .map(|&(_, ref edit)| edit.clone())
+            .collect::<Vec<_>>();
+        edits.reverse();
+        edits
+    }
+
     pub fn len(&self) -> usize {
         self.fragments.len::<CharacterCount>().0
     }
@@ -177,6 +478,36 @@ impl Buffer {
         Ok((row_end_offset - row_start_offset) as u32)
     }
 
+    pub fn line(&self, row: u32) -> Result<Vec<u16>> {
+        if row > self.max_point().row {
+            return Err(Error::OffsetOutOfRange);
+        }
+
+        Ok(self.iter_starting_at_row(row)
+            .take_while(|c| *c != b'\n' as u16)
+            .collect())
+    }
+
+    pub fn text_for_range(&self, range: Range<Point>) -> Result<Vec<u16>> {
+        let start_offset = self.offset_for_point(range.start)?;
+        let end_offset = self.offset_for_point(range.end)?;
+        Ok(self.iter_starting_at_row(range.start.row)
+            .skip(range.start.column as usize)
+            .take(end_offset.saturating_sub(start_offset))
+            .collect())
+    }
+
+    /// Returns the closest valid point to the given one.
+    pub fn clip_point(&self, point: Point) -> Point {
+        let max_point = self.max_point();
+        if point.row > max_point.row {
+            max_point
+        } else {
+            let max_column = self.len_for_row(point.row).unwrap();
+            Point::new(point.row, cmp::min(point.column, max_column))
+        }
+    }
+
     pub fn max_point(&self) -> Point {
         self.fragments.len::<Point>()
     }
@@ -192,15 +523,72 @@ impl Buffer {
         String::from_utf16_lossy(self.iter().collect::<Vec<u16>>().as_slice())
     }
 
+    /// Returns the ranges of all non-empty matches of the given regex as UTF-16 offsets.
+    pub fn find_all(&self, regex: &Regex) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        self.for_each_match(regex, |range, _| ranges.push(range));
+        ranges
+    }
+
+    /// Returns the range of each non-empty match of the given regex along with the text that
+    /// should replace it, which is built by expanding capture group references such as `$1` in
+    /// `template`.
+    pub fn find_replacements(&self, regex: &Regex, template: &str) -> Vec<(Range<usize>, String)> {
+        let mut replacements = Vec::new();
+        self.for_each_match(regex, |range, captures| {
+            let mut replacement = String::new();
+            captures.expand(template, &mut replacement);
+            replacements.push((range, replacement));
+        });
+        replacements
+    }
+
+    /// Replaces every match of the given regex in a single transaction, so that the whole
+    /// replacement can be undone in one step. Returns the number of matches replaced.
+    pub fn replace_all(&mut self, regex: &Regex, template: &str) -> usize {
+        let replacements = self.find_replacements(regex, template);
+        self.start_transaction();
+        for &(ref range, ref replacement) in replacements.iter().rev() {
+            self.splice(range.clone(), replacement.as_str());
+        }
+        self.end_transaction();
+        replacements.len()
+    }
+
+    fn for_each_match<F: FnMut(Range<usize>, &Captures)>(&self, regex: &Regex, mut f: F) {
+        let text = String::from_utf16_lossy(&self.to_u16_chars());
+        let mut byte_offset = 0;
+        let mut offset = 0;
+        for captures in regex.captures_iter(&text) {
+            let mat = captures.get(0).unwrap();
+            if mat.start() == mat.end() {
+                continue;
+            }
+
+            offset += text[byte_offset..mat.start()].encode_utf16().count();
+            let start = offset;
+            offset += mat.as_str().encode_utf16().count();
+            byte_offset = mat.end();
+            f(start..offset, &captures);
+        }
+    }
+
     pub fn iter(&self) -> Iter {
-        Iter::new(self)
+        Iter::new(&self.fragments)
     }
 
     pub fn iter_starting_at_row(&self, row: u32) -> Iter {
-        Iter::starting_at_row(self, row)
+        Iter::starting_at_row(&self.fragments, row)
     }
 
-    pub fn splice<T: Into<Text>>(&mut self, old_range: Range<usize>, new_text: T) {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            fragments: self.fragments.clone(),
+            version: self.current_version(),
+        }
+    }
+
+    pub fn splice<T: Into<Text>>(&mut self, old_range: Range<usize>, new_text: T) -> Option<Operation> {
         let new_text = new_text.into();
         let new_text = if new_text.len() > 0 {
             Some(new_text)
@@ -208,17 +596,460 @@ impl Buffer {
             None
         };
         if new_text.is_some() || old_range.end > old_range.start {
+            self.start_transaction();
             self.local_clock += 1;
             self.lamport_clock += 1;
             let change_id = ChangeId {
                 replica_id: self.replica_id,
                 local_timestamp: self.local_clock,
             };
-            self.splice_fragments(change_id, old_range, new_text);
-            self.anchor_cache.borrow_mut().clear();
-            self.offset_cache.borrow_mut().clear();
-            self.version.set(Version(self.local_clock));
+            let old_start = self.point_for_offset(old_range.start).unwrap();
+            let old_end = self.point_for_offset(old_range.end).unwrap();
+            let new_end_offset = old_range.start + new_text.as_ref().map_or(0, |text| text.len());
+            let old_offset_range = old_range.clone();
+            let operation = self.splice_fragments(change_id, old_range, new_text);
+            let new_end = self.point_for_offset(new_end_offset).unwrap();
+            self.pending_edits.push(Edit {
+                old_range: old_start..old_end,
+                new_range: old_start..new_end,
+                new_offset_range: old_offset_range.start..new_end_offset,
+                old_offset_range,
+            });
+            if let Operation::Edit { ref deletions, .. } = operation {
+                self.change_deletions.insert(change_id, deletions.clone());
+            }
+            self.history
+                .current_transaction
+                .as_mut()
+                .unwrap()
+                .changes
+                .push(change_id);
+            self.end_transaction();
+            self.did_change();
+            Some(operation)
+        } else {
+            None
+        }
+    }
+
+    /// Groups all splices until the matching call to `end_transaction` into a single undo step.
+    /// Transactions can be nested, in which case only the outermost one is recorded.
+    pub fn start_transaction(&mut self) {
+        if self.history.transaction_depth == 0 {
+            self.history.current_transaction = Some(Transaction {
+                id: TransactionId(self.local_clock),
+                changes: Vec::new(),
+            });
+        }
+        self.history.transaction_depth += 1;
+    }
+
+    /// Returns the id of the recorded transaction when the outermost transaction ends and it
+    /// contains at least one change.
+    pub fn end_transaction(&mut self) -> Option<TransactionId> {
+        debug_assert!(self.history.transaction_depth > 0);
+        self.history.transaction_depth -= 1;
+        if self.history.transaction_depth == 0 {
+            let transaction = self.history.current_transaction.take().unwrap();
+            if !transaction.changes.is_empty() {
+                let transaction_id = transaction.id;
+                self.history.undo_stack.push(transaction);
+                self.history.redo_stack.clear();
+                return Some(transaction_id);
+            }
+        }
+        None
+    }
+
+    pub fn undo(&mut self) -> Option<(TransactionId, Operation)> {
+        let transaction = self.history.undo_stack.pop()?;
+        let result = self.toggle_transaction(&transaction);
+        self.history.redo_stack.push(transaction);
+        Some(result)
+    }
+
+    pub fn redo(&mut self) -> Option<(TransactionId, Operation)> {
+        let transaction = self.history.redo_stack.pop()?;
+        let result = self.toggle_transaction(&transaction);
+        self.history.undo_stack.push(transaction);
+        Some(result)
+    }
+
+    fn toggle_transaction(&mut self, transaction: &Transaction) -> (TransactionId, Operation) {
+        self.lamport_clock += 1;
+        let undo_counts = transaction
+            .changes
+            .iter()
+            .map(|change_id| {
+                let undo_count = self.undo_counts.get(change_id).cloned().unwrap_or(0);
+                (*change_id, undo_count + 1)
+            })
+            .collect::<Vec<_>>();
+        let operation = Operation::Undo {
+            lamport_timestamp: self.lamport_clock,
+            undo_counts,
+        };
+        self.apply_op(operation.clone()).unwrap();
+        (transaction.id, operation)
+    }
+
+    /// Integrates operations produced by `splice` on other replicas. Operations that depend on
+    /// insertions we haven't seen yet are deferred until those insertions arrive.
+    pub fn apply_ops<I: IntoIterator<Item = Operation>>(&mut self, ops: I) -> Result<()> {
+        self.deferred_ops.extend(ops);
+
+        let mut applied_op = true;
+        while applied_op {
+            applied_op = false;
+            for op in self.deferred_ops.drain(..).collect::<Vec<_>>() {
+                if self.can_apply_op(&op) {
+                    self.apply_op(op)?;
+                    applied_op = true;
+                } else {
+                    self.deferred_ops.push(op);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn can_apply_op(&self, op: &Operation) -> bool {
+        match *op {
+            Operation::Edit {
+                ref insertion,
+                ref deletions,
+                ..
+            } => {
+                let parent_is_known = match *insertion {
+                    Some(ref insertion) => self.insertions.contains_key(&insertion.parent_id),
+                    None => true,
+                };
+                parent_is_known
+                    && deletions
+                        .iter()
+                        .all(|deletion| self.insertions.contains_key(&deletion.insertion_id))
+            }
+            Operation::Undo {
+                ref undo_counts, ..
+            } => undo_counts
+                .iter()
+                .all(|&(change_id, _)| self.change_deletions.contains_key(&change_id)),
+        }
+    }
+
+    fn apply_op(&mut self, op: Operation) -> Result<()> {
+        match op {
+            Operation::Edit {
+                id,
+                lamport_timestamp,
+                insertion,
+                deletions,
+            } => {
+                if let Some(insertion) = insertion {
+                    self.integrate_insertion(id, lamport_timestamp, insertion)?;
+                }
+                for deletion in &deletions {
+                    self.integrate_deletion(id, deletion)?;
+                }
+                self.change_deletions.insert(id, deletions);
+                self.lamport_clock = cmp::max(self.lamport_clock, lamport_timestamp);
+            }
+            Operation::Undo {
+                lamport_timestamp,
+                undo_counts,
+            } => {
+                for (change_id, undo_count) in undo_counts {
+                    self.integrate_undo(change_id, undo_count)?;
+                }
+                self.lamport_clock = cmp::max(self.lamport_clock, lamport_timestamp);
+            }
+        }
+
+        self.local_clock += 1;
+        self.did_change();
+        Ok(())
+    }
+
+    fn did_change(&mut self) {
+        self.anchor_cache.borrow_mut().clear();
+        self.offset_cache.borrow_mut().clear();
+        let version = Version(self.local_clock);
+        if let Some(syntax) = self.syntax.get_mut() {
+            for edit in &self.pending_edits {
+                syntax.edit(edit);
+            }
         }
+        for edit in self.pending_edits.drain(..) {
+            self.edits.push((version, edit));
+        }
+        self.version.set(version);
+    }
+
+    fn integrate_insertion(
+        &mut self,
+        change_id: ChangeId,
+        lamport_timestamp: LamportTimestamp,
+        insertion: TextInsertion,
+    ) -> Result<()> {
+        self.split_insertion_at(insertion.parent_id, insertion.offset_in_parent)?;
+        let parent_fragment_id =
+            self.fragment_id_for_insertion_offset(insertion.parent_id, insertion.offset_in_parent)?;
+
+        let old_fragments = self.fragments.clone();
+        let mut cursor = old_fragments.cursor();
+        let mut updated_fragments = cursor.build_prefix(&parent_fragment_id, SeekBias::Left);
+        updated_fragments.push(cursor.item().unwrap().clone());
+        cursor.next();
+
+        // Skip over insertions made concurrently at the same location that take precedence over
+        // this one, along with anything inserted inside them. All of these have a greater Lamport
+        // timestamp, so every replica ends up ordering them identically.
+        while let Some(fragment) = cursor.item() {
+            let fragment_precedence =
+                (fragment.insertion.lamport_timestamp, fragment.insertion.replica_id);
+            if fragment_precedence > (lamport_timestamp, change_id.replica_id) {
+                updated_fragments.push(fragment.clone());
+                cursor.next();
+            } else {
+                break;
+            }
+        }
+
+        let new_fragment_id = FragmentId::between(
+            &cursor.prev_item().unwrap().id,
+            cursor
+                .item()
+                .map(|f| &f.id)
+                .unwrap_or(&FragmentId::max_value()),
+        );
+        let mut split_tree = Tree::new();
+        split_tree.push(FragmentMapping {
+            extent: insertion.text.len(),
+            fragment_id: new_fragment_id.clone(),
+        });
+        self.insertions.insert(change_id, split_tree);
+
+        let start = updated_fragments.len::<Point>();
+        let start_offset = updated_fragments.len::<CharacterCount>().0;
+        let fragment = Fragment::new(
+            new_fragment_id,
+            Insertion {
+                id: change_id,
+                parent_id: insertion.parent_id,
+                offset_in_parent: insertion.offset_in_parent,
+                replica_id: change_id.replica_id,
+                lamport_timestamp,
+                text: insertion.text,
+            },
+        );
+        let summary = fragment.summarize();
+        self.pending_edits.push(Edit {
+            old_range: start..start,
+            new_range: start..start + &summary.extent_2d,
+            old_offset_range: start_offset..start_offset,
+            new_offset_range: start_offset..start_offset + summary.extent,
+        });
+        updated_fragments.push(fragment);
+        updated_fragments.push_tree(cursor.build_suffix());
+        self.fragments = updated_fragments;
+        Ok(())
+    }
+
+    fn integrate_deletion(&mut self, change_id: ChangeId, deletion: &TextDeletion) -> Result<()> {
+        self.split_insertion_at(deletion.insertion_id, deletion.start_offset)?;
+        self.split_insertion_at(deletion.insertion_id, deletion.end_offset)?;
+
+        let fragment_ids = self.fragment_ids_for_insertion_range(
+            deletion.insertion_id,
+            deletion.start_offset..deletion.end_offset,
+        );
+        for fragment_id in fragment_ids {
+            self.update_fragment(&fragment_id, |fragment| {
+                fragment.deletions.insert(change_id);
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Undo counts only ever increase, so replicas converge on the greatest count they observe
+    /// for each change. A change is undone whenever its count is odd, which we represent by
+    /// hiding the text it inserted and restoring the text it deleted. Hiding an insertion adds the
+    /// change's own id to the deletions of its fragments, which can't otherwise contain it.
+    fn integrate_undo(&mut self, change_id: ChangeId, undo_count: usize) -> Result<()> {
+        let prev_undo_count = self.undo_counts.get(&change_id).cloned().unwrap_or(0);
+        if undo_count <= prev_undo_count {
+            return Ok(());
+        }
+        self.undo_counts.insert(change_id, undo_count);
+
+        let undone = undo_count % 2 == 1;
+        if undone == (prev_undo_count % 2 == 1) {
+            return Ok(());
+        }
+
+        let inserted_fragment_ids = self.insertions.get(&change_id).map(|split_tree| {
+            let mut cursor = split_tree.cursor();
+            cursor.seek(&InsertionOffset(0), SeekBias::Left);
+            let mut fragment_ids = Vec::new();
+            while let Some(mapping) = cursor.item() {
+                fragment_ids.push(mapping.fragment_id.clone());
+                cursor.next();
+            }
+            fragment_ids
+        });
+        for fragment_id in inserted_fragment_ids.unwrap_or_default() {
+            self.update_fragment(&fragment_id, |fragment| {
+                if undone {
+                    fragment.deletions.insert(change_id);
+                } else {
+                    fragment.deletions.remove(&change_id);
+                }
+            });
+        }
+
+        let deletions = self.change_deletions
+            .get(&change_id)
+            .cloned()
+            .ok_or(Error::InvalidOperation)?;
+        for deletion in deletions {
+            let fragment_ids = self.fragment_ids_for_insertion_range(
+                deletion.insertion_id,
+                deletion.start_offset..deletion.end_offset,
+            );
+            for fragment_id in fragment_ids {
+                self.update_fragment(&fragment_id, |fragment| {
+                    if undone {
+                        fragment.deletions.remove(&change_id);
+                    } else {
+                        fragment.deletions.insert(change_id);
+                    }
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fragment_ids_for_insertion_range(
+        &self,
+        insertion_id: ChangeId,
+        range: Range<usize>,
+    ) -> Vec<FragmentId> {
+        let mut fragment_ids = Vec::new();
+        if let Some(split_tree) = self.insertions.get(&insertion_id) {
+            let mut cursor = split_tree.cursor();
+            cursor.seek(&InsertionOffset(range.start), SeekBias::Right);
+            while let Some(mapping) = cursor.item() {
+                if cursor.start::<InsertionOffset>().0 >= range.end {
+                    break;
+                }
+                fragment_ids.push(mapping.fragment_id.clone());
+                cursor.next();
+            }
+        }
+        fragment_ids
+    }
+
+    fn update_fragment<F: FnOnce(&mut Fragment)>(&mut self, fragment_id: &FragmentId, f: F) {
+        let old_fragments = self.fragments.clone();
+        let mut cursor = old_fragments.cursor();
+        let mut updated_fragments = cursor.build_prefix(fragment_id, SeekBias::Left);
+        let mut fragment = cursor.item().unwrap().clone();
+        let was_visible = fragment.is_visible();
+        let old_summary = fragment.summarize();
+        f(&mut fragment);
+        if fragment.is_visible() != was_visible {
+            let start = updated_fragments.len::<Point>();
+            let start_offset = updated_fragments.len::<CharacterCount>().0;
+            let new_summary = fragment.summarize();
+            self.pending_edits.push(Edit {
+                old_range: start..start + &old_summary.extent_2d,
+                new_range: start..start + &new_summary.extent_2d,
+                old_offset_range: start_offset..start_offset + old_summary.extent,
+                new_offset_range: start_offset..start_offset + new_summary.extent,
+            });
+        }
+        updated_fragments.push(fragment);
+        cursor.next();
+        updated_fragments.push_tree(cursor.build_suffix());
+        self.fragments = updated_fragments;
+    }
+
+    /// Ensures that a fragment boundary exists at the given offset within an insertion, splitting
+    /// the fragment that contains the offset if necessary.
+    fn split_insertion_at(&mut self, insertion_id: ChangeId, offset: usize) -> Result<()> {
+        let (mapping_start, mapping_end, fragment_id) = {
+            let split_tree = self.insertions
+                .get(&insertion_id)
+                .ok_or(Error::InvalidOperation)?;
+            let mut cursor = split_tree.cursor();
+            cursor.seek(&InsertionOffset(offset), SeekBias::Left);
+            let mapping = cursor.item().ok_or(Error::InvalidOperation)?;
+            let mapping_start = cursor.start::<InsertionOffset>().0;
+            (
+                mapping_start,
+                mapping_start + mapping.extent,
+                mapping.fragment_id.clone(),
+            )
+        };
+
+        if offset == mapping_start || offset == mapping_end {
+            return Ok(());
+        }
+
+        let old_fragments = self.fragments.clone();
+        let mut cursor = old_fragments.cursor();
+        let mut updated_fragments = cursor.build_prefix(&fragment_id, SeekBias::Left);
+        let prev_fragment = cursor.prev_item().unwrap();
+        let fragment = cursor.item().unwrap();
+        let mut prefix = fragment.clone();
+        let mut suffix = fragment.clone();
+        prefix.end_offset = offset;
+        prefix.id = FragmentId::between(&prev_fragment.id, &fragment.id);
+        suffix.start_offset = offset;
+        updated_fragments.push(prefix.clone());
+        updated_fragments.push(suffix.clone());
+        cursor.next();
+        updated_fragments.push_tree(cursor.build_suffix());
+        self.fragments = updated_fragments;
+
+        let mut updated_split_tree;
+        {
+            let split_tree = self.insertions.get(&insertion_id).unwrap();
+            let mut cursor = split_tree.cursor();
+            updated_split_tree =
+                cursor.build_prefix(&InsertionOffset(mapping_start), SeekBias::Right);
+            updated_split_tree.push(FragmentMapping {
+                extent: offset - mapping_start,
+                fragment_id: prefix.id,
+            });
+            updated_split_tree.push(FragmentMapping {
+                extent: mapping_end - offset,
+                fragment_id: suffix.id,
+            });
+            cursor.next();
+            updated_split_tree.push_tree(cursor.build_suffix());
+        }
+        self.insertions.insert(insertion_id, updated_split_tree);
+        Ok(())
+    }
+
+    fn fragment_id_for_insertion_offset(
+        &self,
+        insertion_id: ChangeId,
+        offset: usize,
+    ) -> Result<FragmentId> {
+        let split_tree = self.insertions
+            .get(&insertion_id)
+            .ok_or(Error::InvalidOperation)?;
+        let mut cursor = split_tree.cursor();
+        cursor.seek(&InsertionOffset(offset), SeekBias::Left);
+        cursor
+            .item()
+            .map(|mapping| mapping.fragment_id.clone())
+            .ok_or(Error::InvalidOperation)
     }
 
     fn splice_fragments(
@@ -226,7 +1057,9 @@ impl Buffer {
         change_id: ChangeId,
         old_range: Range<usize>,
         mut new_text: Option<Text>,
-    ) {
+    ) -> Operation {
+        let mut inserted_text = None;
+        let mut deleted_text = Vec::new();
         let old_fragments = self.fragments.clone();
         let mut cursor = old_fragments.cursor();
         let mut updated_fragments =
@@ -256,21 +1089,22 @@ impl Buffer {
             });
 
             before_range.map(|fragment| inserted_fragments.push(fragment));
-            insertion.map(|fragment| inserted_fragments.push(fragment));
+            insertion.map(|fragment| {
+                inserted_text = Some(TextInsertion::from(&fragment));
+                inserted_fragments.push(fragment);
+            });
             within_range.map(|mut fragment| {
                 fragment.deletions.insert(change_id.clone());
+                deleted_text.push(TextDeletion::from(&fragment));
                 inserted_fragments.push(fragment);
             });
             after_range.map(|fragment| inserted_fragments.push(fragment));
             cursor.next();
         } else {
             new_text.take().map(|new_text| {
-                inserted_fragments.push(self.build_insertion(
-                    change_id,
-                    prev_fragment,
-                    None,
-                    new_text,
-                ));
+                let fragment = self.build_insertion(change_id, prev_fragment, None, new_text);
+                inserted_text = Some(TextInsertion::from(&fragment));
+                inserted_fragments.push(fragment);
             });
         }
 
@@ -290,12 +1124,14 @@ impl Buffer {
                     self.split_fragment(prev_fragment, cur_fragment, fragment_start, &old_range);
                 let mut within_range = within_range.unwrap();
                 within_range.deletions.insert(change_id.clone());
+                deleted_text.push(TextDeletion::from(&within_range));
                 inserted_fragments.push(within_range);
                 inserted_fragments.push(after_range.unwrap());
             } else {
                 let mut fragment = cur_fragment.clone();
                 if fragment.is_visible() {
                     fragment.deletions.insert(change_id.clone());
+                    deleted_text.push(TextDeletion::from(&fragment));
                 }
                 inserted_fragments.push(fragment)
             }
@@ -306,6 +1142,13 @@ impl Buffer {
         updated_fragments.extend(inserted_fragments);
         updated_fragments.push_tree(cursor.build_suffix());
         self.fragments = updated_fragments;
+
+        Operation::Edit {
+            id: change_id,
+            lamport_timestamp: self.lamport_clock,
+            insertion: inserted_text,
+            deletions: deleted_text,
+        }
     }
 
     fn split_fragment(
@@ -359,7 +1202,8 @@ impl Buffer {
 
                 if let Some(ref fragment) = within_range {
                     updated_split_tree.push(FragmentMapping {
-                        extent: range.end - range.start,
+                        extent: cmp::min(range.end, fragment_end)
+                            - cmp::max(range.start, fragment_start),
                         fragment_id: fragment.id.clone(),
                     })
                 }
@@ -574,7 +1418,23 @@ impl Buffer {
         }
     }
 
-    fn offset_for_point(&self, point: Point) -> Result<usize> {
+    fn point_for_offset(&self, offset: usize) -> Result<Point> {
+        if offset == self.len() {
+            return Ok(self.max_point());
+        }
+
+        let mut fragments_cursor = self.fragments.cursor();
+        fragments_cursor.seek(&CharacterCount(offset), SeekBias::Right);
+        fragments_cursor
+            .item()
+            .ok_or(Error::OffsetOutOfRange)
+            .and_then(|fragment| {
+                let overshoot = offset - fragments_cursor.start::<CharacterCount>().0;
+                Ok(fragments_cursor.start::<Point>() + &fragment.point_for_offset(overshoot)?)
+            })
+    }
+
+    pub fn offset_for_point(&self, point: Point) -> Result<usize> {
         let cached_offset = {
             let offset_cache = self.offset_cache.try_borrow().ok();
             offset_cache
@@ -620,6 +1480,43 @@ impl Buffer {
     }
 }
 
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::IoError(error)
+    }
+}
+
+impl Default for IndentSettings {
+    fn default() -> Self {
+        IndentSettings {
+            soft_tabs: true,
+            tab_length: 4,
+            increase_indent_tokens: vec!["{".into(), "[".into(), "(".into()],
+            decrease_indent_tokens: vec!["}".into(), "]".into(), ")".into()],
+        }
+    }
+}
+
+impl DiskState {
+    fn new(metadata: &fs::Metadata, contents: &[u8]) -> Self {
+        DiskState {
+            modified: metadata.modified().ok(),
+            content_hash: hash_contents(contents),
+        }
+    }
+}
+
+impl History {
+    fn new() -> Self {
+        History {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            current_transaction: None,
+            transaction_depth: 0,
+        }
+    }
+}
+
 impl Point {
     pub fn new(row: u32, column: u32) -> Self {
         Point { row, column }
@@ -688,9 +1585,29 @@ impl Ord for Point {
         a.cmp(&b)
     }
 }
+impl Snapshot {
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn max_point(&self) -> Point {
+        self.fragments.len::<Point>()
+    }
+
+    pub fn line(&self, row: u32) -> Result<Vec<u16>> {
+        if row > self.max_point().row {
+            return Err(Error::OffsetOutOfRange);
+        }
+
+        Ok(Iter::starting_at_row(&self.fragments, row)
+            .take_while(|c| *c != b'\n' as u16)
+            .collect())
+    }
+}
+
 impl<'a> Iter<'a> {
-    fn new(buffer: &'a Buffer) -> Self {
-        let mut fragment_cursor = buffer.fragments.cursor();
+    fn new(fragments: &'a Tree<Fragment>) -> Self {
+        let mut fragment_cursor = fragments.cursor();
         fragment_cursor.seek(&CharacterCount(0), SeekBias::Right);
         Self {
             fragment_cursor,
@@ -698,8 +1615,8 @@ impl<'a> Iter<'a> {
         }
     }
 
-    fn starting_at_row(buffer: &'a Buffer, target_row: u32) -> Self {
-        let mut fragment_cursor = buffer.fragments.cursor();
+    fn starting_at_row(fragments: &'a Tree<Fragment>, target_row: u32) -> Self {
+        let mut fragment_cursor = fragments.cursor();
         fragment_cursor.seek(
             &Point {
                 row: target_row,
@@ -712,9 +1629,15 @@ impl<'a> Iter<'a> {
         if let Some(fragment) = fragment_cursor.item() {
             let fragment_start_row = fragment_cursor.start::<Point>().row;
             if target_row != fragment_start_row {
-                let target_row_within_fragment = target_row - fragment_start_row - 1;
-                fragment_offset = fragment.insertion.text.newline_offsets
-                    [target_row_within_fragment as usize] + 1;
+                // Fragments can start in the middle of their insertion's text, so we skip the
+                // newlines that precede the fragment when looking up the target row's start.
+                let text = &fragment.insertion.text;
+                let newlines_before_fragment = text.point_for_offset(fragment.start_offset)
+                    .unwrap()
+                    .row;
+                let newline_index = newlines_before_fragment + target_row - fragment_start_row - 1;
+                fragment_offset =
+                    text.newline_offsets[newline_index as usize] + 1 - fragment.start_offset;
             }
         }
 
@@ -816,6 +1739,18 @@ impl Text {
     }
 }
 
+impl Serialize for Text {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        self.code_units.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+        Ok(Text::new(Vec::deserialize(deserializer)?))
+    }
+}
+
 impl<'a> From<&'a str> for Text {
     fn from(s: &'a str) -> Self {
         Self::new(s.encode_utf16().collect())
@@ -937,6 +1872,26 @@ impl Fragment {
     }
 }
 
+impl<'a> From<&'a Fragment> for TextInsertion {
+    fn from(fragment: &'a Fragment) -> Self {
+        TextInsertion {
+            parent_id: fragment.insertion.parent_id,
+            offset_in_parent: fragment.insertion.offset_in_parent,
+            text: fragment.insertion.text.clone(),
+        }
+    }
+}
+
+impl<'a> From<&'a Fragment> for TextDeletion {
+    fn from(fragment: &'a Fragment) -> Self {
+        TextDeletion {
+            insertion_id: fragment.insertion.id,
+            start_offset: fragment.start_offset,
+            end_offset: fragment.end_offset,
+        }
+    }
+}
+
 impl tree::Item for Fragment {
     type Summary = FragmentSummary;
 
@@ -1051,6 +2006,18 @@ impl AddAssign for InsertionOffset {
     }
 }
 
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    fs::File::open(path)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+fn hash_contents(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
 fn find_insertion_index<T: Ord>(v: &Vec<T>, x: &T) -> usize {
     match v.binary_search(x) {
         Ok(index) => index,
@@ -1063,7 +2030,9 @@ mod tests {
     extern crate rand;
 
     use super::*;
+    use serde_json;
     use std::cmp::Ordering;
+    use test_helpers::{read_to_string, temp_dir, write_file};
 
     #[test]
     fn splice() {
@@ -1082,6 +2051,24 @@ mod tests {
         assert_eq!(buffer.to_string(), "ghiamnoef");
     }
 
+    #[test]
+    fn text_for_range() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "abc\ndef\nghi");
+        let text = |start: (u32, u32), end: (u32, u32)| {
+            let range = Point::new(start.0, start.1)..Point::new(end.0, end.1);
+            String::from_utf16_lossy(&buffer.text_for_range(range).unwrap())
+        };
+        assert_eq!(text((0, 1), (0, 3)), "bc");
+        assert_eq!(text((0, 2), (2, 1)), "c\ndef\ng");
+        assert_eq!(text((1, 0), (2, 0)), "def\n");
+        assert_eq!(text((2, 3), (2, 3)), "");
+        assert_eq!(
+            buffer.text_for_range(Point::new(3, 0)..Point::new(3, 0)),
+            Err(Error::OffsetOutOfRange)
+        );
+    }
+
     #[test]
     fn random_splice() {
         use self::rand::{Rng, SeedableRng, StdRng};
@@ -1120,6 +2107,325 @@ mod tests {
         }
     }
 
+    #[test]
+    fn replication() {
+        let mut buffer_1 = Buffer::new(1);
+        let mut buffer_2 = Buffer::new(2);
+        let op_1 = buffer_1.splice(0..0, "abc").unwrap();
+        let op_2 = buffer_1.splice(1..2, "def").unwrap();
+
+        // Operations that arrive before the insertions they depend on are deferred.
+        buffer_2.apply_ops(vec![op_2.clone()]).unwrap();
+        assert_eq!(buffer_2.to_string(), "");
+        buffer_2.apply_ops(vec![op_1]).unwrap();
+        assert_eq!(buffer_2.to_string(), "adefc");
+
+        // Concurrent insertions at the same location are ordered identically on both replicas.
+        let op_3 = buffer_1.splice(2..2, "ghi").unwrap();
+        let op_4 = buffer_2.splice(2..4, "jkl").unwrap();
+        buffer_1.apply_ops(vec![op_4]).unwrap();
+        buffer_2.apply_ops(vec![op_3]).unwrap();
+        assert_eq!(buffer_1.to_string(), buffer_2.to_string());
+        assert_eq!(buffer_1.to_string(), "adjklghic");
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "abc");
+        buffer.splice(1..2, "de");
+
+        buffer.start_transaction();
+        buffer.splice(0..1, "");
+        buffer.splice(3..3, "fg");
+        buffer.end_transaction();
+        assert_eq!(buffer.to_string(), "decfg");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "adec");
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "abc");
+        buffer.redo().unwrap();
+        assert_eq!(buffer.to_string(), "adec");
+        buffer.undo().unwrap();
+        buffer.undo().unwrap();
+        assert_eq!(buffer.to_string(), "");
+        assert!(buffer.undo().is_none());
+
+        buffer.redo().unwrap();
+        buffer.redo().unwrap();
+        buffer.redo().unwrap();
+        assert_eq!(buffer.to_string(), "decfg");
+        assert!(buffer.redo().is_none());
+
+        // Making a new change clears the redo stack.
+        buffer.undo().unwrap();
+        buffer.splice(0..0, "h");
+        assert_eq!(buffer.to_string(), "hadec");
+        assert!(buffer.redo().is_none());
+    }
+
+    #[test]
+    fn undo_concurrent_edits() {
+        let mut buffer_1 = Buffer::new(1);
+        let mut buffer_2 = Buffer::new(2);
+        let op_1 = buffer_1.splice(0..0, "abcdef").unwrap();
+        buffer_2.apply_ops(vec![op_1]).unwrap();
+
+        // Undoing a deletion that was concurrently extended by another replica only restores the
+        // text deleted by the undone change.
+        let op_2 = buffer_1.splice(1..3, "").unwrap();
+        let op_3 = buffer_2.splice(2..5, "X").unwrap();
+        buffer_1.apply_ops(vec![op_3]).unwrap();
+        buffer_2.apply_ops(vec![op_2]).unwrap();
+        assert_eq!(buffer_1.to_string(), "aXf");
+        assert_eq!(buffer_2.to_string(), "aXf");
+
+        let (_, op_4) = buffer_1.undo().unwrap();
+        buffer_2.apply_ops(vec![op_4]).unwrap();
+        assert_eq!(buffer_1.to_string(), "abXf");
+        assert_eq!(buffer_2.to_string(), "abXf");
+
+        let (_, op_5) = buffer_2.undo().unwrap();
+        buffer_1.apply_ops(vec![op_5]).unwrap();
+        assert_eq!(buffer_1.to_string(), "abcdef");
+        assert_eq!(buffer_2.to_string(), "abcdef");
+    }
+
+    #[test]
+    fn random_concurrent_edits() {
+        use self::rand::{Rng, SeedableRng, StdRng};
+
+        for seed in 0..100 {
+            println!("{:?}", seed);
+            let mut rng = StdRng::from_seed(&[seed]);
+
+            let replica_count = rng.gen_range(2, 5);
+            let mut buffers = (1..replica_count + 1)
+                .map(Buffer::new)
+                .collect::<Vec<_>>();
+            let mut inboxes = vec![Vec::new(); replica_count];
+
+            for _i in 0..50 {
+                let replica_index = rng.gen_range(0, replica_count);
+                if rng.gen_weighted_bool(3) {
+                    let mut ops = inboxes[replica_index].split_off(0);
+                    rng.shuffle(&mut ops);
+                    buffers[replica_index].apply_ops(ops).unwrap();
+                } else if rng.gen_weighted_bool(4) {
+                    let buffer = &mut buffers[replica_index];
+                    let result = if rng.gen() {
+                        buffer.undo()
+                    } else {
+                        buffer.redo()
+                    };
+                    if let Some((_, op)) = result {
+                        for (index, inbox) in inboxes.iter_mut().enumerate() {
+                            if index != replica_index {
+                                inbox.push(op.clone());
+                            }
+                        }
+                    }
+                } else {
+                    let buffer = &mut buffers[replica_index];
+                    let end = rng.gen_range::<usize>(0, buffer.len() + 1);
+                    let start = rng.gen_range::<usize>(0, end + 1);
+                    let new_text_len = rng.gen_range(0, 5);
+                    let new_text = rng.gen_ascii_chars()
+                        .take(new_text_len)
+                        .collect::<String>();
+                    if let Some(op) = buffer.splice(start..end, new_text.as_str()) {
+                        // Ensure operations survive a round-trip over the wire.
+                        let op: Operation =
+                            serde_json::from_str(&serde_json::to_string(&op).unwrap()).unwrap();
+                        for (index, inbox) in inboxes.iter_mut().enumerate() {
+                            if index != replica_index {
+                                inbox.push(op.clone());
+                            }
+                        }
+                    }
+                }
+            }
+
+            for (buffer, inbox) in buffers.iter_mut().zip(inboxes.iter_mut()) {
+                let mut ops = inbox.split_off(0);
+                rng.shuffle(&mut ops);
+                buffer.apply_ops(ops).unwrap();
+                assert!(buffer.deferred_ops.is_empty());
+            }
+
+            let text = buffers[0].to_string();
+            for buffer in &buffers[1..] {
+                assert_eq!(buffer.to_string(), text);
+            }
+        }
+    }
+
+    #[test]
+    fn find_all() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "αβ ab\nxab a b");
+        let regex = Regex::new("a ?b").unwrap();
+        assert_eq!(buffer.find_all(&regex), vec![3..5, 7..9, 10..13]);
+        assert_eq!(buffer.find_all(&Regex::new("x*").unwrap()), vec![6..7]);
+    }
+
+    #[test]
+    fn replace_all() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "let α = f(a, b);\nlet c = f(d, e);");
+        let regex = Regex::new(r"f\((\w+), (\w+)\)").unwrap();
+        assert_eq!(
+            buffer.find_replacements(&regex, "g($2, $1)"),
+            vec![(8..15, "g(b, a)".to_string()), (25..32, "g(e, d)".to_string())]
+        );
+
+        assert_eq!(buffer.replace_all(&regex, "g($2, $1)"), 2);
+        assert_eq!(buffer.to_string(), "let α = g(b, a);\nlet c = g(e, d);");
+
+        // Replacing all matches is a single undoable step.
+        buffer.undo();
+        assert_eq!(buffer.to_string(), "let α = f(a, b);\nlet c = f(d, e);");
+        assert_eq!(buffer.replace_all(&Regex::new("z").unwrap(), "y"), 0);
+        assert!(buffer.redo().is_some());
+        assert_eq!(buffer.to_string(), "let α = g(b, a);\nlet c = g(e, d);");
+    }
+
+    #[test]
+    fn edits_since() {
+        let mut buffer = Buffer::new(1);
+        let op = buffer.splice(0..0, "abc\ndef").unwrap();
+        let version = buffer.current_version();
+        buffer.splice(2..5, "x\ny\nz");
+        assert_eq!(
+            buffer.edits_since(version),
+            vec![Edit {
+                old_range: Point::new(0, 2)..Point::new(1, 1),
+                new_range: Point::new(0, 2)..Point::new(2, 1),
+                old_offset_range: 2..5,
+                new_offset_range: 2..7,
+            }]
+        );
+        assert!(buffer.edits_since(buffer.current_version()).is_empty());
+
+        // Undoing hides the inserted text and restores the deleted text, one fragment at a time.
+        let version = buffer.current_version();
+        buffer.undo();
+        assert_eq!(buffer.to_string(), "abc\ndef");
+        assert_eq!(
+            buffer.edits_since(version),
+            vec![
+                Edit {
+                    old_range: Point::new(0, 2)..Point::new(2, 1),
+                    new_range: Point::new(0, 2)..Point::new(0, 2),
+                    old_offset_range: 2..7,
+                    new_offset_range: 2..2,
+                },
+                Edit {
+                    old_range: Point::new(0, 2)..Point::new(0, 2),
+                    new_range: Point::new(0, 2)..Point::new(1, 1),
+                    old_offset_range: 2..2,
+                    new_offset_range: 2..5,
+                },
+            ]
+        );
+
+        // Remote insertions are reported once they're integrated.
+        let mut remote_buffer = Buffer::new(2);
+        let version = remote_buffer.current_version();
+        remote_buffer.apply_ops(vec![op]).unwrap();
+        assert_eq!(
+            remote_buffer.edits_since(version),
+            vec![Edit {
+                old_range: Point::new(0, 0)..Point::new(0, 0),
+                new_range: Point::new(0, 0)..Point::new(1, 3),
+                old_offset_range: 0..0,
+                new_offset_range: 0..7,
+            }]
+        );
+    }
+
+    #[test]
+    fn load_and_save() {
+        let dir = temp_dir("buffer");
+        let path = dir.join("file.txt");
+        write_file(&path, "abc\r\ndef\r\n");
+
+        let mut buffer = Buffer::load(1, &path).unwrap();
+        assert_eq!(buffer.to_string(), "abc\ndef\n");
+        assert_eq!(buffer.path(), Some(path.as_path()));
+        assert!(!buffer.is_dirty());
+
+        // Loading isn't undoable.
+        assert!(buffer.undo().is_none());
+
+        buffer.splice(3..3, "\nghi");
+        assert!(buffer.is_dirty());
+        buffer.save().unwrap();
+        assert!(!buffer.is_dirty());
+        assert_eq!(read_to_string(&path), "abc\r\nghi\r\ndef\r\n");
+
+        // Files with mixed line endings are saved verbatim.
+        write_file(&path, "abc\r\ndef\n");
+        let mut buffer = Buffer::load(1, &path).unwrap();
+        buffer.splice(0..0, "\n");
+        buffer.save().unwrap();
+        assert_eq!(read_to_string(&path), "\nabc\r\ndef\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn save_after_external_modification() {
+        let dir = temp_dir("buffer");
+        let path = dir.join("file.txt");
+        write_file(&path, "abc");
+
+        let mut buffer = Buffer::load(1, &path).unwrap();
+        buffer.splice(3..3, "def");
+
+        // Writing identical contents doesn't count as a modification.
+        write_file(&path, "abc");
+        buffer.save().unwrap();
+        assert_eq!(read_to_string(&path), "abcdef");
+
+        write_file(&path, "modified elsewhere");
+        buffer.splice(0..0, "ghi");
+        match buffer.save() {
+            Err(SaveError::ModifiedOnDisk) => {}
+            result => panic!("Expected a conflict, got {:?}", result),
+        }
+        assert_eq!(read_to_string(&path), "modified elsewhere");
+        assert!(buffer.is_dirty());
+
+        buffer.save_overwriting().unwrap();
+        assert_eq!(read_to_string(&path), "ghiabcdef");
+        assert!(!buffer.is_dirty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_through_symlink() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir("buffer");
+        let path = dir.join("file.txt");
+        let link_path = dir.join("link.txt");
+        write_file(&path, "abc");
+        symlink(&path, &link_path).unwrap();
+
+        let mut buffer = Buffer::load(1, &link_path).unwrap();
+        buffer.splice(3..3, "def");
+        buffer.save().unwrap();
+        assert!(fs::symlink_metadata(&link_path).unwrap().file_type().is_symlink());
+        assert_eq!(read_to_string(&path), "abcdef");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     fn test_len_for_row() {
         let mut buffer = Buffer::new(1);
@@ -1177,6 +2483,15 @@ mod tests {
 
         let iter = buffer.iter_starting_at_row(5);
         assert_eq!(String::from_utf16_lossy(&iter.collect::<Vec<u16>>()), "");
+
+        // Rows inside a fragment that starts in the middle of its insertion.
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "ab\ncd\nef\ngh");
+        buffer.splice(4..4, "X");
+        let iter = buffer.iter_starting_at_row(2);
+        assert_eq!(String::from_utf16_lossy(&iter.collect::<Vec<u16>>()), "ef\ngh");
+        let iter = buffer.iter_starting_at_row(3);
+        assert_eq!(String::from_utf16_lossy(&iter.collect::<Vec<u16>>()), "gh");
     }
 
     #[test]
@@ -1,26 +1,53 @@
-use futures::{Poll, Stream};
+use futures::{Async, Poll, Stream};
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::cmp::{self, Ordering};
+use std::collections::HashMap;
 use std::mem;
 use std::ops::Range;
+use std::sync::Arc;
+use regex::Regex;
 use serde_json;
-use notify_cell::NotifyCell;
-use buffer::{Anchor, Buffer, Point};
+use notify_cell::{NotifyCell, NotifyCellObserver};
+use clipboard::{self, Clip, Clipboard};
+use buffer::{Anchor, Buffer, Point, SaveError, TransactionId, Version};
+use display_map::{Bias, DisplayMap, DisplayPoint};
+use highlight_map::HighlightMap;
+use indentation;
 use movement;
-use window::{View, WindowHandle};
+use tokenizer::{self, Token, Tokenizer};
+use window::{Command, View, WindowHandle};
 
 pub struct BufferView {
     buffer: Rc<RefCell<Buffer>>,
+    buffer_updates: NotifyCellObserver<Version>,
+    display_map: RefCell<DisplayMap>,
+    highlight_map: RefCell<HighlightMap>,
+    clipboard: Clipboard,
+    window_handle: Option<WindowHandle>,
     updates: NotifyCell<()>,
     dropped: NotifyCell<bool>,
     selections: Vec<Selection>,
+    selection_history: HashMap<TransactionId, (Vec<Selection>, Vec<Selection>)>,
+    syntax_selection_stack: Vec<Vec<Selection>>,
+    syntax_selections: Vec<Selection>,
+    find_regex: Option<Regex>,
+    find_matches: Vec<Range<Anchor>>,
     height: f64,
     width: f64,
     line_height: f64,
+    char_width: f64,
     scroll_top: f64,
+    scroll_left: f64,
+    autoscroll_margin: u32,
+    soft_wrap: bool,
+    soft_wrap_column: Option<u32>,
+    save_conflict: bool,
+    mouse_selection: Option<MouseSelection>,
+    focused: bool,
 }
 
+#[derive(Clone)]
 struct Selection {
     start: Anchor,
     end: Anchor,
@@ -28,10 +55,33 @@ struct Selection {
     goal_column: Option<u32>,
 }
 
-#[derive(Debug, Eq, PartialEq, Serialize)]
+/// A selection being built by pressing and dragging the mouse. The selection always contains the
+/// origin range, which is the character, word or line under the initial click.
+struct MouseSelection {
+    other_selections: Vec<Selection>,
+    origin: Range<Anchor>,
+    granularity: SelectionGranularity,
+}
+
+#[derive(Clone, Copy)]
+enum SelectionGranularity {
+    Character,
+    Word,
+    Line,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Modifiers {
+    alt: bool,
+    shift: bool,
+}
+
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 struct SelectionProps {
     pub start: Point,
     pub end: Point,
+    #[serde(default)]
     pub reversed: bool,
 }
 
@@ -39,20 +89,100 @@ struct SelectionProps {
 #[serde(tag = "type")]
 enum BufferViewAction {
     UpdateScrollTop { delta: f64 },
+    UpdateScrollLeft { delta: f64 },
     SetDimensions { width: u64, height: u64 },
+    SetCharWidth { char_width: f64 },
+    ToggleSoftWrap,
+    MouseDown {
+        x: f64,
+        y: f64,
+        click_count: usize,
+        #[serde(default)]
+        modifiers: Modifiers,
+    },
+    MouseDrag { x: f64, y: f64 },
+    MouseUp,
     Edit { text: String },
     MoveUp,
     MoveDown,
     MoveLeft,
     MoveRight,
+    PageUp,
+    PageDown,
+    SelectPageUp,
+    SelectPageDown,
+    SelectUp,
+    SelectDown,
+    SelectLeft,
+    SelectRight,
+    AddSelection { start: Point, end: Point },
+    AddSelectionAbove,
+    AddSelectionBelow,
+    SelectAll,
+    SelectLine,
+    SelectWord,
+    SetSelections { ranges: Vec<SelectionProps> },
+    ClearSelections,
+    Undo,
+    Redo,
+    Save,
+    ForceSave,
+    SelectNextMatch,
+    SelectPreviousMatch,
+    SelectAllMatches,
+    Backspace,
+    Delete,
+    DeleteToPreviousWordBoundary,
+    DeleteToNextWordBoundary,
+    DeleteLine,
+    DeleteToEndOfLine,
+    MoveToPreviousWordBoundary,
+    SelectToPreviousWordBoundary,
+    MoveToNextWordBoundary,
+    SelectToNextWordBoundary,
+    MoveToPreviousSubwordBoundary,
+    SelectToPreviousSubwordBoundary,
+    MoveToNextSubwordBoundary,
+    SelectToNextSubwordBoundary,
+    MoveToBeginningOfLine,
+    SelectToBeginningOfLine,
+    MoveToEndOfLine,
+    SelectToEndOfLine,
+    MoveToBeginningOfBuffer,
+    SelectToBeginningOfBuffer,
+    MoveToEndOfBuffer,
+    SelectToEndOfBuffer,
+    MoveToPreviousParagraph,
+    SelectToPreviousParagraph,
+    MoveToNextParagraph,
+    SelectToNextParagraph,
+    Fold,
+    Unfold,
+    FoldAll,
+    UnfoldAll,
+    ExpandSelection,
+    ShrinkSelection,
+    Copy,
+    Cut,
+    Paste,
+    Indent,
+    Outdent,
+    AutoIndentSelection,
 }
 
 impl BufferView {
     pub fn new(buffer: Rc<RefCell<Buffer>>) -> Self {
         let selections;
+        let display_map;
+        let highlight_map;
+        let buffer_updates;
 
         {
             let buffer = buffer.borrow();
+            buffer_updates = buffer.version.observe();
+            display_map = DisplayMap::new(&buffer, buffer.indent_settings().tab_length, None);
+            let tokenizer = buffer.path().and_then(tokenizer::tokenizer_for_path);
+            highlight_map = HighlightMap::new(&buffer, tokenizer);
             selections = vec![
                 Selection {
                     start: buffer.anchor_before_offset(0).unwrap(),
@@ -65,13 +195,31 @@ impl BufferView {
 
         Self {
             updates: NotifyCell::new(()),
+            buffer_updates,
             buffer,
+            display_map: RefCell::new(display_map),
+            highlight_map: RefCell::new(highlight_map),
+            clipboard: Clipboard::new(),
+            window_handle: None,
             selections,
+            selection_history: HashMap::new(),
+            syntax_selection_stack: Vec::new(),
+            syntax_selections: Vec::new(),
+            find_regex: None,
+            find_matches: Vec::new(),
             dropped: NotifyCell::new(false),
             height: 0.0,
             width: 0.0,
             line_height: 10.0,
+            char_width: 10.0,
             scroll_top: 0.0,
+            scroll_left: 0.0,
+            autoscroll_margin: 2,
+            soft_wrap: false,
+            soft_wrap_column: None,
+            save_conflict: false,
+            mouse_selection: None,
+            focused: false,
         }
     }
 
@@ -83,6 +231,7 @@ impl BufferView {
 
     pub fn set_width(&mut self, width: f64) -> &mut Self {
         self.width = width;
+        self.update_wrap_width();
         self.updated();
         self
     }
@@ -93,79 +242,1044 @@ impl BufferView {
         self
     }
 
+    pub fn set_char_width(&mut self, char_width: f64) -> &mut Self {
+        self.char_width = char_width;
+        self.update_wrap_width();
+        self.updated();
+        self
+    }
+
     pub fn set_scroll_top(&mut self, scroll_top: f64) -> &mut Self {
         self.scroll_top = scroll_top;
         self.updated();
         self
     }
 
-    pub fn edit(&mut self, text: &str) {
-        {
-            let mut buffer = self.buffer.borrow_mut();
-            let mut offset_ranges = Vec::new();
+    pub fn set_scroll_left(&mut self, scroll_left: f64) -> &mut Self {
+        self.scroll_left = scroll_left;
+        self.updated();
+        self
+    }
+
+    /// Sets the number of rows above and below, and columns to either side of the cursor that
+    /// autoscrolling keeps visible when the viewport is large enough.
+    pub fn set_autoscroll_margin(&mut self, autoscroll_margin: u32) -> &mut Self {
+        self.autoscroll_margin = autoscroll_margin;
+        self
+    }
+
+    /// Sets the tab length of the buffer, which determines both the width of tabs and the width
+    /// of a level of indentation.
+    pub fn set_tab_width(&mut self, tab_width: u32) -> &mut Self {
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            let mut indent_settings = buffer.indent_settings().clone();
+            indent_settings.tab_length = tab_width;
+            buffer.set_indent_settings(indent_settings);
+            self.display_map
+                .borrow_mut()
+                .set_tab_width(&buffer, tab_width);
+        }
+        self.updated();
+        self
+    }
+
+    /// Enables or disables wrapping lines that don't fit within the width of the view.
+    pub fn set_soft_wrap(&mut self, soft_wrap: bool) -> &mut Self {
+        self.soft_wrap = soft_wrap;
+        self.update_wrap_width();
+        self.updated();
+        self
+    }
+
+    /// Limits the number of columns at which lines are soft-wrapped, even when the view is wide
+    /// enough to show longer lines.
+    pub fn set_soft_wrap_column(&mut self, soft_wrap_column: Option<u32>) -> &mut Self {
+        self.soft_wrap_column = soft_wrap_column;
+        self.update_wrap_width();
+        self.updated();
+        self
+    }
+
+    pub fn toggle_soft_wrap(&mut self) {
+        let soft_wrap = !self.soft_wrap;
+        self.set_soft_wrap(soft_wrap);
+    }
+
+    fn update_wrap_width(&mut self) {
+        let wrap_width = if self.soft_wrap {
+            let view_columns = if self.width > 0.0 {
+                Some((self.width / self.char_width).floor() as u32)
+            } else {
+                None
+            };
+            match (view_columns, self.soft_wrap_column) {
+                (Some(view_columns), Some(column)) => Some(cmp::min(view_columns, column)),
+                (view_columns, column) => view_columns.or(column),
+            }
+        } else {
+            None
+        };
+
+        let buffer = self.buffer.borrow();
+        self.display_map
+            .borrow_mut()
+            .set_wrap_width(&buffer, wrap_width);
+    }
+
+    /// Replaces the tokenizer used to highlight the buffer, which is otherwise chosen based on the
+    /// buffer's path.
+    pub fn set_tokenizer(&mut self, tokenizer: Option<Arc<Tokenizer>>) -> &mut Self {
+        {
+            let buffer = self.buffer.borrow();
+            self.highlight_map
+                .borrow_mut()
+                .set_tokenizer(&buffer, tokenizer);
+        }
+        self.updated();
+        self
+    }
+
+    /// Shares the given clipboard with this view, which otherwise copies to a clipboard of its own.
+    pub fn set_clipboard(&mut self, clipboard: Clipboard) -> &mut Self {
+        self.clipboard = clipboard;
+        self
+    }
+
+    /// Returns the display map after bringing it up to date with any edits made to the buffer,
+    /// including those made outside of this view.
+    fn display_map<'a>(&'a self) -> Ref<'a, DisplayMap> {
+        {
+            let buffer = self.buffer.borrow();
+            let mut display_map = self.display_map.borrow_mut();
+            display_map.sync(&buffer);
+            display_map.set_tab_width(&buffer, buffer.indent_settings().tab_length);
+        }
+        self.display_map.borrow()
+    }
+
+    /// Replaces each selection with the given text. Typing a newline also indents the new line
+    /// according to the buffer's indent settings.
+    pub fn edit(&mut self, text: &str) {
+        let selections_before_edit = self.selections.clone();
+        if text == "\n" {
+            self.insert_newline(selections_before_edit);
+        } else {
+            self.edit_selections(text, selections_before_edit);
+        }
+    }
+
+    fn insert_newline(&mut self, selections_before_edit: Vec<Selection>) {
+        let edits = {
+            let buffer = self.buffer.borrow();
+            let indent_settings = buffer.indent_settings();
+            self.selections
+                .iter()
+                .map(|selection| {
+                    let start = buffer.point_for_anchor(&selection.start).unwrap();
+                    let end = buffer.point_for_anchor(&selection.end).unwrap();
+                    let previous_line = buffer.line(start.row).unwrap();
+                    let line = buffer.line(end.row).unwrap();
+                    let indentation = indentation::suggested_indentation(
+                        &previous_line[..start.column as usize],
+                        &line[end.column as usize..],
+                        indent_settings,
+                    );
+
+                    let mut text = vec![b'\n' as u16];
+                    text.extend(indentation::whitespace(indentation, indent_settings));
+                    let start = buffer.offset_for_point(start).unwrap();
+                    let end = buffer.offset_for_point(end).unwrap();
+                    (start..end, text, start)
+                })
+                .collect()
+        };
+        self.apply_edits(edits, selections_before_edit);
+    }
+
+    /// Indents every line touched by a selection by a level, skipping blank lines.
+    pub fn indent(&mut self) {
+        self.edit_selected_rows(|buffer, row| {
+            let line = buffer.line(row).unwrap();
+            if indentation::is_blank(&line) {
+                None
+            } else {
+                let indent_settings = buffer.indent_settings();
+                let indentation = if indent_settings.soft_tabs {
+                    vec![b' ' as u16; indent_settings.tab_length as usize]
+                } else {
+                    vec![b'\t' as u16]
+                };
+                Some((0..0, indentation))
+            }
+        });
+    }
+
+    /// Removes a level of indentation from every line touched by a selection.
+    pub fn outdent(&mut self) {
+        self.edit_selected_rows(|buffer, row| {
+            let line = buffer.line(row).unwrap();
+            let tab_length = buffer.indent_settings().tab_length as usize;
+            let len = if line.first() == Some(&(b'\t' as u16)) {
+                1
+            } else {
+                line.iter()
+                    .take(tab_length)
+                    .take_while(|&&code_unit| code_unit == b' ' as u16)
+                    .count()
+            };
+            if len == 0 {
+                None
+            } else {
+                Some((0..len as u32, Vec::new()))
+            }
+        });
+    }
+
+    /// Indents every non-blank line touched by a selection according to the line above it.
+    pub fn auto_indent_selection(&mut self) {
+        self.edit_selected_rows(|buffer, row| {
+            let line = buffer.line(row).unwrap();
+            if indentation::is_blank(&line) {
+                return None;
+            }
+
+            let indent_settings = buffer.indent_settings();
+            let indentation = indentation::suggested_indentation_for_row(buffer, row);
+            let whitespace = indentation::whitespace(indentation, indent_settings);
+            let whitespace_len = indentation::leading_whitespace_len(&line);
+            if line[..whitespace_len] == whitespace[..] {
+                None
+            } else {
+                Some((0..whitespace_len as u32, whitespace))
+            }
+        });
+    }
+
+    /// Replaces a range of columns in each row touched by a selection with the text returned by
+    /// `f`, in a single transaction. Rows are edited from top to bottom, so `f` sees the edits
+    /// made to the rows above the one it is given. Rows touched by several selections are only
+    /// edited once.
+    fn edit_selected_rows<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Buffer, u32) -> Option<(Range<u32>, Vec<u16>)>,
+    {
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            let mut rows = Vec::new();
+            for selection in &self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                let end_row = if end.row > start.row && end.column == 0 {
+                    end.row
+                } else {
+                    end.row + 1
+                };
+                for row in start.row..end_row {
+                    if rows.last() < Some(&row) {
+                        rows.push(row);
+                    }
+                }
+            }
+
+            buffer.start_transaction();
+            for row in rows {
+                if let Some((columns, text)) = f(&buffer, row) {
+                    let start = buffer
+                        .offset_for_point(Point::new(row, columns.start))
+                        .unwrap();
+                    let end = buffer.offset_for_point(Point::new(row, columns.end)).unwrap();
+                    buffer.splice(start..end, text);
+                }
+            }
+            if let Some(transaction_id) = buffer.end_transaction() {
+                self.selection_history.insert(
+                    transaction_id,
+                    (self.selections.clone(), self.selections.clone()),
+                );
+            }
+        }
+
+        self.update_find_matches();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    pub fn backspace(&mut self) {
+        self.delete_to(movement::left);
+    }
+
+    pub fn delete(&mut self) {
+        self.delete_to(movement::right);
+    }
+
+    pub fn delete_to_previous_word_boundary(&mut self) {
+        self.delete_to(movement::prev_word_boundary);
+    }
+
+    pub fn delete_to_next_word_boundary(&mut self) {
+        self.delete_to(movement::next_word_boundary);
+    }
+
+    /// Deletes from each cursor to the end of its line, or deletes the newline if the cursor is
+    /// already at the end of the line.
+    pub fn delete_to_end_of_line(&mut self) {
+        self.delete_to(|buffer, point| {
+            let line_end = Point::new(point.row, buffer.len_for_row(point.row).unwrap());
+            if point == line_end {
+                movement::right(buffer, point)
+            } else {
+                line_end
+            }
+        });
+    }
+
+    /// Deletes every line touched by a selection, including its newline.
+    pub fn delete_line(&mut self) {
+        self.delete_ranges(|buffer, range| {
+            let start_row = range.start.row;
+            let mut end_row = range.end.row;
+            if range.end.column == 0 && end_row > start_row {
+                end_row -= 1;
+            }
+
+            let max_point = buffer.max_point();
+            if end_row < max_point.row {
+                Point::new(start_row, 0)..Point::new(end_row + 1, 0)
+            } else if start_row > 0 {
+                let start_column = buffer.len_for_row(start_row - 1).unwrap();
+                Point::new(start_row - 1, start_column)..max_point
+            } else {
+                Point::new(0, 0)..max_point
+            }
+        });
+    }
+
+    /// Copies the text of each selection to the clipboard. Empty selections copy the entirety of
+    /// their line, including its newline.
+    pub fn copy(&self) {
+        let buffer = self.buffer.borrow();
+        let clips = self.selections
+            .iter()
+            .map(|selection| {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                if start == end {
+                    let range = Self::line_range(&buffer, start.row);
+                    let mut text =
+                        String::from_utf16_lossy(&buffer.text_for_range(range).unwrap());
+                    if !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    Clip {
+                        text,
+                        is_full_line: true,
+                    }
+                } else {
+                    Clip {
+                        text: String::from_utf16_lossy(&buffer.text_for_range(start..end).unwrap()),
+                        is_full_line: false,
+                    }
+                }
+            })
+            .collect();
+        self.clipboard.write(clips);
+    }
+
+    /// Copies the text of each selection to the clipboard and deletes it. Empty selections cut the
+    /// entirety of their line.
+    pub fn cut(&mut self) {
+        self.copy();
+        self.delete_ranges(|buffer, range| {
+            if range.start == range.end {
+                Self::line_range(buffer, range.start.row)
+            } else {
+                range
+            }
+        });
+    }
+
+    /// Replaces each selection with the contents of the clipboard. When there are as many clips as
+    /// selections, each selection is replaced with its own clip. Full lines are pasted above the
+    /// line containing each cursor rather than at the cursor.
+    pub fn paste(&mut self) {
+        let clips = self.clipboard.read();
+        if clips.is_empty() {
+            return;
+        }
+
+        let joined_clip;
+        let clips = if clips.len() == self.selections.len() {
+            clips.iter().collect::<Vec<_>>()
+        } else {
+            joined_clip = clipboard::join(&clips);
+            vec![&joined_clip; self.selections.len()]
+        };
+
+        let selections_before_edit = self.selections.clone();
+        let edits = {
+            let buffer = self.buffer.borrow();
+            self.selections
+                .iter()
+                .zip(clips)
+                .map(|(selection, clip)| {
+                    let start = buffer.offset_for_anchor(&selection.start).unwrap();
+                    let end = buffer.offset_for_anchor(&selection.end).unwrap();
+                    let text = clip.text.encode_utf16().collect::<Vec<_>>();
+                    if clip.is_full_line && start == end {
+                        let row = buffer.point_for_anchor(&selection.start).unwrap().row;
+                        let line_start = buffer.offset_for_point(Point::new(row, 0)).unwrap();
+                        (line_start..line_start, text, start)
+                    } else {
+                        (start..end, text, start)
+                    }
+                })
+                .collect()
+        };
+        self.apply_edits(edits, selections_before_edit);
+    }
+
+    fn line_range(buffer: &Buffer, row: u32) -> Range<Point> {
+        let max_point = buffer.max_point();
+        if row < max_point.row {
+            Point::new(row, 0)..Point::new(row + 1, 0)
+        } else {
+            Point::new(row, 0)..max_point
+        }
+    }
+
+    /// Deletes the text in each non-empty selection, and for each empty selection, deletes the
+    /// text between the cursor and the point returned by `f`.
+    fn delete_to<F: Fn(&Buffer, Point) -> Point>(&mut self, f: F) {
+        self.delete_ranges(|buffer, range| {
+            if range.start == range.end {
+                let point = f(buffer, range.start);
+                cmp::min(point, range.start)..cmp::max(point, range.start)
+            } else {
+                range
+            }
+        });
+    }
+
+    /// Expands each selection to the range returned by `f` and deletes the expanded ranges,
+    /// merging any that overlap. Undoing the deletion restores the original selections.
+    fn delete_ranges<F: Fn(&Buffer, Range<Point>) -> Range<Point>>(&mut self, f: F) {
+        let selections_before_edit = self.selections.clone();
+        {
+            let buffer = self.buffer.borrow();
+            for selection in &mut self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                let range = f(&buffer, start..end);
+                selection.start = buffer.anchor_before_point(range.start).unwrap();
+                selection.end = buffer.anchor_before_point(range.end).unwrap();
+                selection.reversed = false;
+                selection.goal_column = None;
+            }
+        }
+        self.merge_selections();
+        self.edit_selections("", selections_before_edit);
+    }
+
+    fn edit_selections(&mut self, text: &str, selections_before_edit: Vec<Selection>) {
+        let text = text.encode_utf16().collect::<Vec<_>>();
+        let edits = {
+            let buffer = self.buffer.borrow();
+            self.selections
+                .iter()
+                .map(|selection| {
+                    let start = buffer.offset_for_anchor(&selection.start).unwrap();
+                    let end = buffer.offset_for_anchor(&selection.end).unwrap();
+                    (start..end, text.clone(), start)
+                })
+                .collect()
+        };
+        self.apply_edits(edits, selections_before_edit);
+    }
+
+    /// Replaces each range of offsets with its text in a single transaction, placing a cursor
+    /// after the inserted text. The text is inserted before the offset that accompanies it, which
+    /// is where the cursor would have been had the text been there all along.
+    fn apply_edits(
+        &mut self,
+        edits: Vec<(Range<usize>, Vec<u16>, usize)>,
+        selections_before_edit: Vec<Selection>,
+    ) {
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            buffer.start_transaction();
+            for (range, text, _) in edits.iter().rev() {
+                buffer.splice(range.clone(), text.clone());
+            }
+            let transaction_id = buffer.end_transaction();
+
+            let mut delta = 0_isize;
+            self.selections = edits
+                .into_iter()
+                .map(|(range, text, cursor)| {
+                    let anchor = buffer
+                        .anchor_before_offset((cursor as isize + delta) as usize + text.len())
+                        .unwrap();
+                    delta += text.len() as isize - (range.end - range.start) as isize;
+                    Selection {
+                        start: anchor.clone(),
+                        end: anchor,
+                        reversed: false,
+                        goal_column: None,
+                    }
+                })
+                .collect();
+
+            if let Some(transaction_id) = transaction_id {
+                self.selection_history.insert(
+                    transaction_id,
+                    (selections_before_edit, self.selections.clone()),
+                );
+            }
+        }
+
+        self.update_find_matches();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    pub fn undo(&mut self) {
+        let result = self.buffer.borrow_mut().undo();
+        if let Some((transaction_id, _)) = result {
+            if let Some(selections) = self.selection_history.get(&transaction_id) {
+                self.selections = selections.0.clone();
+            }
+            self.update_find_matches();
+            self.autoscroll_to_cursor();
+            self.updated();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let result = self.buffer.borrow_mut().redo();
+        if let Some((transaction_id, _)) = result {
+            if let Some(selections) = self.selection_history.get(&transaction_id) {
+                self.selections = selections.1.clone();
+            }
+            self.update_find_matches();
+            self.autoscroll_to_cursor();
+            self.updated();
+        }
+    }
+
+    /// Saves the buffer unless the file has changed on disk since it was loaded, in which case
+    /// the conflict is rendered so the user can decide whether to overwrite it via `force_save`.
+    pub fn save(&mut self) {
+        let result = self.buffer.borrow_mut().save();
+        self.handle_save_result(result);
+    }
+
+    pub fn force_save(&mut self) {
+        let result = self.buffer.borrow_mut().save_overwriting();
+        self.handle_save_result(result);
+    }
+
+    fn handle_save_result(&mut self, result: Result<(), SaveError>) {
+        match result {
+            Ok(()) => self.save_conflict = false,
+            Err(SaveError::ModifiedOnDisk) => self.save_conflict = true,
+            Err(error) => eprintln!("Error saving buffer: {:?}", error),
+        }
+        self.updated();
+    }
+
+    /// Highlights all matches of the given regex, or clears them if it is `None`. Matches are
+    /// kept up to date as the buffer is edited.
+    pub fn set_find_regex(&mut self, regex: Option<Regex>) {
+        self.find_regex = regex;
+        self.update_find_matches();
+        self.updated();
+    }
+
+    pub fn find_match_count(&self) -> usize {
+        self.find_matches.len()
+    }
+
+    pub fn select_next_match(&mut self) {
+        let next_match = {
+            let buffer = self.buffer.borrow();
+            let offset = buffer
+                .offset_for_anchor(&self.selections.last().unwrap().end)
+                .unwrap();
+            self.find_matches
+                .iter()
+                .find(|range| buffer.offset_for_anchor(&range.start).unwrap() >= offset)
+                .or_else(|| self.find_matches.first())
+                .cloned()
+        };
+
+        if let Some(range) = next_match {
+            self.select_find_match(range);
+        }
+    }
+
+    pub fn select_previous_match(&mut self) {
+        let previous_match = {
+            let buffer = self.buffer.borrow();
+            let offset = buffer
+                .offset_for_anchor(&self.selections.first().unwrap().start)
+                .unwrap();
+            self.find_matches
+                .iter()
+                .rev()
+                .find(|range| buffer.offset_for_anchor(&range.end).unwrap() <= offset)
+                .or_else(|| self.find_matches.last())
+                .cloned()
+        };
+
+        if let Some(range) = previous_match {
+            self.select_find_match(range);
+        }
+    }
+
+    pub fn select_all_matches(&mut self) {
+        if !self.find_matches.is_empty() {
+            self.selections = self.find_matches
+                .iter()
+                .map(|range| Selection {
+                    start: range.start.clone(),
+                    end: range.end.clone(),
+                    reversed: false,
+                    goal_column: None,
+                })
+                .collect();
+            self.updated();
+        }
+    }
+
+    /// Replaces the last selection if it is a find match, expanding capture group references in
+    /// `template`, and then selects the next match.
+    pub fn replace_current_match(&mut self, template: &str) {
+        let regex = match self.find_regex {
+            Some(ref regex) => regex.clone(),
+            None => return,
+        };
+
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            let (start, end) = {
+                let selection = self.selections.last().unwrap();
+                (
+                    buffer.offset_for_anchor(&selection.start).unwrap(),
+                    buffer.offset_for_anchor(&selection.end).unwrap(),
+                )
+            };
+            let replacement = buffer
+                .find_replacements(&regex, template)
+                .into_iter()
+                .find(|&(ref range, _)| range.start == start && range.end == end);
+
+            if let Some((range, text)) = replacement {
+                let selections_before_edit = self.selections.clone();
+                buffer.start_transaction();
+                buffer.splice(range.clone(), text.as_str());
+                let transaction_id = buffer.end_transaction();
+
+                let anchor = buffer
+                    .anchor_before_offset(range.start + text.encode_utf16().count())
+                    .unwrap();
+                self.selections = vec![
+                    Selection {
+                        start: anchor.clone(),
+                        end: anchor,
+                        reversed: false,
+                        goal_column: None,
+                    },
+                ];
+                if let Some(transaction_id) = transaction_id {
+                    self.selection_history.insert(
+                        transaction_id,
+                        (selections_before_edit, self.selections.clone()),
+                    );
+                }
+            }
+        }
+
+        self.update_find_matches();
+        self.select_next_match();
+        self.updated();
+    }
+
+    /// Replaces every find match as a single undoable edit.
+    pub fn replace_all_matches(&mut self, template: &str) {
+        let regex = match self.find_regex {
+            Some(ref regex) => regex.clone(),
+            None => return,
+        };
+
+        {
+            let mut buffer = self.buffer.borrow_mut();
+            buffer.start_transaction();
+            buffer.replace_all(&regex, template);
+            if let Some(transaction_id) = buffer.end_transaction() {
+                self.selection_history.insert(
+                    transaction_id,
+                    (self.selections.clone(), self.selections.clone()),
+                );
+            }
+        }
+
+        self.update_find_matches();
+        self.updated();
+    }
+
+    fn select_find_match(&mut self, range: Range<Anchor>) {
+        self.selections = vec![
+            Selection {
+                start: range.start,
+                end: range.end,
+                reversed: false,
+                goal_column: None,
+            },
+        ];
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    fn update_find_matches(&mut self) {
+        let buffer = self.buffer.borrow();
+        self.find_matches = match self.find_regex {
+            Some(ref regex) => buffer
+                .find_all(regex)
+                .into_iter()
+                .map(|range| {
+                    buffer.anchor_before_offset(range.start).unwrap()
+                        ..buffer.anchor_before_offset(range.end).unwrap()
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+
+    pub fn add_selection(&mut self, start: Point, end: Point) {
+        debug_assert!(start <= end); // TODO: Reverse selection if end < start
+
+        {
+            let buffer = self.buffer.borrow();
+
+            // TODO: Clip points or return a result.
+            let start_anchor = buffer.anchor_before_point(start).unwrap();
+            let end_anchor = buffer.anchor_before_point(end).unwrap();
+            let index = match self.selections
+                .binary_search_by(|probe| buffer.cmp_anchors(&probe.start, &start_anchor).unwrap())
+            {
+                Ok(index) => index,
+                Err(index) => index,
+            };
+            self.selections.insert(
+                index,
+                Selection {
+                    start: start_anchor,
+                    end: end_anchor,
+                    reversed: false,
+                    goal_column: None,
+                },
+            );
+        }
+
+        self.merge_selections();
+        self.updated();
+    }
+
+    pub fn select_all(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            self.selections = vec![
+                Selection {
+                    start: buffer.anchor_before_offset(0).unwrap(),
+                    end: buffer.anchor_before_point(buffer.max_point()).unwrap(),
+                    reversed: false,
+                    goal_column: None,
+                },
+            ];
+        }
+        self.updated();
+    }
+
+    /// Expands each selection to contain the entirety of the lines it touches, including their
+    /// newlines, so that repeating it extends selections by a line at a time.
+    pub fn select_line(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            let max_point = buffer.max_point();
+            for selection in &mut self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                let end = if end.row < max_point.row {
+                    Point::new(end.row + 1, 0)
+                } else {
+                    max_point
+                };
+                selection.start = buffer.anchor_before_point(Point::new(start.row, 0)).unwrap();
+                selection.end = buffer.anchor_before_point(end).unwrap();
+                selection.reversed = false;
+                selection.goal_column = None;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    /// Expands each selection to contain the words touching its start and end.
+    pub fn select_word(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            for selection in &mut self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                let start = movement::word_range(&buffer, start).start;
+                let end = movement::word_range(&buffer, end).end;
+                selection.start = buffer.anchor_before_point(start).unwrap();
+                selection.end = buffer.anchor_before_point(end).unwrap();
+                selection.goal_column = None;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    /// Expands each selection to the smallest syntax node that strictly contains it. The previous
+    /// selections are remembered so that `shrink_selection` can restore them.
+    pub fn expand_selection(&mut self) {
+        let tree = match self.buffer.borrow().syntax_tree() {
+            Some(tree) => tree,
+            None => return,
+        };
+        if !self.selections_match(&self.syntax_selections) {
+            self.syntax_selection_stack.clear();
+        }
+
+        let previous_selections = self.selections.clone();
+        let mut expanded = false;
+        {
+            let buffer = self.buffer.borrow();
+            for selection in &mut self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                let mut node = tree.node_for_range(start..end);
+                while let Some(parent) = node {
+                    let range = parent.range();
+                    if range.start < start || range.end > end {
+                        break;
+                    }
+                    node = parent.parent();
+                }
+
+                if let Some(node) = node {
+                    let range = node.range();
+                    selection.start = buffer.anchor_before_point(range.start).unwrap();
+                    selection.end = buffer.anchor_before_point(range.end).unwrap();
+                    selection.goal_column = None;
+                    expanded = true;
+                }
+            }
+        }
+
+        if expanded {
+            self.syntax_selection_stack.push(previous_selections);
+            self.merge_selections();
+            self.syntax_selections = self.selections.clone();
+            self.autoscroll_to_cursor();
+            self.updated();
+        }
+    }
+
+    /// Restores the selections that preceded the last `expand_selection`, as long as the
+    /// selections haven't changed since.
+    pub fn shrink_selection(&mut self) {
+        if !self.selections_match(&self.syntax_selections) {
+            self.syntax_selection_stack.clear();
+        }
+
+        if let Some(selections) = self.syntax_selection_stack.pop() {
+            self.selections = selections;
+            self.syntax_selections = self.selections.clone();
+            self.autoscroll_to_cursor();
+            self.updated();
+        }
+    }
+
+    fn selections_match(&self, selections: &[Selection]) -> bool {
+        let buffer = self.buffer.borrow();
+        self.selections.len() == selections.len()
+            && self.selections.iter().zip(selections).all(|(a, b)| {
+                buffer.point_for_anchor(&a.start).unwrap()
+                    == buffer.point_for_anchor(&b.start).unwrap()
+                    && buffer.point_for_anchor(&a.end).unwrap()
+                        == buffer.point_for_anchor(&b.end).unwrap()
+            })
+    }
+
+    /// Replaces all selections with the given ranges, which are clipped to the buffer. A range
+    /// whose end precedes its start produces a reversed selection. Since there is always at least
+    /// one selection, passing no ranges leaves the selections unchanged.
+    pub fn set_selections(&mut self, ranges: &[Range<Point>]) {
+        if ranges.is_empty() {
+            return;
+        }
+
+        {
+            let buffer = self.buffer.borrow();
+            let mut selections = ranges
+                .iter()
+                .map(|range| {
+                    let start = buffer.clip_point(range.start);
+                    let end = buffer.clip_point(range.end);
+                    let reversed = end < start;
+                    let (start, end) = if reversed { (end, start) } else { (start, end) };
+                    Selection {
+                        start: buffer.anchor_before_point(start).unwrap(),
+                        end: buffer.anchor_before_point(end).unwrap(),
+                        reversed,
+                        goal_column: None,
+                    }
+                })
+                .collect::<Vec<_>>();
+            selections.sort_by(|a, b| buffer.cmp_anchors(&a.start, &b.start).unwrap());
+            self.selections = selections;
+        }
+        self.merge_selections();
+        self.updated();
+    }
+
+    /// Reduces the selections to a single cursor at the head of the first selection.
+    pub fn clear_selections(&mut self) {
+        let mut selection = self.selections.swap_remove(0);
+        selection.start = selection.head().clone();
+        selection.end = selection.start.clone();
+        selection.reversed = false;
+        selection.goal_column = None;
+        self.selections = vec![selection];
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    /// Maps a position in pixels relative to the top left corner of the view to the closest
+    /// point in the buffer.
+    fn point_for_position(&self, x: f64, y: f64) -> Point {
+        let row = ((y + self.scroll_top) / self.line_height).floor().max(0.0);
+        let column = ((x + self.scroll_left) / self.char_width).round().max(0.0);
+        let display_point = DisplayPoint::new(row as u32, column as u32);
+        self.display_map()
+            .to_buffer_point(&self.buffer.borrow(), display_point, Bias::Left)
+    }
 
-            for selection in &self.selections {
-                let start = buffer.offset_for_anchor(&selection.start).unwrap();
-                let end = buffer.offset_for_anchor(&selection.end).unwrap();
-                offset_ranges.push((start, end));
-            }
+    /// Starts building a selection with the mouse. Double and triple clicks select by word and
+    /// line, shift extends the last selection, and alt adds a new selection instead of replacing
+    /// the existing ones.
+    fn mouse_down(&mut self, point: Point, click_count: usize, modifiers: &Modifiers) {
+        let granularity = match click_count {
+            0 | 1 => SelectionGranularity::Character,
+            2 => SelectionGranularity::Word,
+            _ => SelectionGranularity::Line,
+        };
 
-            for &(start, end) in offset_ranges.iter().rev() {
-                buffer.splice(start..end, text);
+        let mouse_selection = {
+            let buffer = self.buffer.borrow();
+            if modifiers.shift {
+                let mut other_selections = self.selections.clone();
+                let last_selection = other_selections.pop().unwrap();
+                let tail = last_selection.tail().clone();
+                MouseSelection {
+                    other_selections,
+                    origin: tail.clone()..tail,
+                    granularity,
+                }
+            } else {
+                let range = Self::range_for_granularity(&buffer, point, granularity);
+                MouseSelection {
+                    other_selections: if modifiers.alt {
+                        self.selections.clone()
+                    } else {
+                        Vec::new()
+                    },
+                    origin: buffer.anchor_before_point(range.start).unwrap()
+                        ..buffer.anchor_before_point(range.end).unwrap(),
+                    granularity,
+                }
             }
+        };
 
-            let mut delta = 0_isize;
-            self.selections = offset_ranges
-                .into_iter()
-                .map(|(start, end)| {
-                    let start = start as isize;
-                    let end = end as isize;
-                    let anchor = buffer
-                        .anchor_before_offset((start + delta) as usize + text.len())
-                        .unwrap();
-                    let deleted_count = end - start;
-                    delta += text.len() as isize - deleted_count;
+        self.mouse_selection = Some(mouse_selection);
+        self.mouse_drag(point);
+    }
+
+    /// Extends the selection being built with the mouse to contain the given point.
+    fn mouse_drag(&mut self, point: Point) {
+        let selections = match self.mouse_selection {
+            Some(ref mouse_selection) => {
+                let buffer = self.buffer.borrow();
+                let origin_start = buffer.point_for_anchor(&mouse_selection.origin.start).unwrap();
+                let origin_end = buffer.point_for_anchor(&mouse_selection.origin.end).unwrap();
+                let range =
+                    Self::range_for_granularity(&buffer, point, mouse_selection.granularity);
+
+                let selection = if range.start < origin_start {
                     Selection {
-                        start: anchor.clone(),
-                        end: anchor,
+                        start: buffer.anchor_before_point(range.start).unwrap(),
+                        end: mouse_selection.origin.end.clone(),
+                        reversed: true,
+                        goal_column: None,
+                    }
+                } else {
+                    Selection {
+                        start: mouse_selection.origin.start.clone(),
+                        end: buffer
+                            .anchor_before_point(cmp::max(range.end, origin_end))
+                            .unwrap(),
                         reversed: false,
                         goal_column: None,
                     }
-                })
-                .collect();
-        }
+                };
+
+                let mut selections = mouse_selection.other_selections.clone();
+                let index = match selections.binary_search_by(|probe| {
+                    buffer.cmp_anchors(&probe.start, &selection.start).unwrap()
+                }) {
+                    Ok(index) => index,
+                    Err(index) => index,
+                };
+                selections.insert(index, selection);
+                selections
+            }
+            None => return,
+        };
 
+        self.selections = selections;
+        self.merge_selections();
+        self.autoscroll_to_cursor();
         self.updated();
     }
 
-    pub fn add_selection(&mut self, start: Point, end: Point) {
-        debug_assert!(start <= end); // TODO: Reverse selection if end < start
-
-        {
-            let buffer = self.buffer.borrow();
+    fn mouse_up(&mut self) {
+        self.mouse_selection = None;
+    }
 
-            // TODO: Clip points or return a result.
-            let start_anchor = buffer.anchor_before_point(start).unwrap();
-            let end_anchor = buffer.anchor_before_point(end).unwrap();
-            let index = match self.selections
-                .binary_search_by(|probe| buffer.cmp_anchors(&probe.start, &start_anchor).unwrap())
-            {
-                Ok(index) => index,
-                Err(index) => index,
-            };
-            self.selections.insert(
-                index,
-                Selection {
-                    start: start_anchor,
-                    end: end_anchor,
-                    reversed: false,
-                    goal_column: None,
-                },
-            );
+    fn range_for_granularity(
+        buffer: &Buffer,
+        point: Point,
+        granularity: SelectionGranularity,
+    ) -> Range<Point> {
+        match granularity {
+            SelectionGranularity::Character => point..point,
+            SelectionGranularity::Word => movement::word_range(buffer, point),
+            SelectionGranularity::Line => {
+                let max_point = buffer.max_point();
+                let end = if point.row < max_point.row {
+                    Point::new(point.row + 1, 0)
+                } else {
+                    max_point
+                };
+                Point::new(point.row, 0)..end
+            }
         }
-
-        self.merge_selections();
-        self.updated();
     }
 
     pub fn add_selection_above(&mut self) {
@@ -227,6 +1341,7 @@ impl BufferView {
         }
 
         self.merge_selections();
+        self.autoscroll_to_cursor();
         self.updated();
     }
 
@@ -289,159 +1404,437 @@ impl BufferView {
             }
         }
 
-        self.merge_selections();
-        self.updated();
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    pub fn move_left(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
+            for selection in &mut self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+
+                if start != end {
+                    selection.end = selection.start.clone();
+                } else {
+                    let point = display_map.clip_point(movement::left(&buffer, start), Bias::Left);
+                    let cursor = buffer.anchor_before_point(point).unwrap();
+                    selection.start = cursor.clone();
+                    selection.end = cursor;
+                }
+                selection.reversed = false;
+                selection.goal_column = None;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    pub fn select_left(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
+            for selection in &mut self.selections {
+                let head = buffer.point_for_anchor(selection.head()).unwrap();
+                let point = display_map.clip_point(movement::left(&buffer, head), Bias::Left);
+                let cursor = buffer.anchor_before_point(point).unwrap();
+                selection.set_head(&buffer, cursor);
+                selection.goal_column = None;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    pub fn move_right(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
+            for selection in &mut self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+
+                if start != end {
+                    selection.start = selection.end.clone();
+                } else {
+                    let point = display_map.clip_point(movement::right(&buffer, end), Bias::Right);
+                    let cursor = buffer.anchor_before_point(point).unwrap();
+                    selection.start = cursor.clone();
+                    selection.end = cursor;
+                }
+                selection.reversed = false;
+                selection.goal_column = None;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    pub fn select_right(&mut self) {
+        {
+            let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
+            for selection in &mut self.selections {
+                let head = buffer.point_for_anchor(selection.head()).unwrap();
+                let point = display_map.clip_point(movement::right(&buffer, head), Bias::Right);
+                let cursor = buffer.anchor_before_point(point).unwrap();
+                selection.set_head(&buffer, cursor);
+                selection.goal_column = None;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    pub fn move_up(&mut self) {
+        self.move_up_by(1);
+    }
+
+    pub fn select_up(&mut self) {
+        self.select_up_by(1);
+    }
+
+    fn move_up_by(&mut self, rows: u32) {
+        {
+            let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
+            for selection in &mut self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                if start != end {
+                    selection.goal_column = None;
+                }
+
+                let (start, goal_column) =
+                    movement::up_by(&buffer, &display_map, start, selection.goal_column, rows);
+                let cursor = buffer.anchor_before_point(start).unwrap();
+                selection.start = cursor.clone();
+                selection.end = cursor;
+                selection.goal_column = goal_column;
+                selection.reversed = false;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    fn select_up_by(&mut self, rows: u32) {
+        {
+            let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
+            for selection in &mut self.selections {
+                let head = buffer.point_for_anchor(selection.head()).unwrap();
+                let (head, goal_column) =
+                    movement::up_by(&buffer, &display_map, head, selection.goal_column, rows);
+                selection.set_head(&buffer, buffer.anchor_before_point(head).unwrap());
+                selection.goal_column = goal_column;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    pub fn move_down(&mut self) {
+        self.move_down_by(1);
+    }
+
+    pub fn select_down(&mut self) {
+        self.select_down_by(1);
+    }
+
+    fn move_down_by(&mut self, rows: u32) {
+        {
+            let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
+            for selection in &mut self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                if start != end {
+                    selection.goal_column = None;
+                }
+
+                let (start, goal_column) =
+                    movement::down_by(&buffer, &display_map, end, selection.goal_column, rows);
+                let cursor = buffer.anchor_before_point(start).unwrap();
+                selection.start = cursor.clone();
+                selection.end = cursor;
+                selection.goal_column = goal_column;
+                selection.reversed = false;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    fn select_down_by(&mut self, rows: u32) {
+        {
+            let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
+            for selection in &mut self.selections {
+                let head = buffer.point_for_anchor(selection.head()).unwrap();
+                let (head, goal_column) =
+                    movement::down_by(&buffer, &display_map, head, selection.goal_column, rows);
+                selection.set_head(&buffer, buffer.anchor_before_point(head).unwrap());
+                selection.goal_column = goal_column;
+            }
+        }
+        self.merge_selections();
+        self.autoscroll_to_cursor();
+        self.updated();
+    }
+
+    /// Moves the cursors up by the number of rows that fit in the viewport, scrolling by the same
+    /// distance.
+    pub fn page_up(&mut self) {
+        let rows = self.rows_per_page();
+        self.scroll_top = (self.scroll_top - rows as f64 * self.line_height).max(0.0);
+        self.move_up_by(rows);
+    }
+
+    /// Moves the cursors down by the number of rows that fit in the viewport, scrolling by the
+    /// same distance.
+    pub fn page_down(&mut self) {
+        let rows = self.rows_per_page();
+        let max_scroll_top = (self.display_map().row_count() - 1) as f64 * self.line_height;
+        self.scroll_top = (self.scroll_top + rows as f64 * self.line_height).min(max_scroll_top);
+        self.move_down_by(rows);
+    }
+
+    pub fn select_page_up(&mut self) {
+        let rows = self.rows_per_page();
+        self.select_up_by(rows);
+    }
+
+    pub fn select_page_down(&mut self) {
+        let rows = self.rows_per_page();
+        self.select_down_by(rows);
+    }
+
+    fn rows_per_page(&self) -> u32 {
+        cmp::max(1, (self.height / self.line_height).floor() as u32)
+    }
+
+    pub fn move_to_previous_word_boundary(&mut self) {
+        self.move_cursors(movement::prev_word_boundary);
+    }
+
+    pub fn select_to_previous_word_boundary(&mut self) {
+        self.select_to(movement::prev_word_boundary);
+    }
+
+    pub fn move_to_next_word_boundary(&mut self) {
+        self.move_cursors(movement::next_word_boundary);
+    }
+
+    pub fn select_to_next_word_boundary(&mut self) {
+        self.select_to(movement::next_word_boundary);
+    }
+
+    pub fn move_to_previous_subword_boundary(&mut self) {
+        self.move_cursors(movement::prev_subword_boundary);
+    }
+
+    pub fn select_to_previous_subword_boundary(&mut self) {
+        self.select_to(movement::prev_subword_boundary);
+    }
+
+    pub fn move_to_next_subword_boundary(&mut self) {
+        self.move_cursors(movement::next_subword_boundary);
+    }
+
+    pub fn select_to_next_subword_boundary(&mut self) {
+        self.select_to(movement::next_subword_boundary);
+    }
+
+    pub fn move_to_beginning_of_line(&mut self) {
+        self.move_cursors(movement::beginning_of_line);
+    }
+
+    pub fn select_to_beginning_of_line(&mut self) {
+        self.select_to(movement::beginning_of_line);
+    }
+
+    pub fn move_to_end_of_line(&mut self) {
+        self.move_cursors(movement::end_of_line);
+    }
+
+    pub fn select_to_end_of_line(&mut self) {
+        self.select_to(movement::end_of_line);
+    }
+
+    pub fn move_to_beginning_of_buffer(&mut self) {
+        self.move_cursors(movement::beginning_of_buffer);
+    }
+
+    pub fn select_to_beginning_of_buffer(&mut self) {
+        self.select_to(movement::beginning_of_buffer);
+    }
+
+    pub fn move_to_end_of_buffer(&mut self) {
+        self.move_cursors(movement::end_of_buffer);
+    }
+
+    pub fn select_to_end_of_buffer(&mut self) {
+        self.select_to(movement::end_of_buffer);
     }
 
-    pub fn move_left(&mut self) {
+    pub fn move_to_previous_paragraph(&mut self) {
+        self.move_cursors(movement::prev_paragraph);
+    }
+
+    pub fn select_to_previous_paragraph(&mut self) {
+        self.select_to(movement::prev_paragraph);
+    }
+
+    pub fn move_to_next_paragraph(&mut self) {
+        self.move_cursors(movement::next_paragraph);
+    }
+
+    pub fn select_to_next_paragraph(&mut self) {
+        self.select_to(movement::next_paragraph);
+    }
+
+    /// Collapses each selection to a cursor at the point `f` returns for its head, skipping over
+    /// any fold in the direction of the movement.
+    fn move_cursors<F: Fn(&Buffer, Point) -> Point>(&mut self, f: F) {
         {
             let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
             for selection in &mut self.selections {
-                let start = buffer.point_for_anchor(&selection.start).unwrap();
-                let end = buffer.point_for_anchor(&selection.end).unwrap();
-
-                if start != end {
-                    selection.end = selection.start.clone();
-                } else {
-                    let cursor = buffer
-                        .anchor_before_point(movement::left(&buffer, start))
-                        .unwrap();
-                    selection.start = cursor.clone();
-                    selection.end = cursor;
-                }
+                let head = buffer.point_for_anchor(selection.head()).unwrap();
+                let point = Self::clip_movement(&display_map, head, f(&buffer, head));
+                let cursor = buffer.anchor_before_point(point).unwrap();
+                selection.start = cursor.clone();
+                selection.end = cursor;
                 selection.reversed = false;
                 selection.goal_column = None;
             }
         }
         self.merge_selections();
+        self.autoscroll_to_cursor();
         self.updated();
     }
 
-    pub fn select_left(&mut self) {
+    /// Moves the head of each selection to the point `f` returns for it, keeping its tail.
+    fn select_to<F: Fn(&Buffer, Point) -> Point>(&mut self, f: F) {
         {
             let buffer = self.buffer.borrow();
+            self.display_map.borrow_mut().sync(&buffer);
+            let display_map = self.display_map.borrow();
             for selection in &mut self.selections {
                 let head = buffer.point_for_anchor(selection.head()).unwrap();
-                let cursor = buffer
-                    .anchor_before_point(movement::left(&buffer, head))
-                    .unwrap();
+                let point = Self::clip_movement(&display_map, head, f(&buffer, head));
+                let cursor = buffer.anchor_before_point(point).unwrap();
                 selection.set_head(&buffer, cursor);
                 selection.goal_column = None;
             }
         }
         self.merge_selections();
+        self.autoscroll_to_cursor();
         self.updated();
     }
 
-    pub fn move_right(&mut self) {
+    fn clip_movement(display_map: &DisplayMap, from: Point, to: Point) -> Point {
+        if to < from {
+            display_map.clip_point(to, Bias::Left)
+        } else {
+            display_map.clip_point(to, Bias::Right)
+        }
+    }
+
+    /// Folds each non-empty selection, along with the indentation block containing each cursor.
+    pub fn fold_selections(&mut self) {
         {
             let buffer = self.buffer.borrow();
-            for selection in &mut self.selections {
+            let mut display_map = self.display_map.borrow_mut();
+            for selection in &self.selections {
                 let start = buffer.point_for_anchor(&selection.start).unwrap();
                 let end = buffer.point_for_anchor(&selection.end).unwrap();
-
-                if start != end {
-                    selection.start = selection.end.clone();
+                if start == end {
+                    if let Some(range) = display_map.indentation_block(&buffer, start.row) {
+                        display_map.fold(&buffer, range);
+                    }
                 } else {
-                    let cursor = buffer
-                        .anchor_before_point(movement::right(&buffer, end))
-                        .unwrap();
-                    selection.start = cursor.clone();
-                    selection.end = cursor;
+                    display_map.fold(&buffer, start..end);
                 }
-                selection.reversed = false;
-                selection.goal_column = None;
             }
         }
-        self.merge_selections();
-        self.updated();
+        self.clip_selections_to_folds();
     }
 
-    pub fn select_right(&mut self) {
+    /// Removes the folds that intersect the rows spanned by each selection.
+    pub fn unfold_selections(&mut self) {
         {
             let buffer = self.buffer.borrow();
-            for selection in &mut self.selections {
-                let head = buffer.point_for_anchor(selection.head()).unwrap();
-                let cursor = buffer
-                    .anchor_before_point(movement::right(&buffer, head))
-                    .unwrap();
-                selection.set_head(&buffer, cursor);
-                selection.goal_column = None;
+            let mut display_map = self.display_map.borrow_mut();
+            for selection in &self.selections {
+                let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let end = buffer.point_for_anchor(&selection.end).unwrap();
+                display_map.unfold(&buffer, start..end);
             }
         }
-        self.merge_selections();
+        self.autoscroll_to_cursor();
         self.updated();
     }
 
-    pub fn move_up(&mut self) {
+    pub fn fold_all(&mut self) {
         {
             let buffer = self.buffer.borrow();
-            for selection in &mut self.selections {
-                let start = buffer.point_for_anchor(&selection.start).unwrap();
-                let end = buffer.point_for_anchor(&selection.end).unwrap();
-                if start != end {
-                    selection.goal_column = None;
-                }
-
-                let (start, goal_column) = movement::up(&buffer, start, selection.goal_column);
-                let cursor = buffer.anchor_before_point(start).unwrap();
-                selection.start = cursor.clone();
-                selection.end = cursor;
-                selection.goal_column = goal_column;
-                selection.reversed = false;
-            }
+            self.display_map.borrow_mut().fold_all(&buffer);
         }
-        self.merge_selections();
-        self.updated();
+        self.clip_selections_to_folds();
     }
 
-    pub fn select_up(&mut self) {
+    pub fn unfold_all(&mut self) {
         {
             let buffer = self.buffer.borrow();
-            for selection in &mut self.selections {
-                let head = buffer.point_for_anchor(selection.head()).unwrap();
-                let (head, goal_column) = movement::up(&buffer, head, selection.goal_column);
-                selection.set_head(&buffer, buffer.anchor_before_point(head).unwrap());
-                selection.goal_column = goal_column;
-            }
+            self.display_map.borrow_mut().unfold_all(&buffer);
         }
-        self.merge_selections();
+        self.autoscroll_to_cursor();
         self.updated();
     }
 
-    pub fn move_down(&mut self) {
+    /// Moves the ends of selections that were hidden by a fold to the start of that fold.
+    fn clip_selections_to_folds(&mut self) {
         {
             let buffer = self.buffer.borrow();
+            let display_map = self.display_map.borrow();
             for selection in &mut self.selections {
                 let start = buffer.point_for_anchor(&selection.start).unwrap();
+                let clipped_start = display_map.clip_point(start, Bias::Left);
+                if clipped_start != start {
+                    selection.start = buffer.anchor_before_point(clipped_start).unwrap();
+                }
+
                 let end = buffer.point_for_anchor(&selection.end).unwrap();
-                if start != end {
+                let clipped_end = display_map.clip_point(end, Bias::Left);
+                if clipped_end != end {
+                    selection.end = buffer.anchor_before_point(clipped_end).unwrap();
                     selection.goal_column = None;
                 }
-
-                let (start, goal_column) = movement::down(&buffer, end, selection.goal_column);
-                let cursor = buffer.anchor_before_point(start).unwrap();
-                selection.start = cursor.clone();
-                selection.end = cursor;
-                selection.goal_column = goal_column;
-                selection.reversed = false;
-            }
-        }
-        self.merge_selections();
-        self.updated();
-    }
-
-    pub fn select_down(&mut self) {
-        {
-            let buffer = self.buffer.borrow();
-            for selection in &mut self.selections {
-                let head = buffer.point_for_anchor(selection.head()).unwrap();
-                let (head, goal_column) = movement::down(&buffer, head, selection.goal_column);
-                selection.set_head(&buffer, buffer.anchor_before_point(head).unwrap());
-                selection.goal_column = goal_column;
             }
         }
         self.merge_selections();
+        self.autoscroll_to_cursor();
         self.updated();
     }
 
@@ -502,8 +1895,133 @@ impl BufferView {
         }
     }
 
+    /// Scrolls the minimum distance needed to show the head of the last selection, along with
+    /// the configured margin around it.
+    fn autoscroll_to_cursor(&mut self) {
+        let head = {
+            let buffer = self.buffer.borrow();
+            let selection = self.selections.last().unwrap();
+            let head = buffer.point_for_anchor(selection.head()).unwrap();
+            self.display_map().to_display_point(&buffer, head)
+        };
+
+        self.scroll_top = Self::autoscroll_position(
+            self.scroll_top,
+            self.height,
+            self.line_height,
+            head.row,
+            self.autoscroll_margin,
+        );
+        self.scroll_left = Self::autoscroll_position(
+            self.scroll_left,
+            self.width,
+            self.char_width,
+            head.column,
+            self.autoscroll_margin,
+        );
+    }
+
+    /// Computes the scroll position along one axis needed to show the cell at `index` with the
+    /// given margin, shrinking the margin when the viewport is too small to fit it on both sides.
+    fn autoscroll_position(
+        scroll_position: f64,
+        viewport_size: f64,
+        cell_size: f64,
+        index: u32,
+        margin: u32,
+    ) -> f64 {
+        let visible_cells = (viewport_size / cell_size).floor();
+        let margin = (margin as f64).min(((visible_cells - 1.0) / 2.0).floor().max(0.0));
+        let desired_start = (index as f64 - margin) * cell_size;
+        let desired_end = (index as f64 + 1.0 + margin) * cell_size;
+
+        if desired_start < scroll_position {
+            desired_start.max(0.0)
+        } else if desired_end > scroll_position + viewport_size {
+            desired_end - viewport_size
+        } else {
+            scroll_position
+        }
+    }
+
     fn updated(&mut self) {
         self.updates.set(());
+        self.tokenize();
+    }
+
+    /// Starts tokenizing any rows invalidated by edits in the background. Rows keep their old
+    /// tokens until the task publishes new ones, which are picked up when the view is polled.
+    fn tokenize(&mut self) {
+        if let Some(ref window_handle) = self.window_handle {
+            let buffer = self.buffer.borrow();
+            if let Some(task) = self.highlight_map.borrow_mut().tokenize(&buffer) {
+                window_handle.spawn(task.for_each(|_| Ok(())));
+            }
+        }
+    }
+
+    /// Returns the tokens on each of the given display rows, whose text is given by `lines`, with
+    /// columns translated into display columns.
+    fn render_tokens(
+        &self,
+        buffer: &Buffer,
+        display_map: &DisplayMap,
+        rows: Range<u32>,
+        lines: &[String],
+    ) -> Vec<Vec<Token>> {
+        let line_lens = lines
+            .iter()
+            .map(|line| line.encode_utf16().count() as u32)
+            .collect::<Vec<_>>();
+        let mut display_tokens = vec![Vec::new(); lines.len()];
+        if lines.is_empty() {
+            return display_tokens;
+        }
+
+        let start =
+            display_map.to_buffer_point(buffer, DisplayPoint::new(rows.start, 0), Bias::Left);
+        let end = display_map.to_buffer_point(
+            buffer,
+            DisplayPoint::new(rows.end - 1, line_lens[line_lens.len() - 1]),
+            Bias::Right,
+        );
+        let mut highlight_map = self.highlight_map.borrow_mut();
+        highlight_map.sync(buffer);
+        let buffer_rows = start.row..end.row + 1;
+        for (row, tokens) in buffer_rows.clone().zip(highlight_map.lines(buffer_rows)) {
+            // Rows that were edited keep their old tokens until they are tokenized again, so
+            // those tokens may extend past the end of the row.
+            let row_len = buffer.len_for_row(row).unwrap();
+            for token in tokens.iter().filter(|token| token.start < row_len) {
+                let token_start =
+                    display_map.to_display_point(buffer, Point::new(row, token.start));
+                let token_end = display_map
+                    .to_display_point(buffer, Point::new(row, cmp::min(token.end, row_len)));
+                let first_row = cmp::max(token_start.row, rows.start);
+                let last_row = cmp::min(token_end.row + 1, rows.end);
+                for display_row in first_row..last_row {
+                    let index = (display_row - rows.start) as usize;
+                    let start = if display_row == token_start.row {
+                        token_start.column
+                    } else {
+                        0
+                    };
+                    let end = if display_row == token_end.row {
+                        token_end.column
+                    } else {
+                        line_lens[index]
+                    };
+                    if start < end {
+                        display_tokens[index].push(Token {
+                            start,
+                            end,
+                            scope: token.scope,
+                        });
+                    }
+                }
+            }
+        }
+        display_tokens
     }
 }
 
@@ -514,51 +2032,71 @@ impl View for BufferView {
 
     fn will_mount(&mut self, window_handle: WindowHandle) {
         self.height = window_handle.height();
+        self.window_handle = Some(window_handle);
+        self.tokenize();
     }
 
     fn render(&self) -> serde_json::Value {
         let buffer = self.buffer.borrow();
+        let display_map = self.display_map();
 
-        let max_scroll_top = buffer.max_point().row as f64 * self.line_height;
+        let max_scroll_top = (display_map.row_count() - 1) as f64 * self.line_height;
         let scroll_top = self.scroll_top.min(max_scroll_top);
         let scroll_bottom = scroll_top + self.height;
-        let start = Point::new((scroll_top / self.line_height).floor() as u32, 0);
-        let end = Point::new((scroll_bottom / self.line_height).ceil() as u32, 0);
-
-        let mut lines = Vec::new();
-        let mut cur_line = Vec::new();
-        let mut cur_row = start.row;
-        for c in buffer.iter_starting_at_row(start.row) {
-            if c == (b'\n' as u16) {
-                lines.push(String::from_utf16_lossy(&cur_line));
-                cur_line = Vec::new();
-                cur_row += 1;
-                if cur_row >= end.row {
-                    break;
-                }
-            } else {
-                cur_line.push(c);
+        let start_row = (scroll_top / self.line_height).floor() as u32;
+        let end_row = (scroll_bottom / self.line_height).ceil() as u32;
+        let lines = display_map.lines(&buffer, start_row..end_row);
+        let end_row = start_row + lines.len() as u32;
+        let tokens = self.render_tokens(&buffer, &display_map, start_row..end_row, &lines);
+
+        let start =
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(start_row, 0), Bias::Left);
+        let end = if end_row < display_map.row_count() {
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(end_row, 0), Bias::Left)
+        } else {
+            Point::new(buffer.max_point().row + 1, 0)
+        };
+
+        let visible_selections = self.query_selections(start..end);
+        let mut visible_find_matches = Vec::new();
+        for range in &self.find_matches {
+            let match_start = buffer.point_for_anchor(&range.start).unwrap();
+            let match_end = buffer.point_for_anchor(&range.end).unwrap();
+            if match_end >= start && match_start < end {
+                visible_find_matches.push(json!({
+                    "start": display_map.to_display_point(&buffer, match_start),
+                    "end": display_map.to_display_point(&buffer, match_end),
+                }));
             }
         }
-        if cur_row < end.row {
-            lines.push(String::from_utf16_lossy(&cur_line));
-        }
 
-        let visible_selections = self.query_selections(start..end);
         json!({
-            "first_visible_row": start.row,
+            "first_visible_row": start_row,
             "lines": lines,
+            "tokens": tokens,
             "scroll_top": self.scroll_top,
+            "scroll_left": self.scroll_left,
             "height": self.height,
             "width": self.width,
             "line_height": self.line_height,
+            "dirty": buffer.is_dirty(),
+            "save_conflict": self.save_conflict,
+            "focused": self.focused,
             "selections": visible_selections.iter()
-                .map(|selection| selection.render(&buffer))
-                .collect::<Vec<_>>()
+                .map(|selection| {
+                    let selection = selection.render(&buffer);
+                    json!({
+                        "start": display_map.to_display_point(&buffer, selection.start),
+                        "end": display_map.to_display_point(&buffer, selection.end),
+                        "reversed": selection.reversed,
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "find_matches": visible_find_matches
         })
     }
 
-    fn dispatch_action(&mut self, action: serde_json::Value) {
+    fn dispatch_action(&mut self, action: serde_json::Value) -> bool {
         match serde_json::from_value(action) {
             Ok(BufferViewAction::UpdateScrollTop { delta }) => {
                 let mut scroll_top = self.scroll_top + delta;
@@ -567,17 +2105,176 @@ impl View for BufferView {
                 }
                 self.set_scroll_top(scroll_top);
             }
+            Ok(BufferViewAction::UpdateScrollLeft { delta }) => {
+                let scroll_left = (self.scroll_left + delta).max(0.0);
+                self.set_scroll_left(scroll_left);
+            }
             Ok(BufferViewAction::SetDimensions { width, height }) => {
                 self.set_width(width as f64);
                 self.set_height(height as f64);
             }
+            Ok(BufferViewAction::SetCharWidth { char_width }) => {
+                self.set_char_width(char_width);
+            }
+            Ok(BufferViewAction::ToggleSoftWrap) => self.toggle_soft_wrap(),
+            Ok(BufferViewAction::Fold) => self.fold_selections(),
+            Ok(BufferViewAction::Unfold) => self.unfold_selections(),
+            Ok(BufferViewAction::FoldAll) => self.fold_all(),
+            Ok(BufferViewAction::UnfoldAll) => self.unfold_all(),
+            Ok(BufferViewAction::ExpandSelection) => self.expand_selection(),
+            Ok(BufferViewAction::ShrinkSelection) => self.shrink_selection(),
+            Ok(BufferViewAction::Copy) => self.copy(),
+            Ok(BufferViewAction::Cut) => self.cut(),
+            Ok(BufferViewAction::Paste) => self.paste(),
+            Ok(BufferViewAction::Indent) => self.indent(),
+            Ok(BufferViewAction::Outdent) => self.outdent(),
+            Ok(BufferViewAction::AutoIndentSelection) => self.auto_indent_selection(),
+            Ok(BufferViewAction::MouseDown {
+                x,
+                y,
+                click_count,
+                modifiers,
+            }) => {
+                let point = self.point_for_position(x, y);
+                self.mouse_down(point, click_count, &modifiers);
+            }
+            Ok(BufferViewAction::MouseDrag { x, y }) => {
+                let point = self.point_for_position(x, y);
+                self.mouse_drag(point);
+            }
+            Ok(BufferViewAction::MouseUp) => self.mouse_up(),
             Ok(BufferViewAction::Edit { text }) => self.edit(text.as_str()),
+            Ok(BufferViewAction::Backspace) => self.backspace(),
+            Ok(BufferViewAction::Delete) => self.delete(),
+            Ok(BufferViewAction::DeleteToPreviousWordBoundary) => {
+                self.delete_to_previous_word_boundary()
+            }
+            Ok(BufferViewAction::DeleteToNextWordBoundary) => self.delete_to_next_word_boundary(),
+            Ok(BufferViewAction::DeleteLine) => self.delete_line(),
+            Ok(BufferViewAction::DeleteToEndOfLine) => self.delete_to_end_of_line(),
             Ok(BufferViewAction::MoveUp) => self.move_up(),
             Ok(BufferViewAction::MoveDown) => self.move_down(),
             Ok(BufferViewAction::MoveLeft) => self.move_left(),
             Ok(BufferViewAction::MoveRight) => self.move_right(),
-            action @ _ => eprintln!("Unrecognized action {:?}", action),
+            Ok(BufferViewAction::PageUp) => self.page_up(),
+            Ok(BufferViewAction::PageDown) => self.page_down(),
+            Ok(BufferViewAction::SelectPageUp) => self.select_page_up(),
+            Ok(BufferViewAction::SelectPageDown) => self.select_page_down(),
+            Ok(BufferViewAction::SelectUp) => self.select_up(),
+            Ok(BufferViewAction::SelectDown) => self.select_down(),
+            Ok(BufferViewAction::SelectLeft) => self.select_left(),
+            Ok(BufferViewAction::SelectRight) => self.select_right(),
+            Ok(BufferViewAction::AddSelection { start, end }) => {
+                let (start, end) = {
+                    let buffer = self.buffer.borrow();
+                    (buffer.clip_point(start), buffer.clip_point(end))
+                };
+                self.add_selection(cmp::min(start, end), cmp::max(start, end));
+            }
+            Ok(BufferViewAction::AddSelectionAbove) => self.add_selection_above(),
+            Ok(BufferViewAction::AddSelectionBelow) => self.add_selection_below(),
+            Ok(BufferViewAction::SelectAll) => self.select_all(),
+            Ok(BufferViewAction::SelectLine) => self.select_line(),
+            Ok(BufferViewAction::SelectWord) => self.select_word(),
+            Ok(BufferViewAction::SetSelections { ranges }) => {
+                let ranges = ranges
+                    .into_iter()
+                    .map(|range| {
+                        if range.reversed {
+                            range.end..range.start
+                        } else {
+                            range.start..range.end
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                self.set_selections(&ranges);
+            }
+            Ok(BufferViewAction::ClearSelections) => self.clear_selections(),
+            Ok(BufferViewAction::MoveToPreviousWordBoundary) => {
+                self.move_to_previous_word_boundary()
+            }
+            Ok(BufferViewAction::SelectToPreviousWordBoundary) => {
+                self.select_to_previous_word_boundary()
+            }
+            Ok(BufferViewAction::MoveToNextWordBoundary) => self.move_to_next_word_boundary(),
+            Ok(BufferViewAction::SelectToNextWordBoundary) => self.select_to_next_word_boundary(),
+            Ok(BufferViewAction::MoveToPreviousSubwordBoundary) => {
+                self.move_to_previous_subword_boundary()
+            }
+            Ok(BufferViewAction::SelectToPreviousSubwordBoundary) => {
+                self.select_to_previous_subword_boundary()
+            }
+            Ok(BufferViewAction::MoveToNextSubwordBoundary) => self.move_to_next_subword_boundary(),
+            Ok(BufferViewAction::SelectToNextSubwordBoundary) => {
+                self.select_to_next_subword_boundary()
+            }
+            Ok(BufferViewAction::MoveToBeginningOfLine) => self.move_to_beginning_of_line(),
+            Ok(BufferViewAction::SelectToBeginningOfLine) => self.select_to_beginning_of_line(),
+            Ok(BufferViewAction::MoveToEndOfLine) => self.move_to_end_of_line(),
+            Ok(BufferViewAction::SelectToEndOfLine) => self.select_to_end_of_line(),
+            Ok(BufferViewAction::MoveToBeginningOfBuffer) => self.move_to_beginning_of_buffer(),
+            Ok(BufferViewAction::SelectToBeginningOfBuffer) => self.select_to_beginning_of_buffer(),
+            Ok(BufferViewAction::MoveToEndOfBuffer) => self.move_to_end_of_buffer(),
+            Ok(BufferViewAction::SelectToEndOfBuffer) => self.select_to_end_of_buffer(),
+            Ok(BufferViewAction::MoveToPreviousParagraph) => self.move_to_previous_paragraph(),
+            Ok(BufferViewAction::SelectToPreviousParagraph) => self.select_to_previous_paragraph(),
+            Ok(BufferViewAction::MoveToNextParagraph) => self.move_to_next_paragraph(),
+            Ok(BufferViewAction::SelectToNextParagraph) => self.select_to_next_paragraph(),
+            Ok(BufferViewAction::Undo) => self.undo(),
+            Ok(BufferViewAction::Redo) => self.redo(),
+            Ok(BufferViewAction::Save) => self.save(),
+            Ok(BufferViewAction::ForceSave) => self.force_save(),
+            Ok(BufferViewAction::SelectNextMatch) => self.select_next_match(),
+            Ok(BufferViewAction::SelectPreviousMatch) => self.select_previous_match(),
+            Ok(BufferViewAction::SelectAllMatches) => self.select_all_matches(),
+            _ => return false,
         }
+        true
+    }
+
+    fn did_focus(&mut self) {
+        self.focused = true;
+        self.updates.set(());
+    }
+
+    fn did_blur(&mut self) {
+        self.focused = false;
+        self.updates.set(());
+    }
+
+    // Saving is offered by the workspace, which forwards it to the active editor.
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new("Undo", json!({"type": "Undo"})),
+            Command::new("Redo", json!({"type": "Redo"})),
+            Command::new("Copy", json!({"type": "Copy"})),
+            Command::new("Cut", json!({"type": "Cut"})),
+            Command::new("Paste", json!({"type": "Paste"})),
+            Command::new("Select All", json!({"type": "SelectAll"})),
+            Command::new("Select Line", json!({"type": "SelectLine"})),
+            Command::new("Select Word", json!({"type": "SelectWord"})),
+            Command::new("Clear Selections", json!({"type": "ClearSelections"})),
+            Command::new("Add Selection Above", json!({"type": "AddSelectionAbove"})),
+            Command::new("Add Selection Below", json!({"type": "AddSelectionBelow"})),
+            Command::new("Expand Selection", json!({"type": "ExpandSelection"})),
+            Command::new("Shrink Selection", json!({"type": "ShrinkSelection"})),
+            Command::new("Select Next Match", json!({"type": "SelectNextMatch"})),
+            Command::new("Select Previous Match", json!({"type": "SelectPreviousMatch"})),
+            Command::new("Select All Matches", json!({"type": "SelectAllMatches"})),
+            Command::new("Move To Beginning Of Buffer", json!({"type": "MoveToBeginningOfBuffer"})),
+            Command::new("Move To End Of Buffer", json!({"type": "MoveToEndOfBuffer"})),
+            Command::new("Delete Line", json!({"type": "DeleteLine"})),
+            Command::new("Delete To End Of Line", json!({"type": "DeleteToEndOfLine"})),
+            Command::new("Indent", json!({"type": "Indent"})),
+            Command::new("Outdent", json!({"type": "Outdent"})),
+            Command::new("Auto Indent Selection", json!({"type": "AutoIndentSelection"})),
+            Command::new("Fold", json!({"type": "Fold"})),
+            Command::new("Unfold", json!({"type": "Unfold"})),
+            Command::new("Fold All", json!({"type": "FoldAll"})),
+            Command::new("Unfold All", json!({"type": "UnfoldAll"})),
+            Command::new("Toggle Soft Wrap", json!({"type": "ToggleSoftWrap"})),
+            Command::new("Force Save", json!({"type": "ForceSave"})),
+        ]
     }
 }
 
@@ -586,7 +2283,25 @@ impl Stream for BufferView {
     type Error = ();
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        self.updates.poll()
+        let tokenized = self.highlight_map.borrow_mut().poll_tokenize();
+        if tokenized {
+            // Edits made while the task was running are tokenized by a new one.
+            self.tokenize();
+        }
+
+        // The buffer may be shared with other views, whose edits need to be rendered here too.
+        let buffer_updated = self.buffer_updates.poll()?.is_ready();
+        if buffer_updated {
+            self.update_find_matches();
+            self.tokenize();
+        }
+
+        let updates_poll = self.updates.poll()?;
+        if tokenized || buffer_updated || updates_poll.is_ready() {
+            Ok(Async::Ready(Some(())))
+        } else {
+            Ok(Async::NotReady)
+        }
     }
 }
 
@@ -643,6 +2358,8 @@ mod tests {
     extern crate tokio_core;
 
     use super::*;
+    use futures::{future, Future};
+    use tree_sitter_rust;
 
     #[test]
     fn test_cursor_movement() {
@@ -816,6 +2533,117 @@ mod tests {
         assert_eq!(render_selections(&editor), vec![empty_selection(0, 1)]);
     }
 
+    #[test]
+    fn test_word_movement() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor
+            .buffer
+            .borrow_mut()
+            .splice(0..0, "fooBar_baz HTMLParser.x\n  qux");
+
+        let mut cursors = Vec::new();
+        for _ in 0..9 {
+            editor.move_to_next_subword_boundary();
+            cursors.extend(render_selections(&editor));
+        }
+        assert_eq!(
+            cursors,
+            vec![
+                empty_selection(0, 3),
+                empty_selection(0, 6),
+                empty_selection(0, 10),
+                empty_selection(0, 15),
+                empty_selection(0, 21),
+                empty_selection(0, 22),
+                empty_selection(0, 23),
+                empty_selection(1, 0),
+                empty_selection(1, 5),
+            ]
+        );
+
+        let mut cursors = Vec::new();
+        for _ in 0..10 {
+            editor.move_to_previous_subword_boundary();
+            cursors.extend(render_selections(&editor));
+        }
+        assert_eq!(
+            cursors,
+            vec![
+                empty_selection(1, 2),
+                empty_selection(1, 0),
+                empty_selection(0, 23),
+                empty_selection(0, 22),
+                empty_selection(0, 21),
+                empty_selection(0, 15),
+                empty_selection(0, 11),
+                empty_selection(0, 7),
+                empty_selection(0, 3),
+                empty_selection(0, 0),
+            ]
+        );
+
+        editor.move_to_next_word_boundary();
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 10)]);
+        editor.select_to_next_word_boundary();
+        assert_eq!(render_selections(&editor), vec![selection((0, 10), (0, 21))]);
+        editor.select_to_previous_word_boundary();
+        editor.select_to_previous_word_boundary();
+        assert_eq!(render_selections(&editor), vec![rev_selection((0, 0), (0, 10))]);
+        editor.move_to_previous_word_boundary();
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 0)]);
+    }
+
+    #[test]
+    fn test_line_and_buffer_movement() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "a\n  bcd\n\n\ne\nf\n\ng");
+        editor.move_down();
+        editor.move_right();
+        editor.move_right();
+        editor.move_right();
+
+        // Moving to the beginning of the line toggles between the indentation and column 0.
+        editor.move_to_beginning_of_line();
+        assert_eq!(render_selections(&editor), vec![empty_selection(1, 2)]);
+        editor.move_to_beginning_of_line();
+        assert_eq!(render_selections(&editor), vec![empty_selection(1, 0)]);
+        editor.move_to_beginning_of_line();
+        assert_eq!(render_selections(&editor), vec![empty_selection(1, 2)]);
+        editor.select_to_end_of_line();
+        assert_eq!(render_selections(&editor), vec![selection((1, 2), (1, 5))]);
+        editor.move_to_end_of_line();
+        assert_eq!(render_selections(&editor), vec![empty_selection(1, 5)]);
+
+        editor.select_to_end_of_buffer();
+        assert_eq!(render_selections(&editor), vec![selection((1, 5), (7, 1))]);
+        editor.select_to_beginning_of_buffer();
+        assert_eq!(render_selections(&editor), vec![rev_selection((0, 0), (1, 5))]);
+        editor.move_to_end_of_buffer();
+        assert_eq!(render_selections(&editor), vec![empty_selection(7, 1)]);
+
+        let mut cursors = Vec::new();
+        for _ in 0..3 {
+            editor.move_to_previous_paragraph();
+            cursors.extend(render_selections(&editor));
+        }
+        assert_eq!(
+            cursors,
+            vec![empty_selection(6, 0), empty_selection(3, 0), empty_selection(0, 0)]
+        );
+
+        let mut cursors = Vec::new();
+        for _ in 0..3 {
+            editor.move_to_next_paragraph();
+            cursors.extend(render_selections(&editor));
+        }
+        assert_eq!(
+            cursors,
+            vec![empty_selection(2, 0), empty_selection(6, 0), empty_selection(7, 1)]
+        );
+        editor.select_to_previous_paragraph();
+        assert_eq!(render_selections(&editor), vec![rev_selection((6, 0), (7, 1))]);
+    }
+
     #[test]
     fn test_add_selection() {
         let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
@@ -834,39 +2662,152 @@ mod tests {
         assert_eq!(
             render_selections(&editor),
             vec![
-                selection((0, 0), (0, 1)),
-                selection((0, 2), (0, 2)),
-                selection((0, 3), (1, 2)),
-                selection((2, 2), (2, 3)),
+                selection((0, 0), (0, 1)),
+                selection((0, 2), (0, 2)),
+                selection((0, 3), (1, 2)),
+                selection((2, 2), (2, 3)),
+            ]
+        );
+
+        // Adding a selection that starts at the start of an existing selection
+        editor.add_selection(Point::new(0, 3), Point::new(1, 0));
+        editor.add_selection(Point::new(0, 3), Point::new(1, 3));
+        editor.add_selection(Point::new(0, 3), Point::new(1, 2));
+
+        assert_eq!(
+            render_selections(&editor),
+            vec![
+                selection((0, 0), (0, 1)),
+                selection((0, 2), (0, 2)),
+                selection((0, 3), (1, 3)),
+                selection((2, 2), (2, 3)),
+            ]
+        );
+
+        // Adding a selection that starts or ends inside an existing selection
+        editor.add_selection(Point::new(0, 1), Point::new(0, 2));
+        editor.add_selection(Point::new(1, 2), Point::new(1, 4));
+        editor.add_selection(Point::new(2, 1), Point::new(2, 2));
+        assert_eq!(
+            render_selections(&editor),
+            vec![
+                selection((0, 0), (0, 2)),
+                selection((0, 3), (1, 4)),
+                selection((2, 1), (2, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_all_line_and_word() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "abc def\nghi\njkl");
+
+        editor.select_all();
+        assert_eq!(render_selections(&editor), vec![selection((0, 0), (2, 3))]);
+
+        editor.set_selections(&[
+            Point::new(0, 5)..Point::new(0, 5),
+            Point::new(1, 1)..Point::new(1, 2),
+        ]);
+        editor.select_word();
+        assert_eq!(
+            render_selections(&editor),
+            vec![selection((0, 4), (0, 7)), selection((1, 0), (1, 3))]
+        );
+
+        // Selecting lines merges selections that end up touching, and repeating it extends the
+        // selection downward.
+        editor.select_line();
+        assert_eq!(render_selections(&editor), vec![selection((0, 0), (2, 0))]);
+        editor.select_line();
+        assert_eq!(render_selections(&editor), vec![selection((0, 0), (2, 3))]);
+    }
+
+    #[test]
+    fn test_set_and_clear_selections() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "abc\ndef\nghi");
+
+        editor.set_selections(&[
+            Point::new(2, 1)..Point::new(1, 2),
+            Point::new(0, 1)..Point::new(0, 10),
+            Point::new(0, 2)..Point::new(0, 3),
+            Point::new(5, 0)..Point::new(5, 0),
+        ]);
+        assert_eq!(
+            render_selections(&editor),
+            vec![
+                selection((0, 1), (0, 3)),
+                rev_selection((1, 2), (2, 1)),
+                empty_selection(2, 3),
             ]
         );
 
-        // Adding a selection that starts at the start of an existing selection
-        editor.add_selection(Point::new(0, 3), Point::new(1, 0));
-        editor.add_selection(Point::new(0, 3), Point::new(1, 3));
-        editor.add_selection(Point::new(0, 3), Point::new(1, 2));
+        editor.set_selections(&[]);
+        assert_eq!(render_selections(&editor).len(), 3);
 
+        editor.set_selections(&[
+            Point::new(1, 2)..Point::new(0, 1),
+            Point::new(2, 0)..Point::new(2, 1),
+        ]);
+        editor.clear_selections();
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 1)]);
+    }
+
+    #[test]
+    fn test_mouse_selection() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "abc def\nghi jkl\nmno");
+        editor.set_line_height(10.0).set_char_width(5.0).set_scroll_top(5.0);
+        assert_eq!(editor.point_for_position(12.0, 4.0), Point::new(0, 2));
+        assert_eq!(editor.point_for_position(-3.0, 6.0), Point::new(1, 0));
+        assert_eq!(editor.point_for_position(100.0, 100.0), Point::new(2, 3));
+        editor.set_scroll_top(0.0);
+
+        let click = Modifiers::default();
+        editor.mouse_down(Point::new(0, 5), 1, &click);
+        editor.mouse_drag(Point::new(1, 1));
+        assert_eq!(render_selections(&editor), vec![selection((0, 5), (1, 1))]);
+        editor.mouse_drag(Point::new(0, 1));
+        assert_eq!(render_selections(&editor), vec![rev_selection((0, 1), (0, 5))]);
+        editor.mouse_up();
+        editor.mouse_drag(Point::new(2, 0));
+        assert_eq!(render_selections(&editor), vec![rev_selection((0, 1), (0, 5))]);
+
+        // Dragging after a double click extends the selection by whole words.
+        editor.mouse_down(Point::new(1, 5), 2, &click);
+        assert_eq!(render_selections(&editor), vec![selection((1, 4), (1, 7))]);
+        editor.mouse_drag(Point::new(1, 1));
+        assert_eq!(render_selections(&editor), vec![rev_selection((1, 0), (1, 7))]);
+        editor.mouse_up();
+
+        editor.mouse_down(Point::new(1, 2), 3, &click);
+        editor.mouse_drag(Point::new(2, 1));
+        assert_eq!(render_selections(&editor), vec![selection((1, 0), (2, 3))]);
+        editor.mouse_up();
+
+        let alt_click = Modifiers {
+            alt: true,
+            shift: false,
+        };
+        editor.mouse_down(Point::new(0, 1), 1, &click);
+        editor.mouse_down(Point::new(2, 2), 1, &alt_click);
+        editor.mouse_up();
         assert_eq!(
             render_selections(&editor),
-            vec![
-                selection((0, 0), (0, 1)),
-                selection((0, 2), (0, 2)),
-                selection((0, 3), (1, 3)),
-                selection((2, 2), (2, 3)),
-            ]
+            vec![empty_selection(0, 1), empty_selection(2, 2)]
         );
 
-        // Adding a selection that starts or ends inside an existing selection
-        editor.add_selection(Point::new(0, 1), Point::new(0, 2));
-        editor.add_selection(Point::new(1, 2), Point::new(1, 4));
-        editor.add_selection(Point::new(2, 1), Point::new(2, 2));
+        let shift_click = Modifiers {
+            alt: false,
+            shift: true,
+        };
+        editor.mouse_down(Point::new(1, 3), 1, &shift_click);
+        editor.mouse_up();
         assert_eq!(
             render_selections(&editor),
-            vec![
-                selection((0, 0), (0, 2)),
-                selection((0, 3), (1, 4)),
-                selection((2, 1), (2, 3)),
-            ]
+            vec![empty_selection(0, 1), rev_selection((1, 3), (2, 2))]
         );
     }
 
@@ -1025,6 +2966,589 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_backspace_and_delete() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "abc def\nghi.jkl\nmno");
+        editor.add_selection(Point::new(0, 2), Point::new(0, 2));
+        editor.add_selection(Point::new(1, 0), Point::new(1, 0));
+        editor.add_selection(Point::new(2, 1), Point::new(2, 3));
+
+        editor.backspace();
+        assert_eq!(editor.buffer.borrow().to_string(), "ac defghi.jkl\nm");
+        assert_eq!(
+            render_selections(&editor),
+            vec![
+                empty_selection(0, 0),
+                empty_selection(0, 1),
+                empty_selection(0, 6),
+                empty_selection(1, 1),
+            ]
+        );
+
+        // Undo restores the selections as they were before they were expanded for deletion.
+        editor.undo();
+        assert_eq!(editor.buffer.borrow().to_string(), "abc def\nghi.jkl\nmno");
+        assert_eq!(
+            render_selections(&editor),
+            vec![
+                empty_selection(0, 0),
+                empty_selection(0, 2),
+                empty_selection(1, 0),
+                selection((2, 1), (2, 3)),
+            ]
+        );
+
+        editor.delete();
+        assert_eq!(editor.buffer.borrow().to_string(), "b def\nhi.jkl\nm");
+        assert_eq!(
+            render_selections(&editor),
+            vec![
+                empty_selection(0, 0),
+                empty_selection(0, 1),
+                empty_selection(1, 0),
+                empty_selection(2, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_delete_to_word_boundary() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "foo.bar  baz\nqux");
+        editor.add_selection(Point::new(0, 7), Point::new(0, 7));
+        editor.add_selection(Point::new(0, 12), Point::new(0, 12));
+
+        editor.delete_to_previous_word_boundary();
+        assert_eq!(editor.buffer.borrow().to_string(), "foo.  \nqux");
+        assert_eq!(
+            render_selections(&editor),
+            vec![
+                empty_selection(0, 0),
+                empty_selection(0, 4),
+                empty_selection(0, 6),
+            ]
+        );
+
+        // The ranges deleted by the last two cursors touch, so their selections are merged.
+        editor.delete_to_next_word_boundary();
+        assert_eq!(editor.buffer.borrow().to_string(), ".qux");
+        assert_eq!(
+            render_selections(&editor),
+            vec![empty_selection(0, 0), empty_selection(0, 1)]
+        );
+    }
+
+    #[test]
+    fn test_delete_line() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "a\nb\nc\nd");
+        editor.add_selection(Point::new(1, 1), Point::new(2, 0));
+
+        editor.delete_line();
+        assert_eq!(editor.buffer.borrow().to_string(), "c\nd");
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 0)]);
+
+        // Deleting the last line also deletes the preceding newline.
+        editor.add_selection(Point::new(1, 0), Point::new(1, 0));
+        editor.delete_line();
+        assert_eq!(editor.buffer.borrow().to_string(), "");
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 0)]);
+
+        editor.buffer.borrow_mut().splice(0..0, "abc\ndef");
+        editor.move_right();
+        editor.delete_to_end_of_line();
+        assert_eq!(editor.buffer.borrow().to_string(), "a\ndef");
+        editor.delete_to_end_of_line();
+        assert_eq!(editor.buffer.borrow().to_string(), "adef");
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 1)]);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "abcdefgh\nhijklmno");
+
+        editor.move_right();
+        editor.select_right();
+        editor.add_selection(Point::new(1, 2), Point::new(1, 2));
+        editor.edit("-");
+        editor.edit("+");
+        assert_eq!(editor.buffer.borrow().to_string(), "a-+cdefgh\nhi-+jklmno");
+
+        // Undoing a multi-cursor edit reverts every selection in a single step.
+        editor.undo();
+        assert_eq!(editor.buffer.borrow().to_string(), "a-cdefgh\nhi-jklmno");
+        assert_eq!(
+            render_selections(&editor),
+            vec![empty_selection(0, 2), empty_selection(1, 3)]
+        );
+        editor.undo();
+        assert_eq!(editor.buffer.borrow().to_string(), "abcdefgh\nhijklmno");
+        assert_eq!(
+            render_selections(&editor),
+            vec![selection((0, 1), (0, 2)), empty_selection(1, 2)]
+        );
+
+        editor.redo();
+        assert_eq!(editor.buffer.borrow().to_string(), "a-cdefgh\nhi-jklmno");
+        assert_eq!(
+            render_selections(&editor),
+            vec![empty_selection(0, 2), empty_selection(1, 3)]
+        );
+        editor.redo();
+        assert_eq!(editor.buffer.borrow().to_string(), "a-+cdefgh\nhi-+jklmno");
+        assert_eq!(
+            render_selections(&editor),
+            vec![empty_selection(0, 3), empty_selection(1, 4)]
+        );
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "foo bar\nbar foo\nfoo");
+        editor.move_right();
+
+        editor.set_find_regex(Some(Regex::new("foo").unwrap()));
+        assert_eq!(editor.find_match_count(), 3);
+
+        editor.select_next_match();
+        assert_eq!(render_selections(&editor), vec![selection((1, 4), (1, 7))]);
+        editor.select_next_match();
+        assert_eq!(render_selections(&editor), vec![selection((2, 0), (2, 3))]);
+        editor.select_next_match();
+        assert_eq!(render_selections(&editor), vec![selection((0, 0), (0, 3))]);
+        editor.select_previous_match();
+        assert_eq!(render_selections(&editor), vec![selection((2, 0), (2, 3))]);
+        editor.select_previous_match();
+        assert_eq!(render_selections(&editor), vec![selection((1, 4), (1, 7))]);
+
+        editor.select_all_matches();
+        assert_eq!(
+            render_selections(&editor),
+            vec![
+                selection((0, 0), (0, 3)),
+                selection((1, 4), (1, 7)),
+                selection((2, 0), (2, 3)),
+            ]
+        );
+
+        // Matches are recomputed as the buffer is edited.
+        editor.edit("baz");
+        assert_eq!(editor.find_match_count(), 0);
+        editor.undo();
+        assert_eq!(editor.find_match_count(), 3);
+
+        editor.set_find_regex(None);
+        assert_eq!(editor.find_match_count(), 0);
+    }
+
+    #[test]
+    fn test_replace_matches() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "a1 b2\nc3");
+        editor.set_find_regex(Some(Regex::new(r"(\w)(\d)").unwrap()));
+
+        // The current selection isn't a match, so the first match is only selected.
+        editor.replace_current_match("$2$1");
+        assert_eq!(editor.buffer.borrow().to_string(), "a1 b2\nc3");
+        assert_eq!(render_selections(&editor), vec![selection((0, 0), (0, 2))]);
+
+        editor.replace_current_match("$2$1");
+        assert_eq!(editor.buffer.borrow().to_string(), "1a b2\nc3");
+        assert_eq!(render_selections(&editor), vec![selection((0, 3), (0, 5))]);
+        assert_eq!(editor.find_match_count(), 2);
+
+        editor.replace_all_matches("$2$1");
+        assert_eq!(editor.buffer.borrow().to_string(), "1a 2b\n3c");
+        assert_eq!(editor.find_match_count(), 0);
+        editor.undo();
+        assert_eq!(editor.buffer.borrow().to_string(), "1a b2\nc3");
+        editor.undo();
+        assert_eq!(editor.buffer.borrow().to_string(), "a1 b2\nc3");
+        assert_eq!(render_selections(&editor), vec![selection((0, 0), (0, 2))]);
+    }
+
+    #[test]
+    fn test_autoscroll() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        let mut text = (0..20).map(|row| row.to_string()).collect::<Vec<_>>();
+        text[10] = "x".repeat(30);
+        editor.buffer.borrow_mut().splice(0..0, text.join("\n").as_str());
+        editor
+            .set_height(50.0)
+            .set_width(50.0)
+            .set_line_height(10.0)
+            .set_char_width(10.0)
+            .set_autoscroll_margin(1);
+
+        for _ in 0..3 {
+            editor.move_down();
+        }
+        assert_eq!(editor.scroll_top, 0.0);
+        editor.move_down();
+        editor.move_down();
+        assert_eq!(editor.scroll_top, 20.0);
+
+        // Paging scrolls by a page, and the cursor stays within the margin.
+        editor.page_down();
+        assert_eq!(render_selections(&editor), vec![empty_selection(10, 0)]);
+        assert_eq!(editor.scroll_top, 70.0);
+        editor.page_up();
+        assert_eq!(render_selections(&editor), vec![empty_selection(5, 0)]);
+        assert_eq!(editor.scroll_top, 20.0);
+        editor.move_to_beginning_of_buffer();
+        assert_eq!(editor.scroll_top, 0.0);
+
+        editor.set_selections(&[Point::new(10, 0)..Point::new(10, 0)]);
+        editor.move_to_end_of_line();
+        assert_eq!(editor.scroll_top, 70.0);
+        assert_eq!(editor.scroll_left, 270.0);
+        editor.move_left();
+        assert_eq!(editor.scroll_left, 270.0);
+        editor.move_to_beginning_of_line();
+        assert_eq!(editor.scroll_left, 0.0);
+
+        // When the margin doesn't fit in the viewport, it shrinks to keep the cursor centered.
+        editor.set_height(30.0).set_autoscroll_margin(5);
+        editor.move_to_end_of_buffer();
+        assert_eq!(editor.scroll_top, 180.0);
+    }
+
+    #[test]
+    fn test_soft_wrap() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor
+            .buffer
+            .borrow_mut()
+            .splice(0..0, "abc def ghi\n\tjk");
+        editor
+            .set_height(100.0)
+            .set_width(50.0)
+            .set_line_height(10.0)
+            .set_char_width(10.0)
+            .set_tab_width(2);
+
+        let frame = editor.render();
+        assert_eq!(stringify_lines(&frame["lines"]), vec!["abc def ghi", "  jk"]);
+
+        editor.set_soft_wrap(true);
+        let frame = editor.render();
+        assert_eq!(
+            stringify_lines(&frame["lines"]),
+            vec!["abc ", "def ", "ghi", "  jk"]
+        );
+
+        // Vertical movement goes through display rows, aiming for the same display column.
+        editor.move_right();
+        editor.move_down();
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 5)]);
+        editor.move_down();
+        editor.move_down();
+        assert_eq!(render_selections(&editor), vec![empty_selection(1, 0)]);
+        editor.move_right();
+        editor.move_up();
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 10)]);
+        let frame = editor.render();
+        assert_eq!(frame["selections"], json!([selection((2, 2), (2, 2))]));
+
+        // Edits made directly to the buffer are reflected the next time the view renders.
+        editor.buffer.borrow_mut().splice(0..0, "xyz ");
+        let frame = editor.render();
+        assert_eq!(
+            stringify_lines(&frame["lines"]),
+            vec!["xyz ", "abc ", "def ", "ghi", "  jk"]
+        );
+
+        editor.set_soft_wrap_column(Some(8));
+        let frame = editor.render();
+        assert_eq!(
+            stringify_lines(&frame["lines"]),
+            vec!["xyz ", "abc ", "def ", "ghi", "  jk"]
+        );
+        editor.set_width(200.0);
+        let frame = editor.render();
+        assert_eq!(
+            stringify_lines(&frame["lines"]),
+            vec!["xyz abc ", "def ghi", "  jk"]
+        );
+    }
+
+    #[test]
+    fn test_folds() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor
+            .buffer
+            .borrow_mut()
+            .splice(0..0, "fn a() {\n    b();\n    c();\n}\nfn d() {\n    e();\n}");
+        editor
+            .set_height(20.0)
+            .set_width(100.0)
+            .set_line_height(10.0)
+            .set_char_width(10.0);
+
+        // Folding at a cursor hides the indentation block around it behind a placeholder.
+        editor.move_down();
+        editor.move_right();
+        editor.fold_selections();
+        let frame = editor.render();
+        assert_eq!(stringify_lines(&frame["lines"]), vec!["fn a() {\u{22ef}", "}"]);
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 8)]);
+
+        // Horizontal movement skips over the folded text.
+        editor.move_right();
+        assert_eq!(render_selections(&editor), vec![empty_selection(2, 8)]);
+        editor.move_left();
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 8)]);
+
+        // Scrolling is measured in display rows, so folded rows are skipped.
+        editor.set_scroll_top(10.0);
+        let frame = editor.render();
+        assert_eq!(frame["first_visible_row"], 1);
+        assert_eq!(stringify_lines(&frame["lines"]), vec!["}", "fn d() {"]);
+
+        editor.set_height(100.0);
+        editor.fold_all();
+        let frame = editor.render();
+        assert_eq!(
+            stringify_lines(&frame["lines"]),
+            vec!["fn a() {\u{22ef}", "}", "fn d() {\u{22ef}", "}"]
+        );
+
+        editor.unfold_all();
+        let frame = editor.render();
+        assert_eq!(
+            stringify_lines(&frame["lines"]),
+            vec!["fn a() {", "    b();", "    c();", "}", "fn d() {", "    e();", "}"]
+        );
+    }
+
+    #[test]
+    fn test_expand_selection() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        {
+            let mut buffer = editor.buffer.borrow_mut();
+            buffer.splice(0..0, "fn a() {\n    b(1, c);\n}");
+            buffer.set_language(Some(tree_sitter_rust::language()));
+        }
+        editor.set_selections(&[Point::new(1, 6)..Point::new(1, 6)]);
+
+        // Each expansion selects the smallest node that is larger than the selection.
+        editor.expand_selection();
+        assert_eq!(render_selections(&editor), vec![selection((1, 6), (1, 7))]);
+        editor.expand_selection();
+        assert_eq!(render_selections(&editor), vec![selection((1, 5), (1, 11))]);
+        editor.expand_selection();
+        assert_eq!(render_selections(&editor), vec![selection((1, 4), (1, 11))]);
+        editor.expand_selection();
+        assert_eq!(render_selections(&editor), vec![selection((1, 4), (1, 12))]);
+
+        // Shrinking retraces the expansions.
+        editor.shrink_selection();
+        assert_eq!(render_selections(&editor), vec![selection((1, 4), (1, 11))]);
+        editor.shrink_selection();
+        assert_eq!(render_selections(&editor), vec![selection((1, 5), (1, 11))]);
+
+        // Changing the selections by other means forgets the previous expansions.
+        editor.move_left();
+        editor.shrink_selection();
+        assert_eq!(render_selections(&editor), vec![empty_selection(1, 5)]);
+
+        // Expanding the whole buffer has no effect.
+        editor.select_all();
+        editor.expand_selection();
+        assert_eq!(render_selections(&editor), vec![selection((0, 0), (2, 1))]);
+        editor.shrink_selection();
+        assert_eq!(render_selections(&editor), vec![selection((0, 0), (2, 1))]);
+    }
+
+    #[test]
+    fn test_copy_and_paste() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "abc\ndef\nghi");
+
+        // With as many cursors as copied selections, each cursor pastes its own clip.
+        editor.set_selections(&[
+            Point::new(0, 1)..Point::new(0, 3),
+            Point::new(1, 0)..Point::new(1, 2),
+        ]);
+        editor.copy();
+        editor.set_selections(&[
+            Point::new(2, 0)..Point::new(2, 0),
+            Point::new(2, 3)..Point::new(2, 3),
+        ]);
+        editor.paste();
+        assert_eq!(editor.buffer.borrow().to_string(), "abc\ndef\nbcghide");
+        assert_eq!(
+            render_selections(&editor),
+            vec![empty_selection(2, 2), empty_selection(2, 7)]
+        );
+
+        // Otherwise, every cursor pastes all of the clips.
+        editor.set_selections(&[Point::new(0, 0)..Point::new(0, 0)]);
+        editor.paste();
+        assert_eq!(editor.buffer.borrow().to_string(), "bc\ndeabc\ndef\nbcghide");
+        assert_eq!(render_selections(&editor), vec![empty_selection(1, 2)]);
+        editor.undo();
+        assert_eq!(editor.buffer.borrow().to_string(), "abc\ndef\nbcghide");
+        assert_eq!(render_selections(&editor), vec![empty_selection(0, 0)]);
+
+        // Empty selections copy their entire line, which is pasted above the cursor's line.
+        editor.set_selections(&[Point::new(2, 4)..Point::new(2, 4)]);
+        editor.copy();
+        editor.set_selections(&[Point::new(1, 1)..Point::new(1, 1)]);
+        editor.paste();
+        assert_eq!(editor.buffer.borrow().to_string(), "abc\nbcghide\ndef\nbcghide");
+        assert_eq!(render_selections(&editor), vec![empty_selection(2, 1)]);
+
+        // Full lines replace non-empty selections like any other text.
+        editor.set_selections(&[Point::new(0, 0)..Point::new(0, 3)]);
+        editor.paste();
+        assert_eq!(editor.buffer.borrow().to_string(), "bcghide\n\nbcghide\ndef\nbcghide");
+
+        // Cutting an empty selection removes its line, and the clipboard is shared by views
+        // that are given the same clipboard.
+        editor.set_selections(&[Point::new(3, 1)..Point::new(3, 1)]);
+        editor.cut();
+        assert_eq!(editor.buffer.borrow().to_string(), "bcghide\n\nbcghide\nbcghide");
+        let mut other_editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        other_editor.set_clipboard(editor.clipboard.clone());
+        other_editor.paste();
+        assert_eq!(other_editor.buffer.borrow().to_string(), "def\n");
+    }
+
+    #[test]
+    fn test_indentation() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor.buffer.borrow_mut().splice(0..0, "fn a() {}\n  b");
+
+        // Newlines copy the indentation of the line they split, adjusted for indent tokens.
+        editor.set_selections(&[
+            Point::new(0, 8)..Point::new(0, 8),
+            Point::new(1, 3)..Point::new(1, 3),
+        ]);
+        editor.edit("\n");
+        assert_eq!(editor.buffer.borrow().to_string(), "fn a() {\n}\n  b\n  ");
+        assert_eq!(
+            render_selections(&editor),
+            vec![empty_selection(1, 0), empty_selection(3, 2)]
+        );
+        editor.set_selections(&[Point::new(0, 8)..Point::new(0, 8)]);
+        editor.edit("\n");
+        editor.edit("c");
+        assert_eq!(editor.buffer.borrow().to_string(), "fn a() {\n    c\n}\n  b\n  ");
+
+        // Indenting and outdenting affect every line touched by a selection, skipping blank lines.
+        editor.set_selections(&[
+            Point::new(0, 2)..Point::new(1, 1),
+            Point::new(1, 3)..Point::new(1, 3),
+            Point::new(3, 0)..Point::new(4, 0),
+        ]);
+        editor.indent();
+        assert_eq!(
+            editor.buffer.borrow().to_string(),
+            "    fn a() {\n        c\n}\n      b\n  "
+        );
+        editor.outdent();
+        editor.outdent();
+        assert_eq!(editor.buffer.borrow().to_string(), "fn a() {\nc\n}\nb\n  ");
+        editor.undo();
+        assert_eq!(editor.buffer.borrow().to_string(), "fn a() {\n    c\n}\n  b\n  ");
+
+        // Auto-indenting uses tabs when soft tabs are disabled.
+        {
+            let mut buffer = editor.buffer.borrow_mut();
+            let mut indent_settings = buffer.indent_settings().clone();
+            indent_settings.soft_tabs = false;
+            buffer.set_indent_settings(indent_settings);
+        }
+        editor.select_all();
+        editor.auto_indent_selection();
+        assert_eq!(editor.buffer.borrow().to_string(), "fn a() {\n\tc\n}\nb\n  ");
+        editor.set_selections(&[Point::new(2, 1)..Point::new(2, 1)]);
+        editor.indent();
+        assert_eq!(editor.buffer.borrow().to_string(), "fn a() {\n\tc\n\t}\nb\n  ");
+    }
+
+    #[test]
+    fn test_shared_buffer() {
+        let buffer = Rc::new(RefCell::new(Buffer::new(1)));
+        buffer.borrow_mut().splice(0..0, "abc");
+        let mut editor_1 = BufferView::new(buffer.clone());
+        let mut editor_2 = BufferView::new(buffer);
+        editor_2.set_height(100.0).set_line_height(10.0);
+        editor_2.set_selections(&[Point::new(0, 2)..Point::new(0, 2)]);
+        future::poll_fn(|| editor_2.poll().map(|_| Async::Ready(())))
+            .wait()
+            .unwrap();
+
+        // Edits made through one view notify every other view of the buffer.
+        editor_1.edit("xy");
+        future::poll_fn(|| {
+            assert_eq!(editor_2.poll(), Ok(Async::Ready(Some(()))));
+            assert_eq!(editor_2.poll(), Ok(Async::NotReady));
+            Ok::<_, ()>(Async::Ready(()))
+        }).wait()
+            .unwrap();
+        assert_eq!(stringify_lines(&editor_2.render()["lines"]), vec!["xyabc"]);
+        assert_eq!(render_selections(&editor_2), vec![empty_selection(0, 4)]);
+    }
+
+    #[test]
+    fn test_render_tokens() {
+        let mut editor = BufferView::new(Rc::new(RefCell::new(Buffer::new(1))));
+        editor
+            .buffer
+            .borrow_mut()
+            .splice(0..0, "fn a() {\n\tlet bcd = \"efg\";\n}");
+        editor
+            .set_height(100.0)
+            .set_width(100.0)
+            .set_line_height(10.0)
+            .set_char_width(10.0)
+            .set_tab_width(2)
+            .set_tokenizer(Some(tokenizer::RUST.clone()));
+        tokenize(&mut editor);
+        let frame = editor.render();
+        assert_eq!(
+            frame["tokens"],
+            json!([
+                [token(0, 2, "keyword"), token(3, 4, "entity.name.function")],
+                [token(2, 5, "keyword"), token(12, 17, "string")],
+                [],
+            ])
+        );
+
+        // Token columns are translated into display columns on each wrapped row.
+        editor.set_soft_wrap(true);
+        let frame = editor.render();
+        assert_eq!(
+            stringify_lines(&frame["lines"]),
+            vec!["fn a() {", "  let bcd ", "= \"efg\";", "}"]
+        );
+        assert_eq!(
+            frame["tokens"],
+            json!([
+                [token(0, 2, "keyword"), token(3, 4, "entity.name.function")],
+                [token(2, 5, "keyword")],
+                [token(2, 7, "string")],
+                [],
+            ])
+        );
+
+        // Edited rows keep their old tokens until they are tokenized again.
+        editor.buffer.borrow_mut().splice(0..0, "x");
+        let frame = editor.render();
+        assert_eq!(
+            frame["tokens"][0],
+            json!([token(0, 2, "keyword"), token(3, 4, "entity.name.function")])
+        );
+        tokenize(&mut editor);
+        let frame = editor.render();
+        assert_eq!(frame["tokens"][0], json!([token(4, 5, "entity.name.function")]));
+    }
+
     #[test]
     fn test_render() {
         let buffer = Rc::new(RefCell::new(Buffer::new(1)));
@@ -1121,6 +3645,24 @@ mod tests {
         assert_eq!(frame["selections"], json!([selection((2, 3), (2, 3))]));
     }
 
+    fn token(start: u32, end: u32, scope: &'static str) -> Token {
+        Token { start, end, scope }
+    }
+
+    /// Tokenizes the view's buffer synchronously, as if the view were mounted in a window.
+    fn tokenize(editor: &mut BufferView) {
+        let task = editor
+            .highlight_map
+            .borrow_mut()
+            .tokenize(&editor.buffer.borrow());
+        if let Some(task) = task {
+            task.for_each(|_| Ok(())).wait().unwrap();
+            future::poll_fn(|| editor.poll().map(|_| Async::Ready(())))
+                .wait()
+                .unwrap();
+        }
+    }
+
     fn stringify_lines(lines: &serde_json::Value) -> Vec<String> {
         lines
             .as_array()
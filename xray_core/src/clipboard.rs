@@ -0,0 +1,91 @@
+use std::rc::Rc;
+use std::sync::Arc;
+use futures::Stream;
+use notify_cell::NotifyCell;
+
+/// Text copied from buffer views. A single clipboard is shared by every window of the app, and it
+/// holds one clip per copied selection so that pasting with the same number of cursors can put
+/// each clip back at its own cursor.
+#[derive(Clone)]
+pub struct Clipboard(Rc<NotifyCell<Arc<Vec<Clip>>>>);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Clip {
+    pub text: String,
+    /// Whether the clip was copied from an empty selection and holds the entirety of its line,
+    /// which is pasted above the line containing the cursor rather than at the cursor.
+    pub is_full_line: bool,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Clipboard(Rc::new(NotifyCell::new(Arc::new(Vec::new()))))
+    }
+
+    pub fn write(&self, clips: Vec<Clip>) {
+        self.0.set(Arc::new(clips));
+    }
+
+    pub fn read(&self) -> Arc<Vec<Clip>> {
+        self.0.get().unwrap()
+    }
+
+    /// Returns a stream of the text of each subsequent write, so that it can be mirrored into the
+    /// system clipboard.
+    pub fn text_updates(&self) -> Box<Stream<Item = String, Error = ()>> {
+        Box::new(self.0.observe().map(|clips| join(&clips).text))
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Combines clips into one, separating them with newlines unless they already end with one.
+pub fn join(clips: &[Clip]) -> Clip {
+    let mut text = String::new();
+    for clip in clips {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&clip.text);
+    }
+    Clip {
+        text,
+        is_full_line: !clips.is_empty() && clips.iter().all(|clip| clip.is_full_line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{Async, Stream};
+
+    #[test]
+    fn test_join() {
+        assert_eq!(
+            join(&[clip("a", false), clip("b\n", true), clip("c", false)]),
+            clip("a\nb\nc", false)
+        );
+        assert_eq!(join(&[clip("a\n", true), clip("b\n", true)]), clip("a\nb\n", true));
+        assert_eq!(join(&[]), clip("", false));
+    }
+
+    #[test]
+    fn test_text_updates() {
+        let clipboard = Clipboard::new();
+        let mut updates = clipboard.text_updates();
+        clipboard.clone().write(vec![clip("a", false), clip("b", false)]);
+        assert_eq!(updates.poll(), Ok(Async::Ready(Some("a\nb".to_string()))));
+        assert_eq!(clipboard.read().as_slice(), &[clip("a", false), clip("b", false)]);
+    }
+
+    fn clip(text: &str, is_full_line: bool) -> Clip {
+        Clip {
+            text: text.to_string(),
+            is_full_line,
+        }
+    }
+}
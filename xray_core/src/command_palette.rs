@@ -0,0 +1,281 @@
+use futures::{Poll, Stream};
+use std::cmp;
+use std::rc::Weak;
+use std::cell::RefCell;
+use fuzzy_search::{Search, SearchResult};
+use window::{AvailableCommand, View, WindowHandle};
+use notify_cell::NotifyCell;
+use serde_json;
+
+pub trait CommandPaletteViewDelegate {
+    fn did_close(&mut self);
+}
+
+/// Lists the commands that were available when the palette was opened, ranked by how well their
+/// names match the query. Confirming a command closes the palette before dispatching the command to
+/// the view that offered it, so the palette no longer has focus by the time the command runs.
+pub struct CommandPaletteView<T: CommandPaletteViewDelegate> {
+    delegate: Weak<RefCell<T>>,
+    commands: Vec<AvailableCommand>,
+    query: String,
+    selected_index: usize,
+    search_results: Vec<CommandPaletteSearchResult>,
+    window_handle: Option<WindowHandle>,
+    updates: NotifyCell<()>,
+}
+
+struct CommandPaletteSearchResult {
+    command_index: usize,
+    score: usize,
+    match_indices: Vec<u16>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum CommandPaletteAction {
+    UpdateQuery { query: String },
+    SelectPrevious,
+    SelectNext,
+    Confirm,
+    Close,
+}
+
+impl<T: CommandPaletteViewDelegate> View for CommandPaletteView<T> {
+    fn component_name(&self) -> &'static str {
+        "CommandPalette"
+    }
+
+    fn render(&self) -> serde_json::Value {
+        json!({
+            "selected_index": self.selected_index,
+            "query": self.query.as_str(),
+            "results": self.search_results
+                .iter()
+                .map(|result| {
+                    let command = &self.commands[result.command_index];
+                    let keystrokes = command.keystrokes.as_ref().map(|keystrokes| {
+                        keystrokes
+                            .iter()
+                            .map(|keystroke| keystroke.to_string())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    });
+                    json!({
+                        "name": command.command.name,
+                        "match_indices": result.match_indices,
+                        "keystrokes": keystrokes,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn will_mount(&mut self, window_handle: WindowHandle) {
+        self.window_handle = Some(window_handle);
+    }
+
+    fn dispatch_action(&mut self, action: serde_json::Value) -> bool {
+        match serde_json::from_value(action) {
+            Ok(CommandPaletteAction::UpdateQuery { query }) => self.update_query(query),
+            Ok(CommandPaletteAction::SelectPrevious) => self.select_previous(),
+            Ok(CommandPaletteAction::SelectNext) => self.select_next(),
+            Ok(CommandPaletteAction::Confirm) => self.confirm(),
+            Ok(CommandPaletteAction::Close) => self.close(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl<T: CommandPaletteViewDelegate> Stream for CommandPaletteView<T> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.updates.poll()
+    }
+}
+
+impl<T: CommandPaletteViewDelegate> CommandPaletteView<T> {
+    pub fn new(delegate: Weak<RefCell<T>>, commands: Vec<AvailableCommand>) -> Self {
+        let mut view = Self {
+            delegate,
+            commands,
+            query: String::new(),
+            selected_index: 0,
+            search_results: Vec::new(),
+            window_handle: None,
+            updates: NotifyCell::new(()),
+        };
+        view.search();
+        view
+    }
+
+    fn update_query(&mut self, query: String) {
+        if self.query != query {
+            self.query = query;
+            self.search();
+        }
+    }
+
+    fn search(&mut self) {
+        let mut search_results = Vec::new();
+        for (command_index, command) in self.commands.iter().enumerate() {
+            let mut search = Search::new(&self.query);
+            search.set_subword_start_bonus(10).set_consecutive_bonus(5);
+            // Queries are lowercased by the search, so names are too in order to match them.
+            let characters = command.command.name.chars().map(|c| c.to_ascii_lowercase());
+            if let Some(SearchResult {
+                score,
+                match_indices,
+                ..
+            }) = search.process(characters, 1).finish()
+            {
+                search_results.push(CommandPaletteSearchResult {
+                    command_index,
+                    score,
+                    match_indices,
+                });
+            }
+        }
+
+        // The sort is stable, so commands with equal scores remain ordered from the focused view
+        // outward.
+        search_results.sort_by_key(|result| cmp::Reverse(result.score));
+        self.search_results = search_results;
+        self.selected_index = 0;
+        self.updates.set(());
+    }
+
+    fn select_previous(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+            self.updates.set(());
+        }
+    }
+
+    fn select_next(&mut self) {
+        if self.selected_index + 1 < self.search_results.len() {
+            self.selected_index += 1;
+            self.updates.set(());
+        }
+    }
+
+    fn confirm(&mut self) {
+        let command = match self.search_results.get(self.selected_index) {
+            Some(search_result) => self.commands[search_result.command_index].clone(),
+            None => return,
+        };
+        self.close();
+        self.window_handle
+            .as_ref()
+            .unwrap()
+            .dispatch_action(command.view_id, command.command.action);
+    }
+
+    fn close(&mut self) {
+        let delegate = self.delegate.upgrade().unwrap();
+        let mut delegate = delegate.borrow_mut();
+        delegate.did_close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use window::{Command, Window};
+
+    #[test]
+    fn test_search_and_confirm() {
+        let mut window = Window::new(None, 100.0);
+        let target = Rc::new(RefCell::new(TestView { actions: Vec::new() }));
+        let target_handle = window.handle().add_shared_view(target.clone());
+        let target_id = target_handle.view_id;
+        let delegate = Rc::new(RefCell::new(TestDelegate { close_count: 0 }));
+        let commands = vec![
+            command(target_id, "Select All", "SelectAll"),
+            command(target_id, "Save", "Save"),
+            command(target_id, "Toggle Soft Wrap", "ToggleSoftWrap"),
+        ];
+        let palette = Rc::new(RefCell::new(CommandPaletteView::new(
+            Rc::downgrade(&delegate),
+            commands,
+        )));
+        let palette_handle = window.handle().add_shared_view(palette.clone());
+        let palette_id = palette_handle.view_id;
+
+        // Every command is listed in its original order until there is a query.
+        assert_eq!(result_names(&palette), vec!["Select All", "Save", "Toggle Soft Wrap"]);
+
+        // Matches at the start of words rank highest.
+        window.dispatch_action(palette_id, json!({"type": "UpdateQuery", "query": "T"}));
+        assert_eq!(result_names(&palette), vec!["Toggle Soft Wrap", "Select All"]);
+        window.dispatch_action(palette_id, json!({"type": "SelectNext"}));
+        assert_eq!(palette.borrow().selected_index, 1);
+
+        window.dispatch_action(palette_id, json!({"type": "UpdateQuery", "query": "sw"}));
+        assert_eq!(result_names(&palette), vec!["Toggle Soft Wrap"]);
+        assert_eq!(palette.borrow().selected_index, 0);
+        assert_eq!(palette.borrow().render()["results"][0]["match_indices"], json!([7, 12]));
+
+        window.dispatch_action(palette_id, json!({"type": "Confirm"}));
+        assert_eq!(delegate.borrow().close_count, 1);
+        assert_eq!(target.borrow().actions, vec![json!({"type": "ToggleSoftWrap"})]);
+    }
+
+    fn command(view_id: usize, name: &'static str, action_type: &str) -> AvailableCommand {
+        AvailableCommand {
+            view_id,
+            command: Command::new(name, json!({ "type": action_type })),
+            keystrokes: None,
+        }
+    }
+
+    fn result_names(palette: &Rc<RefCell<CommandPaletteView<TestDelegate>>>) -> Vec<String> {
+        palette.borrow().render()["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    struct TestDelegate {
+        close_count: usize,
+    }
+
+    impl CommandPaletteViewDelegate for TestDelegate {
+        fn did_close(&mut self) {
+            self.close_count += 1;
+        }
+    }
+
+    struct TestView {
+        actions: Vec<serde_json::Value>,
+    }
+
+    impl View for TestView {
+        fn component_name(&self) -> &'static str {
+            "TestView"
+        }
+
+        fn render(&self) -> serde_json::Value {
+            json!({})
+        }
+
+        fn dispatch_action(&mut self, action: serde_json::Value) -> bool {
+            self.actions.push(action);
+            true
+        }
+    }
+
+    impl Stream for TestView {
+        type Item = ();
+        type Error = ();
+
+        fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+            Ok(::futures::Async::NotReady)
+        }
+    }
+}
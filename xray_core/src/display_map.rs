@@ -0,0 +1,1008 @@
+use std::char;
+use std::cmp::{self, Ordering};
+use std::ops::{Add, AddAssign, Range};
+use buffer::{Anchor, Buffer, Point, Version};
+use tree::{self, SeekBias, Tree};
+
+/// Translates between points in a buffer and points on the screen, where folded ranges are
+/// replaced by a placeholder, tabs are expanded to the next tab stop and lines longer than the
+/// wrap width are split across several display rows. The layout of each line is stored in a tree
+/// so that conversions and edits only need to visit the rows they touch.
+pub struct DisplayMap {
+    lines: Tree<Line>,
+    folds: Vec<Range<Anchor>>,
+    fold_ranges: Vec<Range<Point>>,
+    tab_width: u32,
+    wrap_width: Option<u32>,
+    version: Version,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct DisplayPoint {
+    pub row: u32,
+    pub column: u32,
+}
+
+/// Determines which side of a character that spans several display columns, such as a tab, a
+/// display point inside of it is clipped to. Also determines which end of a fold a point inside
+/// of it is clipped to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Bias {
+    Left,
+    Right,
+}
+
+/// The layout of a line on the screen, which spans several buffer rows when it contains folds.
+/// Each display row after the first one starts at one of the wrap columns, which are measured in
+/// the line's folded text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Line {
+    buffer_rows: u32,
+    wrap_columns: Vec<u32>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct LineSummary {
+    buffer_rows: u32,
+    display_rows: u32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct BufferRow(u32);
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct DisplayRow(u32);
+
+/// The text of a line on the screen before wrapping and tab expansion, in which every fold is
+/// replaced by a single placeholder character.
+struct FoldedLine {
+    text: Vec<u16>,
+    segments: Vec<Segment>,
+}
+
+/// A run of the folded text, which either comes from a single buffer row or is the placeholder
+/// for the fold between `start` and `end`.
+struct Segment {
+    column: u32,
+    start: Point,
+    end: Point,
+    is_fold: bool,
+}
+
+const FOLD_PLACEHOLDER: u16 = 0x22ef;
+
+impl DisplayMap {
+    pub fn new(buffer: &Buffer, tab_width: u32, wrap_width: Option<u32>) -> Self {
+        let mut display_map = Self {
+            lines: Tree::new(),
+            folds: Vec::new(),
+            fold_ranges: Vec::new(),
+            tab_width: cmp::max(1, tab_width),
+            wrap_width,
+            version: buffer.current_version(),
+        };
+        display_map.layout(buffer);
+        display_map
+    }
+
+    pub fn set_tab_width(&mut self, buffer: &Buffer, tab_width: u32) {
+        let tab_width = cmp::max(1, tab_width);
+        if tab_width != self.tab_width {
+            self.tab_width = tab_width;
+            self.layout(buffer);
+        }
+    }
+
+    /// Sets the number of display columns after which lines are soft-wrapped, or disables soft
+    /// wrap when `None`.
+    pub fn set_wrap_width(&mut self, buffer: &Buffer, wrap_width: Option<u32>) {
+        let wrap_width = wrap_width.map(|wrap_width| cmp::max(1, wrap_width));
+        if wrap_width != self.wrap_width {
+            self.wrap_width = wrap_width;
+            self.layout(buffer);
+        }
+    }
+
+    /// Brings the map up to date with the buffer by laying out again only the rows that were
+    /// edited since the last call.
+    pub fn sync(&mut self, buffer: &Buffer) {
+        let version = buffer.current_version();
+        if version == self.version {
+            return;
+        }
+
+        // Edits are expressed in the coordinates of the buffer right before they were applied, so
+        // we splice in placeholders for the rows they touched first and then lay out those rows
+        // once the tree's rows line up with the buffer's current contents.
+        let mut dirty_rows: Vec<Range<u32>> = Vec::new();
+        for edit in buffer.edits_since(self.version) {
+            let old_rows =
+                self.line_boundaries(edit.old_range.start.row..edit.old_range.end.row + 1);
+            let new_rows = old_rows.start
+                ..old_rows.end - edit.old_range.end.row + edit.new_range.end.row;
+            let placeholders = (new_rows.start..new_rows.end).map(|_| Line {
+                buffer_rows: 1,
+                wrap_columns: Vec::new(),
+            });
+            self.splice_lines(old_rows.clone(), placeholders);
+            dirty_rows = interpolate_dirty_rows(dirty_rows, old_rows, new_rows);
+        }
+        self.version = version;
+
+        self.update_fold_ranges(buffer);
+        self.relayout(buffer, dirty_rows);
+    }
+
+    /// Hides the given range behind a placeholder. The fold is anchored to the text around it,
+    /// so that it moves along with edits, and text inserted at either end stays visible.
+    pub fn fold(&mut self, buffer: &Buffer, range: Range<Point>) {
+        self.sync(buffer);
+        if self.add_fold(buffer, range.clone()) {
+            self.update_fold_ranges(buffer);
+            let dirty_rows = range.start.row..range.end.row + 1;
+            self.relayout(buffer, vec![dirty_rows]);
+        }
+    }
+
+    /// Folds every indentation block in the buffer, including nested ones, so that unfolding a
+    /// block reveals the blocks inside of it still folded.
+    pub fn fold_all(&mut self, buffer: &Buffer) {
+        self.sync(buffer);
+        for row in 0..buffer.max_point().row + 1 {
+            if let Some(range) = self.indentation_block_starting_at(buffer, row) {
+                self.add_fold(buffer, range);
+            }
+        }
+        self.update_fold_ranges(buffer);
+        self.layout(buffer);
+    }
+
+    /// Removes every fold that intersects any of the rows spanned by the given range.
+    pub fn unfold(&mut self, buffer: &Buffer, range: Range<Point>) {
+        self.sync(buffer);
+        let mut dirty_rows = Vec::new();
+        self.folds.retain(|fold| {
+            let fold_start = buffer.point_for_anchor(&fold.start).unwrap();
+            let fold_end = buffer.point_for_anchor(&fold.end).unwrap();
+            if fold_start.row <= range.end.row && fold_end.row >= range.start.row {
+                dirty_rows.push(fold_start.row..fold_end.row + 1);
+                false
+            } else {
+                true
+            }
+        });
+        self.update_fold_ranges(buffer);
+        self.relayout(buffer, dirty_rows);
+    }
+
+    pub fn unfold_all(&mut self, buffer: &Buffer) {
+        self.folds.clear();
+        self.fold_ranges.clear();
+        self.layout(buffer);
+    }
+
+    /// Returns the innermost indentation block containing the given row, which starts at the end
+    /// of a row and extends to the end of the last non-blank row after it that is indented
+    /// further. A row that starts a block is considered to be inside of it.
+    pub fn indentation_block(&self, buffer: &Buffer, row: u32) -> Option<Range<Point>> {
+        if let Some(range) = self.indentation_block_starting_at(buffer, row) {
+            return Some(range);
+        }
+
+        let mut min_indentation = self.indentation(&buffer.line(row).unwrap());
+        for start_row in (0..row).rev() {
+            let indentation = self.indentation(&buffer.line(start_row).unwrap());
+            if let Some(indentation) = indentation {
+                match min_indentation {
+                    Some(min_indentation) if indentation >= min_indentation => {}
+                    _ => {
+                        let range = self.indentation_block_starting_at(buffer, start_row);
+                        match range {
+                            Some(ref range) if range.end.row >= row => return Some(range.clone()),
+                            _ => min_indentation = Some(indentation),
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the given point, moved to the start or end of the fold containing it, if any.
+    pub fn clip_point(&self, point: Point, bias: Bias) -> Point {
+        let index = match self.fold_ranges
+            .binary_search_by(|range| range.start.cmp(&point))
+        {
+            Ok(_) => return point,
+            Err(index) => index,
+        };
+
+        if index > 0 {
+            let range = &self.fold_ranges[index - 1];
+            if point < range.end {
+                return match bias {
+                    Bias::Left => range.start,
+                    Bias::Right => range.end,
+                };
+            }
+        }
+        point
+    }
+
+    /// Returns the number of rows on the screen, which is at least one.
+    pub fn row_count(&self) -> u32 {
+        self.lines.len::<DisplayRow>().0
+    }
+
+    pub fn to_display_point(&self, buffer: &Buffer, point: Point) -> DisplayPoint {
+        debug_assert_eq!(self.version, buffer.current_version());
+
+        let mut cursor = self.lines.cursor();
+        cursor.seek(&BufferRow(point.row), SeekBias::Right);
+        let line = cursor.item().unwrap();
+        let folded_line = self.folded_line(buffer, cursor.start::<BufferRow>().0);
+        let column = folded_line.to_folded_column(point);
+        let wrap_index = line.wrap_columns
+            .iter()
+            .take_while(|wrap_column| **wrap_column <= column)
+            .count();
+        let row_start = line.row_start(wrap_index);
+        let text = &folded_line.text;
+        DisplayPoint::new(
+            cursor.start::<DisplayRow>().0 + wrap_index as u32,
+            self.expanded_column(text, column) - self.expanded_column(text, row_start),
+        )
+    }
+
+    /// Returns the buffer point displayed at the given display point, clipping it to the nearest
+    /// valid position. Display points past the end of a wrapped row are clipped to the last
+    /// character on that row, because the wrap column itself is displayed on the next row.
+    pub fn to_buffer_point(&self, buffer: &Buffer, point: DisplayPoint, bias: Bias) -> Point {
+        debug_assert_eq!(self.version, buffer.current_version());
+
+        let mut cursor = self.lines.cursor();
+        cursor.seek(&DisplayRow(point.row), SeekBias::Right);
+        let line = match cursor.item() {
+            Some(line) => line,
+            None => return buffer.max_point(),
+        };
+        let wrap_index = (point.row - cursor.start::<DisplayRow>().0) as usize;
+        let folded_line = self.folded_line(buffer, cursor.start::<BufferRow>().0);
+        let text = &folded_line.text;
+        let row_start = line.row_start(wrap_index);
+        let row_end = match line.wrap_columns.get(wrap_index) {
+            Some(wrap_column) => wrap_column - 1,
+            None => text.len() as u32,
+        };
+
+        let target_column = self.expanded_column(text, row_start) + point.column;
+        let mut display_column = self.expanded_column(text, row_start);
+        let mut column = row_start;
+        while column < row_end {
+            let width = self.char_width(text[column as usize], display_column);
+            if display_column + width > target_column {
+                if bias == Bias::Right && display_column < target_column {
+                    column += 1;
+                }
+                break;
+            }
+            display_column += width;
+            column += 1;
+        }
+
+        folded_line.to_buffer_point(column)
+    }
+
+    /// Returns the text of the given display rows, with tabs expanded to spaces and folds
+    /// replaced by placeholders.
+    pub fn lines(&self, buffer: &Buffer, rows: Range<u32>) -> Vec<String> {
+        debug_assert_eq!(self.version, buffer.current_version());
+
+        let mut lines = Vec::new();
+        let mut cursor = self.lines.cursor();
+        cursor.seek(&DisplayRow(rows.start), SeekBias::Right);
+        while let Some(line) = cursor.item() {
+            let start_row = cursor.start::<DisplayRow>().0;
+            if start_row >= rows.end {
+                break;
+            }
+
+            let text = self.folded_line(buffer, cursor.start::<BufferRow>().0).text;
+            for wrap_index in 0..line.wrap_columns.len() + 1 {
+                let display_row = start_row + wrap_index as u32;
+                if display_row < rows.start {
+                    continue;
+                } else if display_row >= rows.end {
+                    break;
+                }
+
+                let row_start = line.row_start(wrap_index);
+                let row_end = line.wrap_columns
+                    .get(wrap_index)
+                    .cloned()
+                    .unwrap_or(text.len() as u32);
+                let mut display_column = self.expanded_column(&text, row_start);
+                let mut display_line = Vec::new();
+                for &code_unit in &text[row_start as usize..row_end as usize] {
+                    let width = self.char_width(code_unit, display_column);
+                    if code_unit == b'\t' as u16 {
+                        display_line.extend((0..width).map(|_| b' ' as u16));
+                    } else {
+                        display_line.push(code_unit);
+                    }
+                    display_column += width;
+                }
+                lines.push(String::from_utf16_lossy(&display_line));
+            }
+            cursor.next();
+        }
+        lines
+    }
+
+    fn add_fold(&mut self, buffer: &Buffer, range: Range<Point>) -> bool {
+        if range.start >= range.end {
+            return false;
+        }
+
+        let already_folded = self.folds.iter().any(|fold| {
+            buffer.point_for_anchor(&fold.start).unwrap() == range.start
+                && buffer.point_for_anchor(&fold.end).unwrap() == range.end
+        });
+        if already_folded {
+            false
+        } else {
+            self.folds.push(
+                buffer.anchor_after_point(range.start).unwrap()
+                    ..buffer.anchor_before_point(range.end).unwrap(),
+            );
+            true
+        }
+    }
+
+    /// Resolves the folds to sorted, disjoint ranges, discarding any folds whose text has been
+    /// deleted.
+    fn update_fold_ranges(&mut self, buffer: &Buffer) {
+        self.folds.retain(|fold| {
+            buffer.cmp_anchors(&fold.start, &fold.end).unwrap() == Ordering::Less
+        });
+
+        let mut ranges = self.folds
+            .iter()
+            .map(|fold| {
+                buffer.point_for_anchor(&fold.start).unwrap()
+                    ..buffer.point_for_anchor(&fold.end).unwrap()
+            })
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|range| range.start);
+
+        self.fold_ranges.clear();
+        for range in ranges {
+            if let Some(last_range) = self.fold_ranges.last_mut() {
+                if range.start <= last_range.end {
+                    last_range.end = cmp::max(last_range.end, range.end);
+                    continue;
+                }
+            }
+            self.fold_ranges.push(range);
+        }
+    }
+
+    fn indentation_block_starting_at(&self, buffer: &Buffer, row: u32) -> Option<Range<Point>> {
+        let indentation = self.indentation(&buffer.line(row).unwrap())?;
+        let mut end_row = None;
+        for next_row in row + 1..buffer.max_point().row + 1 {
+            match self.indentation(&buffer.line(next_row).unwrap()) {
+                Some(next_indentation) if next_indentation > indentation => {
+                    end_row = Some(next_row)
+                }
+                Some(_) => break,
+                None => {}
+            }
+        }
+
+        end_row.map(|end_row| {
+            let start = Point::new(row, buffer.len_for_row(row).unwrap());
+            start..Point::new(end_row, buffer.len_for_row(end_row).unwrap())
+        })
+    }
+
+    /// Returns the width of the given line's leading whitespace, or `None` if it's blank.
+    fn indentation(&self, text: &[u16]) -> Option<u32> {
+        let mut indentation = 0;
+        for &code_unit in text {
+            if is_whitespace(code_unit) {
+                indentation += self.char_width(code_unit, indentation);
+            } else {
+                return Some(indentation);
+            }
+        }
+        None
+    }
+
+    fn layout(&mut self, buffer: &Buffer) {
+        let row_count = buffer.max_point().row + 1;
+        let mut lines = Tree::new();
+        lines.extend(self.layout_rows(buffer, 0..row_count));
+        self.lines = lines;
+        self.version = buffer.current_version();
+    }
+
+    /// Lays out the given rows again, after extending them so they don't split any line.
+    fn relayout(&mut self, buffer: &Buffer, dirty_rows: Vec<Range<u32>>) {
+        let mut dirty_rows = dirty_rows
+            .into_iter()
+            .map(|rows| self.expand_to_line_boundaries(rows))
+            .collect::<Vec<_>>();
+        dirty_rows.sort_by_key(|rows| rows.start);
+
+        let mut merged_rows: Vec<Range<u32>> = Vec::new();
+        for rows in dirty_rows {
+            if let Some(last_rows) = merged_rows.last_mut() {
+                if rows.start <= last_rows.end {
+                    last_rows.end = cmp::max(last_rows.end, rows.end);
+                    continue;
+                }
+            }
+            merged_rows.push(rows);
+        }
+
+        for rows in merged_rows {
+            let lines = self.layout_rows(buffer, rows.clone());
+            self.splice_lines(rows, lines);
+        }
+    }
+
+    /// Extends the given rows until they start and end on line boundaries both in the tree as it
+    /// is and once the current folds are applied.
+    fn expand_to_line_boundaries(&self, mut rows: Range<u32>) -> Range<u32> {
+        loop {
+            let mut expanded_rows = self.line_boundaries(rows.clone());
+            for range in &self.fold_ranges {
+                if range.start.row < expanded_rows.end && range.end.row >= expanded_rows.start {
+                    expanded_rows.start = cmp::min(expanded_rows.start, range.start.row);
+                    expanded_rows.end = cmp::max(expanded_rows.end, range.end.row + 1);
+                }
+            }
+
+            if expanded_rows == rows {
+                return rows;
+            }
+            rows = expanded_rows;
+        }
+    }
+
+    /// Extends the given rows to the boundaries of the lines in the tree that contain them.
+    fn line_boundaries(&self, rows: Range<u32>) -> Range<u32> {
+        let mut cursor = self.lines.cursor();
+        cursor.seek(&BufferRow(rows.start), SeekBias::Right);
+        let start = cursor.start::<BufferRow>().0;
+        cursor.seek(&BufferRow(rows.end - 1), SeekBias::Right);
+        let end = match cursor.item() {
+            Some(line) => cursor.start::<BufferRow>().0 + line.buffer_rows,
+            None => rows.end,
+        };
+        start..end
+    }
+
+    fn layout_rows(&self, buffer: &Buffer, rows: Range<u32>) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut row = rows.start;
+        while row < rows.end {
+            let folded_line = self.folded_line(buffer, row);
+            let buffer_rows = folded_line.segments.last().unwrap().end.row - row + 1;
+            lines.push(Line {
+                buffer_rows,
+                wrap_columns: self.wrap_columns(&folded_line.text),
+            });
+            row += buffer_rows;
+        }
+        debug_assert_eq!(row, rows.end);
+        lines
+    }
+
+    /// Builds the text of the line starting at the given buffer row, following any folds that
+    /// start on it to the rows where they end.
+    fn folded_line(&self, buffer: &Buffer, row: u32) -> FoldedLine {
+        let mut folded_line = FoldedLine {
+            text: Vec::new(),
+            segments: Vec::new(),
+        };
+        let mut point = Point::new(row, 0);
+        let mut text = buffer.line(row).unwrap();
+        let mut fold_index = match self.fold_ranges
+            .binary_search_by(|range| range.start.cmp(&point))
+        {
+            Ok(index) => index,
+            Err(index) => index,
+        };
+
+        loop {
+            let fold_range = match self.fold_ranges.get(fold_index) {
+                Some(range) if range.start.row == point.row => Some(range),
+                _ => None,
+            };
+            let segment_end = match fold_range {
+                Some(range) => range.start.column,
+                None => text.len() as u32,
+            };
+            folded_line.segments.push(Segment {
+                column: folded_line.text.len() as u32,
+                start: point,
+                end: Point::new(point.row, segment_end),
+                is_fold: false,
+            });
+            folded_line
+                .text
+                .extend_from_slice(&text[point.column as usize..segment_end as usize]);
+
+            if let Some(range) = fold_range {
+                folded_line.segments.push(Segment {
+                    column: folded_line.text.len() as u32,
+                    start: range.start,
+                    end: range.end,
+                    is_fold: true,
+                });
+                folded_line.text.push(FOLD_PLACEHOLDER);
+                if range.end.row != point.row {
+                    text = buffer.line(range.end.row).unwrap();
+                }
+                point = range.end;
+                fold_index += 1;
+            } else {
+                break;
+            }
+        }
+        folded_line
+    }
+
+    /// Wraps the line greedily, preferring to start each new display row after whitespace.
+    /// Whitespace is allowed to overflow the wrap width so that it never starts a display row,
+    /// and a word that doesn't fit on a row by itself is split wherever it overflows.
+    fn wrap_columns(&self, text: &[u16]) -> Vec<u32> {
+        let mut wrap_columns = Vec::new();
+        if let Some(wrap_width) = self.wrap_width {
+            // Both the row start and the last boundary are (column, display column) pairs.
+            let mut row_start = (0, 0);
+            let mut last_boundary = None;
+            let mut display_column = 0;
+            for (column, &code_unit) in text.iter().enumerate() {
+                let column = column as u32;
+                let width = self.char_width(code_unit, display_column);
+                if !is_whitespace(code_unit) {
+                    while column > row_start.0
+                        && display_column + width - row_start.1 > wrap_width
+                    {
+                        row_start = match last_boundary.take() {
+                            Some(boundary) => boundary,
+                            None => (column, display_column),
+                        };
+                        wrap_columns.push(row_start.0);
+                    }
+                }
+
+                display_column += width;
+                if is_whitespace(code_unit) {
+                    last_boundary = Some((column + 1, display_column));
+                }
+            }
+        }
+        wrap_columns
+    }
+
+    fn splice_lines<I: IntoIterator<Item = Line>>(&mut self, rows: Range<u32>, lines: I) {
+        let old_lines = self.lines.clone();
+        let mut cursor = old_lines.cursor();
+        let mut new_lines = cursor.build_prefix(&BufferRow(rows.start), SeekBias::Right);
+        new_lines.extend(lines);
+        cursor.seek(&BufferRow(rows.end), SeekBias::Right);
+        new_lines.push_tree(cursor.build_suffix());
+        self.lines = new_lines;
+    }
+
+    /// Returns the display column at which the given column of a folded line starts.
+    fn expanded_column(&self, text: &[u16], column: u32) -> u32 {
+        let mut display_column = 0;
+        for &code_unit in &text[..column as usize] {
+            display_column += self.char_width(code_unit, display_column);
+        }
+        display_column
+    }
+
+    fn char_width(&self, code_unit: u16, display_column: u32) -> u32 {
+        if code_unit == b'\t' as u16 {
+            self.tab_width - display_column % self.tab_width
+        } else {
+            1
+        }
+    }
+}
+
+impl DisplayPoint {
+    pub fn new(row: u32, column: u32) -> Self {
+        DisplayPoint { row, column }
+    }
+}
+
+impl FoldedLine {
+    fn to_folded_column(&self, point: Point) -> u32 {
+        for segment in &self.segments {
+            if segment.is_fold {
+                if segment.start < point && point < segment.end {
+                    return segment.column;
+                }
+            } else if segment.start <= point && point <= segment.end {
+                return segment.column + point.column - segment.start.column;
+            }
+        }
+        self.text.len() as u32
+    }
+
+    fn to_buffer_point(&self, column: u32) -> Point {
+        for segment in self.segments.iter().filter(|segment| !segment.is_fold) {
+            if column <= segment.column + segment.end.column - segment.start.column {
+                return Point::new(
+                    segment.start.row,
+                    segment.start.column + column - segment.column,
+                );
+            }
+        }
+        self.segments.last().unwrap().end
+    }
+}
+
+impl Line {
+    fn row_start(&self, wrap_index: usize) -> u32 {
+        if wrap_index == 0 {
+            0
+        } else {
+            self.wrap_columns[wrap_index - 1]
+        }
+    }
+}
+
+impl tree::Item for Line {
+    type Summary = LineSummary;
+
+    fn summarize(&self) -> Self::Summary {
+        LineSummary {
+            buffer_rows: self.buffer_rows,
+            display_rows: self.wrap_columns.len() as u32 + 1,
+        }
+    }
+}
+
+impl<'a> AddAssign<&'a LineSummary> for LineSummary {
+    fn add_assign(&mut self, other: &Self) {
+        self.buffer_rows += other.buffer_rows;
+        self.display_rows += other.display_rows;
+    }
+}
+
+impl tree::Dimension for BufferRow {
+    type Summary = LineSummary;
+
+    fn from_summary(summary: &Self::Summary) -> Self {
+        BufferRow(summary.buffer_rows)
+    }
+}
+
+impl<'a> Add<&'a Self> for BufferRow {
+    type Output = Self;
+
+    fn add(self, other: &Self) -> Self {
+        BufferRow(self.0 + other.0)
+    }
+}
+
+impl tree::Dimension for DisplayRow {
+    type Summary = LineSummary;
+
+    fn from_summary(summary: &Self::Summary) -> Self {
+        DisplayRow(summary.display_rows)
+    }
+}
+
+impl<'a> Add<&'a Self> for DisplayRow {
+    type Output = Self;
+
+    fn add(self, other: &Self) -> Self {
+        DisplayRow(self.0 + other.0)
+    }
+}
+
+/// Maps sorted, disjoint ranges of rows that need to be laid out across an edit that replaced
+/// `old_rows` with `new_rows`, merging any ranges the edit overlaps into its new rows.
+fn interpolate_dirty_rows(
+    dirty_rows: Vec<Range<u32>>,
+    old_rows: Range<u32>,
+    new_rows: Range<u32>,
+) -> Vec<Range<u32>> {
+    let mut result = Vec::with_capacity(dirty_rows.len() + 1);
+    let mut edited_rows = new_rows.clone();
+    let mut pushed_edited_rows = false;
+    for rows in dirty_rows {
+        if rows.end <= old_rows.start {
+            result.push(rows);
+        } else if rows.start >= old_rows.end {
+            if !pushed_edited_rows {
+                result.push(edited_rows.clone());
+                pushed_edited_rows = true;
+            }
+            let start = rows.start - old_rows.end + new_rows.end;
+            let end = rows.end - old_rows.end + new_rows.end;
+            result.push(start..end);
+        } else {
+            edited_rows.start = cmp::min(edited_rows.start, rows.start);
+            if rows.end > old_rows.end {
+                edited_rows.end = rows.end - old_rows.end + new_rows.end;
+            }
+        }
+    }
+    if !pushed_edited_rows {
+        result.push(edited_rows);
+    }
+    result
+}
+
+fn is_whitespace(code_unit: u16) -> bool {
+    match char::from_u32(code_unit as u32) {
+        Some(c) => c.is_whitespace(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate rand;
+
+    use super::*;
+    use self::rand::{Rng, SeedableRng, StdRng};
+
+    #[test]
+    fn test_tab_expansion() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "\tab\tc\n  \td");
+        let mut display_map = DisplayMap::new(&buffer, 4, None);
+        assert_eq!(display_map.lines(&buffer, 0..2), vec!["    ab  c", "    d"]);
+
+        assert_eq!(
+            display_map.to_display_point(&buffer, Point::new(0, 1)),
+            DisplayPoint::new(0, 4)
+        );
+        assert_eq!(
+            display_map.to_display_point(&buffer, Point::new(0, 4)),
+            DisplayPoint::new(0, 8)
+        );
+        assert_eq!(
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(0, 2), Bias::Left),
+            Point::new(0, 0)
+        );
+        assert_eq!(
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(0, 2), Bias::Right),
+            Point::new(0, 1)
+        );
+        assert_eq!(
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(1, 20), Bias::Left),
+            Point::new(1, 4)
+        );
+        assert_eq!(
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(5, 0), Bias::Left),
+            Point::new(1, 4)
+        );
+
+        display_map.set_tab_width(&buffer, 2);
+        assert_eq!(display_map.lines(&buffer, 0..2), vec!["  ab  c", "    d"]);
+    }
+
+    #[test]
+    fn test_soft_wrap() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "abc defg hi\njklmnopqrs\n\ntu");
+        let mut display_map = DisplayMap::new(&buffer, 4, Some(5));
+        assert_eq!(display_map.row_count(), 7);
+        assert_eq!(
+            display_map.lines(&buffer, 0..8),
+            vec!["abc ", "defg ", "hi", "jklmn", "opqrs", "", "tu"]
+        );
+        assert_eq!(display_map.lines(&buffer, 2..4), vec!["hi", "jklmn"]);
+
+        assert_eq!(
+            display_map.to_display_point(&buffer, Point::new(0, 4)),
+            DisplayPoint::new(1, 0)
+        );
+        assert_eq!(
+            display_map.to_display_point(&buffer, Point::new(1, 7)),
+            DisplayPoint::new(4, 2)
+        );
+        assert_eq!(
+            display_map.to_display_point(&buffer, Point::new(3, 2)),
+            DisplayPoint::new(6, 2)
+        );
+        assert_eq!(
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(0, 10), Bias::Left),
+            Point::new(0, 3)
+        );
+        assert_eq!(
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(2, 10), Bias::Left),
+            Point::new(0, 11)
+        );
+        assert_eq!(
+            display_map.to_display_point(&buffer, buffer.max_point()),
+            DisplayPoint::new(6, 2)
+        );
+
+        // Edits only lay out the rows they touch.
+        buffer.splice(12..12, "x ");
+        display_map.sync(&buffer);
+        assert_eq!(
+            display_map.lines(&buffer, 0..8),
+            vec!["abc ", "defg ", "hi", "x ", "jklmn", "opqrs", "", "tu"]
+        );
+
+        display_map.set_wrap_width(&buffer, None);
+        assert_eq!(
+            display_map.lines(&buffer, 0..8),
+            vec!["abc defg hi", "x jklmnopqrs", "", "tu"]
+        );
+    }
+
+    #[test]
+    fn test_folds() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "abc\ndefg\nhijk\nlmn");
+        let mut display_map = DisplayMap::new(&buffer, 4, None);
+        display_map.fold(&buffer, Point::new(0, 2)..Point::new(2, 1));
+        assert_eq!(display_map.row_count(), 2);
+        assert_eq!(display_map.lines(&buffer, 0..2), vec!["ab\u{22ef}ijk", "lmn"]);
+
+        assert_eq!(
+            display_map.to_display_point(&buffer, Point::new(1, 2)),
+            DisplayPoint::new(0, 2)
+        );
+        assert_eq!(
+            display_map.to_display_point(&buffer, Point::new(2, 3)),
+            DisplayPoint::new(0, 5)
+        );
+        assert_eq!(
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(0, 3), Bias::Left),
+            Point::new(2, 1)
+        );
+        assert_eq!(
+            display_map.to_buffer_point(&buffer, DisplayPoint::new(1, 1), Bias::Left),
+            Point::new(3, 1)
+        );
+        assert_eq!(display_map.clip_point(Point::new(1, 0), Bias::Left), Point::new(0, 2));
+        assert_eq!(display_map.clip_point(Point::new(1, 0), Bias::Right), Point::new(2, 1));
+
+        // Folds move with edits, and text inserted at either end stays visible.
+        buffer.splice(2..2, "X");
+        buffer.splice(11..11, "Y");
+        display_map.sync(&buffer);
+        assert_eq!(display_map.lines(&buffer, 0..2), vec!["abX\u{22ef}Yijk", "lmn"]);
+
+        // Overlapping folds are merged, and folds whose text is deleted are discarded.
+        display_map.fold(&buffer, Point::new(1, 2)..Point::new(3, 1));
+        assert_eq!(display_map.lines(&buffer, 0..2), vec!["abX\u{22ef}mn"]);
+        assert_eq!(display_map.fold_ranges, vec![Point::new(0, 3)..Point::new(3, 1)]);
+        buffer.splice(3..11, "");
+        display_map.sync(&buffer);
+        assert_eq!(buffer.to_string(), "abXYijk\nlmn");
+        assert_eq!(display_map.fold_ranges, vec![Point::new(0, 3)..Point::new(1, 1)]);
+        assert_eq!(display_map.lines(&buffer, 0..2), vec!["abX\u{22ef}mn"]);
+
+        display_map.unfold(&buffer, Point::new(1, 0)..Point::new(1, 0));
+        assert_eq!(display_map.lines(&buffer, 0..2), vec!["abXYijk", "lmn"]);
+    }
+
+    #[test]
+    fn test_indentation_folds() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "a\n  b\n    c\n\n    d\n  e\n\nf\n\tg");
+        let mut display_map = DisplayMap::new(&buffer, 2, None);
+        assert_eq!(
+            display_map.indentation_block(&buffer, 0),
+            Some(Point::new(0, 1)..Point::new(5, 3))
+        );
+        assert_eq!(
+            display_map.indentation_block(&buffer, 1),
+            Some(Point::new(1, 3)..Point::new(4, 5))
+        );
+        assert_eq!(
+            display_map.indentation_block(&buffer, 3),
+            Some(Point::new(1, 3)..Point::new(4, 5))
+        );
+        assert_eq!(
+            display_map.indentation_block(&buffer, 5),
+            Some(Point::new(0, 1)..Point::new(5, 3))
+        );
+        assert_eq!(
+            display_map.indentation_block(&buffer, 7),
+            Some(Point::new(7, 1)..Point::new(8, 2))
+        );
+        assert_eq!(display_map.indentation_block(&buffer, 6), None);
+
+        display_map.fold_all(&buffer);
+        assert_eq!(
+            display_map.lines(&buffer, 0..10),
+            vec!["a\u{22ef}", "", "f\u{22ef}"]
+        );
+        display_map.unfold(&buffer, Point::new(0, 1)..Point::new(0, 1));
+        assert_eq!(
+            display_map.lines(&buffer, 0..10),
+            vec!["a", "  b\u{22ef}", "  e", "", "f\u{22ef}"]
+        );
+        display_map.unfold_all(&buffer);
+        assert_eq!(display_map.row_count(), 9);
+    }
+
+    #[test]
+    fn test_random_edits_and_folds() {
+        for seed in 0..100 {
+            let mut rng = StdRng::from_seed(&[seed]);
+            let mut buffer = Buffer::new(1);
+            let mut display_map = DisplayMap::new(&buffer, rng.gen_range(1, 5), None);
+            display_map.set_wrap_width(&buffer, Some(rng.gen_range(1, 10)));
+
+            for _ in 0..10 {
+                for _ in 0..rng.gen_range(1, 4) {
+                    let end = rng.gen_range(0, buffer.len() + 1);
+                    let start = rng.gen_range(0, end + 1);
+                    let text = random_text(&mut rng);
+                    if rng.gen_weighted_bool(5) {
+                        buffer.undo();
+                    } else {
+                        buffer.splice(start..end, text.as_str());
+                    }
+                }
+                display_map.sync(&buffer);
+
+                let end = rng.gen_range(0, buffer.len() + 1);
+                let start = rng.gen_range(0, end + 1);
+                let start = buffer.point_for_anchor(&buffer.anchor_before_offset(start).unwrap());
+                let end = buffer.point_for_anchor(&buffer.anchor_before_offset(end).unwrap());
+                if rng.gen_weighted_bool(3) {
+                    display_map.unfold(&buffer, start.unwrap()..end.unwrap());
+                } else {
+                    display_map.fold(&buffer, start.unwrap()..end.unwrap());
+                }
+
+                let mut expected = DisplayMap::new(
+                    &buffer,
+                    display_map.tab_width,
+                    display_map.wrap_width,
+                );
+                expected.folds = display_map.folds.clone();
+                expected.update_fold_ranges(&buffer);
+                expected.layout(&buffer);
+                assert_eq!(
+                    display_map.lines.iter().collect::<Vec<_>>(),
+                    expected.lines.iter().collect::<Vec<_>>()
+                );
+
+                let max_row = buffer.max_point().row;
+                for row in 0..max_row + 1 {
+                    for column in 0..buffer.len_for_row(row).unwrap() + 1 {
+                        let point = Point::new(row, column);
+                        let display_point = display_map.to_display_point(&buffer, point);
+                        assert_eq!(
+                            display_map.to_buffer_point(&buffer, display_point, Bias::Left),
+                            display_map.clip_point(point, Bias::Left)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn random_text<T: Rng>(rng: &mut T) -> String {
+        let len = rng.gen_range(0, 8);
+        (0..len)
+            .map(|_| match rng.gen_range(0, 10) {
+                0 => '\n',
+                1 => '\t',
+                2 | 3 => ' ',
+                _ => rng.gen_range(b'a', b'z') as char,
+            })
+            .collect()
+    }
+}
@@ -1,10 +1,11 @@
 use futures::{Async, Poll, Stream};
+use std::cmp;
 use std::path::PathBuf;
 use fuzzy_search::SearchResult;
 use fs;
 use std::rc::Weak;
 use std::cell::RefCell;
-use window::{View, WindowHandle};
+use window::{Command, View, WindowHandle};
 use notify_cell::{NotifyCell, NotifyCellObserver};
 use serde_json;
 
@@ -17,17 +18,28 @@ pub trait FileFinderViewDelegate {
 pub struct FileFinderView<T: FileFinderViewDelegate> {
     delegate: Weak<RefCell<T>>,
     query: String,
+    include_ignored: bool,
     selected_index: usize,
-    search_results: Vec<SearchResult>,
-    search_updates: Option<NotifyCellObserver<Vec<SearchResult>>>,
+    root_names: Vec<String>,
+    search_results: Vec<FileFinderSearchResult>,
+    root_search_results: Vec<Vec<SearchResult>>,
+    search_updates: Vec<(usize, NotifyCellObserver<Vec<SearchResult>>)>,
+    tree_updates: Vec<Box<Stream<Item = (), Error = ()>>>,
     window_handle: Option<WindowHandle>,
     updates: NotifyCell<()>,
 }
 
+struct FileFinderSearchResult {
+    root_index: usize,
+    relative_path: String,
+    display: SearchResult,
+}
+
 #[derive(Deserialize)]
 #[serde(tag = "type")]
 enum FileFinderAction {
     UpdateQuery { query: String },
+    ToggleIncludeIgnored,
     SelectPrevious,
     SelectNext,
     Confirm,
@@ -43,7 +55,11 @@ impl<T: FileFinderViewDelegate> View for FileFinderView<T> {
         json!({
             "selected_index": self.selected_index,
             "query": self.query.as_str(),
-            "results": self.search_results,
+            "include_ignored": self.include_ignored,
+            "results": self.search_results
+                .iter()
+                .map(|result| &result.display)
+                .collect::<Vec<_>>(),
         })
     }
 
@@ -51,15 +67,24 @@ impl<T: FileFinderViewDelegate> View for FileFinderView<T> {
         self.window_handle = Some(window_handle);
     }
 
-    fn dispatch_action(&mut self, action: serde_json::Value) {
+    fn dispatch_action(&mut self, action: serde_json::Value) -> bool {
         match serde_json::from_value(action) {
             Ok(FileFinderAction::UpdateQuery { query }) => self.update_query(query),
+            Ok(FileFinderAction::ToggleIncludeIgnored) => self.toggle_include_ignored(),
             Ok(FileFinderAction::SelectPrevious) => self.select_previous(),
             Ok(FileFinderAction::SelectNext) => self.select_next(),
             Ok(FileFinderAction::Confirm) => self.confirm(),
             Ok(FileFinderAction::Close) => self.close(),
-            _ => eprintln!("Unrecognized action"),
+            _ => return false,
         }
+        true
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new("Toggle Include Ignored", json!({"type": "ToggleIncludeIgnored"})),
+            Command::new("Close File Finder", json!({"type": "Close"})),
+        ]
     }
 }
 
@@ -68,27 +93,59 @@ impl<T: FileFinderViewDelegate> Stream for FileFinderView<T> {
     type Error = ();
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        let search_poll = self.search_updates.as_mut().map(|s| s.poll()).unwrap_or(Ok(Async::NotReady))?;
+        if self.tree_updates.is_empty() {
+            let delegate = self.delegate.upgrade().unwrap();
+            let delegate = delegate.borrow();
+            self.tree_updates = delegate.trees().iter().map(|tree| tree.updates()).collect();
+        }
+
+        // Re-run the current search when any of the trees change, so results don't go stale. Trees
+        // update constantly while they're being populated, so we only restart searches for queries
+        // that narrow the results rather than listing arbitrary files.
+        let mut trees_updated = false;
+        for tree_updates in self.tree_updates.iter_mut() {
+            if let Async::Ready(Some(())) = tree_updates.poll()? {
+                trees_updated = true;
+            }
+        }
+        if trees_updated && !self.query.is_empty() {
+            self.search();
+        }
+
+        let mut search_results_updated = false;
+        for &mut (root_index, ref mut search_updates) in self.search_updates.iter_mut() {
+            if let Async::Ready(Some(search_results)) = search_updates.poll()? {
+                self.root_search_results[root_index] = search_results;
+                search_results_updated = true;
+            }
+        }
+        if search_results_updated {
+            self.merge_search_results();
+        }
+
         let updates_poll = self.updates.poll()?;
-        match (search_poll, updates_poll) {
-            (Async::NotReady, Async::NotReady) => Ok(Async::NotReady),
-            (Async::Ready(Some(search_results)), _) => {
-                self.search_results = search_results;
-                Ok(Async::Ready(Some(())))
-            },
-            _ => Ok(Async::Ready(Some(())))
+        if search_results_updated || updates_poll.is_ready() {
+            Ok(Async::Ready(Some(())))
+        } else {
+            Ok(Async::NotReady)
         }
     }
 }
 
 impl<T: FileFinderViewDelegate> FileFinderView<T> {
+    const MAX_RESULTS: usize = 10;
+
     pub fn new(delegate: Weak<RefCell<T>>) -> Self {
         Self {
             delegate,
             query: String::new(),
+            include_ignored: false,
             selected_index: 0,
+            root_names: Vec::new(),
             search_results: Vec::new(),
-            search_updates: None,
+            root_search_results: Vec::new(),
+            search_updates: Vec::new(),
+            tree_updates: Vec::new(),
             updates: NotifyCell::new(()),
             window_handle: None,
         }
@@ -97,14 +154,72 @@ impl<T: FileFinderViewDelegate> FileFinderView<T> {
     fn update_query(&mut self, query: String) {
         if self.query != query {
             self.query = query;
-            let delegate = self.delegate.upgrade().unwrap();
-            let delegate = delegate.borrow();
-            if let Ok((search, search_updates)) = delegate.trees()[0].root().search(&self.query, 10) {
-                self.search_updates = Some(search_updates);
+            self.search();
+        }
+    }
+
+    fn toggle_include_ignored(&mut self) {
+        self.include_ignored = !self.include_ignored;
+        self.search();
+    }
+
+    fn search(&mut self) {
+        let delegate = self.delegate.upgrade().unwrap();
+        let delegate = delegate.borrow();
+        let trees = delegate.trees();
+
+        self.root_names = trees
+            .iter()
+            .map(|tree| {
+                let path = tree.path();
+                path.file_name()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        self.root_search_results = vec![Vec::new(); trees.len()];
+        self.search_updates.clear();
+
+        // Each root is searched by its own task, and the results are merged as they arrive.
+        for (root_index, tree) in trees.iter().enumerate() {
+            let query = &self.query;
+            if let Ok((search, search_updates)) = tree.root().search(query, Self::MAX_RESULTS, self.include_ignored) {
+                self.search_updates.push((root_index, search_updates));
                 self.window_handle.as_ref().unwrap().spawn(search.for_each(|_| Ok(())));
             }
-            self.updates.set(());
         }
+        self.updates.set(());
+    }
+
+    fn merge_search_results(&mut self) {
+        let show_root_names = self.root_names.len() > 1;
+        let mut search_results = Vec::new();
+        for (root_index, root_search_results) in self.root_search_results.iter().enumerate() {
+            for search_result in root_search_results {
+                let mut display = search_result.clone();
+                if show_root_names {
+                    let prefix = format!("{}/", self.root_names[root_index]);
+                    let prefix_len = prefix.encode_utf16().count() as u16;
+                    for match_index in display.match_indices.iter_mut() {
+                        *match_index += prefix_len;
+                    }
+                    display.string = prefix + &display.string;
+                }
+
+                search_results.push(FileFinderSearchResult {
+                    root_index,
+                    relative_path: search_result.string.clone(),
+                    display,
+                });
+            }
+        }
+
+        // The sort is stable, so results with equal scores remain ordered by root.
+        search_results.sort_by_key(|result| cmp::Reverse(result.display.score));
+        search_results.truncate(Self::MAX_RESULTS);
+        self.search_results = search_results;
+        self.selected_index = self.selected_index.min(self.search_results.len().saturating_sub(1));
     }
 
     fn select_previous(&mut self) {
@@ -124,8 +239,12 @@ impl<T: FileFinderViewDelegate> FileFinderView<T> {
     fn confirm(&mut self) {
         if let Some(search_result) = self.search_results.get(self.selected_index) {
             let delegate = self.delegate.upgrade().unwrap();
-            let mut delegate = delegate.borrow_mut();
-            delegate.did_confirm(PathBuf::from(search_result.string.clone()));
+            let path = {
+                let delegate = delegate.borrow();
+                let tree = &delegate.trees()[search_result.root_index];
+                tree.path().join(&search_result.relative_path)
+            };
+            delegate.borrow_mut().did_confirm(path);
         }
     }
 
@@ -135,3 +254,143 @@ impl<T: FileFinderViewDelegate> FileFinderView<T> {
         delegate.did_close();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, Future};
+    use std::rc::Rc;
+    use test_helpers::{TestDelegate, TestExecutor, TestTree};
+    use window::Window;
+
+    #[test]
+    fn test_search_after_tree_updates() {
+        let executor = TestExecutor::new();
+        let mut window = Window::new(Some(Box::new(executor.clone())), 100.0);
+        let tree = TestTree::new("/a", &["foo.txt"]);
+        let root = tree.root.clone();
+        let tree_updates = tree.updates.clone();
+        let delegate = Rc::new(RefCell::new(TestDelegate::new(vec![tree])));
+        let finder = Rc::new(RefCell::new(FileFinderView::new(Rc::downgrade(&delegate))));
+        let finder_handle = window.handle().add_shared_view(finder.clone());
+
+        let finder_id = finder_handle.view_id;
+        window.dispatch_action(finder_id, json!({"type": "UpdateQuery", "query": "bar"}));
+        executor.run();
+        poll(&finder);
+        assert_eq!(result_strings(&finder), Vec::<String>::new());
+
+        // Files added to a tree are found without the query changing.
+        root.insert("bar.txt", fs::Entry::file(false, false)).unwrap();
+        tree_updates.set(());
+        poll(&finder);
+        executor.run();
+        poll(&finder);
+        assert_eq!(result_strings(&finder), vec!["bar.txt"]);
+    }
+
+    #[test]
+    fn test_search_after_tree_updates_with_empty_query() {
+        let executor = TestExecutor::new();
+        let mut window = Window::new(Some(Box::new(executor.clone())), 100.0);
+        let tree = TestTree::new("/a", &["foo.txt"]);
+        let root = tree.root.clone();
+        let tree_updates = tree.updates.clone();
+        let delegate = Rc::new(RefCell::new(TestDelegate::new(vec![tree])));
+        let finder = Rc::new(RefCell::new(FileFinderView::new(Rc::downgrade(&delegate))));
+        let finder_handle = window.handle().add_shared_view(finder.clone());
+        let finder_id = finder_handle.view_id;
+
+        window.dispatch_action(finder_id, json!({"type": "UpdateQuery", "query": "f"}));
+        window.dispatch_action(finder_id, json!({"type": "UpdateQuery", "query": ""}));
+        executor.run();
+        poll(&finder);
+        assert_eq!(result_strings(&finder), vec!["foo.txt"]);
+
+        root.insert("bar.txt", fs::Entry::file(false, false)).unwrap();
+        tree_updates.set(());
+        poll(&finder);
+        assert_eq!(executor.take().len(), 0);
+        assert_eq!(result_strings(&finder), vec!["foo.txt"]);
+    }
+
+    #[test]
+    fn test_search_multiple_roots() {
+        let executor = TestExecutor::new();
+        let mut window = Window::new(Some(Box::new(executor.clone())), 100.0);
+        let delegate = Rc::new(RefCell::new(TestDelegate::new(vec![
+            TestTree::new("/x/one", &["alphabet.txt", "src/gamma.rs"]),
+            TestTree::new("/y/two", &["beta.rs", "delta.txt"]),
+        ])));
+        let finder = Rc::new(RefCell::new(FileFinderView::new(Rc::downgrade(&delegate))));
+        let finder_handle = window.handle().add_shared_view(finder.clone());
+        let finder_id = finder_handle.view_id;
+
+        // Results from every root are ranked together and prefixed with their root's name.
+        window.dispatch_action(finder_id, json!({"type": "UpdateQuery", "query": "bet"}));
+        executor.run();
+        poll(&finder);
+        assert_eq!(result_strings(&finder), vec!["two/beta.rs", "one/alphabet.txt"]);
+        assert_eq!(
+            finder.borrow().render()["results"][0]["match_indices"],
+            json!([4, 5, 6])
+        );
+
+        // Confirming a result resolves its path against the root it was found in.
+        window.dispatch_action(finder_id, json!({"type": "SelectNext"}));
+        window.dispatch_action(finder_id, json!({"type": "Confirm"}));
+        window.dispatch_action(finder_id, json!({"type": "SelectPrevious"}));
+        window.dispatch_action(finder_id, json!({"type": "Confirm"}));
+        assert_eq!(
+            delegate.borrow().confirmed_paths,
+            vec![
+                PathBuf::from("/x/one/alphabet.txt"),
+                PathBuf::from("/y/two/beta.rs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_single_root() {
+        let executor = TestExecutor::new();
+        let mut window = Window::new(Some(Box::new(executor.clone())), 100.0);
+        let delegate = Rc::new(RefCell::new(TestDelegate::new(vec![
+            TestTree::new("/x/one", &["alpha.txt", "src/beta.rs"]),
+        ])));
+        let finder = Rc::new(RefCell::new(FileFinderView::new(Rc::downgrade(&delegate))));
+        let finder_handle = window.handle().add_shared_view(finder.clone());
+        let finder_id = finder_handle.view_id;
+
+        // Root names are only shown when they're needed to tell results apart.
+        window.dispatch_action(finder_id, json!({"type": "UpdateQuery", "query": "beta"}));
+        executor.run();
+        poll(&finder);
+        assert_eq!(result_strings(&finder), vec!["src/beta.rs"]);
+        assert_eq!(
+            finder.borrow().render()["results"][0]["match_indices"],
+            json!([4, 5, 6, 7])
+        );
+
+        window.dispatch_action(finder_id, json!({"type": "Confirm"}));
+        assert_eq!(
+            delegate.borrow().confirmed_paths,
+            vec![PathBuf::from("/x/one/src/beta.rs")]
+        );
+    }
+
+    fn poll(finder: &Rc<RefCell<FileFinderView<TestDelegate>>>) {
+        future::poll_fn(|| Ok::<_, ()>(Async::Ready(finder.borrow_mut().poll())))
+            .wait()
+            .unwrap()
+            .unwrap();
+    }
+
+    fn result_strings(finder: &Rc<RefCell<FileFinderView<TestDelegate>>>) -> Vec<String> {
+        finder.borrow().render()["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["string"].as_str().unwrap().to_string())
+            .collect()
+    }
+}
@@ -0,0 +1,190 @@
+use futures::{Poll, Stream};
+use std::cell::RefCell;
+use std::rc::Weak;
+use buffer_view::BufferView;
+use notify_cell::NotifyCell;
+use project_search::SearchQuery;
+use serde_json;
+use window::{Command, View};
+
+pub trait FindBarViewDelegate {
+    fn did_close(&mut self);
+}
+
+pub struct FindBarView<T: FindBarViewDelegate> {
+    delegate: Weak<RefCell<T>>,
+    buffer_view: Weak<RefCell<BufferView>>,
+    query: SearchQuery,
+    replacement: String,
+    error: Option<String>,
+    updates: NotifyCell<()>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum FindBarAction {
+    UpdateQuery { query: String },
+    UpdateReplacement { replacement: String },
+    ToggleRegex,
+    ToggleCaseSensitive,
+    ToggleWholeWord,
+    SelectNext,
+    SelectPrevious,
+    SelectAll,
+    ReplaceNext,
+    ReplaceAll,
+    Close,
+}
+
+impl<T: FindBarViewDelegate> View for FindBarView<T> {
+    fn component_name(&self) -> &'static str {
+        "FindBar"
+    }
+
+    fn render(&self) -> serde_json::Value {
+        let match_count = self.buffer_view
+            .upgrade()
+            .and_then(|buffer_view| {
+                buffer_view
+                    .try_borrow()
+                    .ok()
+                    .map(|buffer_view| buffer_view.find_match_count())
+            })
+            .unwrap_or(0);
+
+        json!({
+            "query": self.query.pattern.as_str(),
+            "replacement": self.replacement.as_str(),
+            "is_regex": self.query.is_regex,
+            "case_sensitive": self.query.case_sensitive,
+            "whole_word": self.query.whole_word,
+            "match_count": match_count,
+            "error": self.error,
+        })
+    }
+
+    fn dispatch_action(&mut self, action: serde_json::Value) -> bool {
+        match serde_json::from_value(action) {
+            Ok(FindBarAction::UpdateQuery { query }) => self.update_query(query),
+            Ok(FindBarAction::UpdateReplacement { replacement }) => {
+                self.replacement = replacement;
+                self.updates.set(());
+            }
+            Ok(FindBarAction::ToggleRegex) => {
+                self.query.is_regex = !self.query.is_regex;
+                self.search();
+            }
+            Ok(FindBarAction::ToggleCaseSensitive) => {
+                self.query.case_sensitive = !self.query.case_sensitive;
+                self.search();
+            }
+            Ok(FindBarAction::ToggleWholeWord) => {
+                self.query.whole_word = !self.query.whole_word;
+                self.search();
+            }
+            Ok(FindBarAction::SelectNext) => self.with_buffer_view(BufferView::select_next_match),
+            Ok(FindBarAction::SelectPrevious) => {
+                self.with_buffer_view(BufferView::select_previous_match)
+            }
+            Ok(FindBarAction::SelectAll) => self.with_buffer_view(BufferView::select_all_matches),
+            Ok(FindBarAction::ReplaceNext) => {
+                let template = self.query.replacement_template(&self.replacement);
+                self.with_buffer_view(|buffer_view| buffer_view.replace_current_match(&template));
+            }
+            Ok(FindBarAction::ReplaceAll) => {
+                let template = self.query.replacement_template(&self.replacement);
+                self.with_buffer_view(|buffer_view| buffer_view.replace_all_matches(&template));
+            }
+            Ok(FindBarAction::Close) => self.close(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new("Find Next", json!({"type": "SelectNext"})),
+            Command::new("Find Previous", json!({"type": "SelectPrevious"})),
+            Command::new("Select All Matches", json!({"type": "SelectAll"})),
+            Command::new("Replace Next", json!({"type": "ReplaceNext"})),
+            Command::new("Replace All", json!({"type": "ReplaceAll"})),
+            Command::new("Toggle Regex", json!({"type": "ToggleRegex"})),
+            Command::new("Toggle Case Sensitive", json!({"type": "ToggleCaseSensitive"})),
+            Command::new("Toggle Whole Word", json!({"type": "ToggleWholeWord"})),
+            Command::new("Close Find Bar", json!({"type": "Close"})),
+        ]
+    }
+}
+
+impl<T: FindBarViewDelegate> Stream for FindBarView<T> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.updates.poll()
+    }
+}
+
+impl<T: FindBarViewDelegate> FindBarView<T> {
+    pub fn new(delegate: Weak<RefCell<T>>, buffer_view: Weak<RefCell<BufferView>>) -> Self {
+        Self {
+            delegate,
+            buffer_view,
+            query: SearchQuery::new(""),
+            replacement: String::new(),
+            error: None,
+            updates: NotifyCell::new(()),
+        }
+    }
+
+    fn update_query(&mut self, query: String) {
+        if self.query.pattern != query {
+            self.query.pattern = query;
+            self.search();
+        }
+    }
+
+    fn search(&mut self) {
+        let regex = if self.query.pattern.is_empty() {
+            self.error = None;
+            None
+        } else {
+            match self.query.to_regex() {
+                Ok(regex) => {
+                    self.error = None;
+                    Some(regex)
+                }
+                Err(error) => {
+                    self.error = Some(error.to_string());
+                    None
+                }
+            }
+        };
+
+        self.with_buffer_view(|buffer_view| buffer_view.set_find_regex(regex));
+    }
+
+    fn with_buffer_view<F: FnOnce(&mut BufferView)>(&mut self, f: F) {
+        if let Some(buffer_view) = self.buffer_view.upgrade() {
+            f(&mut buffer_view.borrow_mut());
+        }
+        self.updates.set(());
+    }
+
+    fn close(&mut self) {
+        self.with_buffer_view(|buffer_view| buffer_view.set_find_regex(None));
+        let delegate = self.delegate.upgrade().unwrap();
+        let mut delegate = delegate.borrow_mut();
+        delegate.did_close();
+    }
+}
+
+impl<T: FindBarViewDelegate> Drop for FindBarView<T> {
+    fn drop(&mut self) {
+        if let Some(buffer_view) = self.buffer_view.upgrade() {
+            if let Ok(mut buffer_view) = buffer_view.try_borrow_mut() {
+                buffer_view.set_find_regex(None);
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use notify_cell::{NotifyCell, NotifyCellObserver, WeakNotifyCell};
 use parking_lot::RwLock;
 use std::ffi::{OsString, OsStr};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use std::result;
 use std::sync::Arc;
 use std::iter::Iterator;
@@ -29,16 +29,19 @@ pub enum Entry {
 pub struct DirInner {
     entries: Arc<Entries>,
     is_symlink: bool,
+    is_ignored: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct FileInner {
     is_symlink: bool,
+    is_ignored: bool,
 }
 
 pub struct Search {
     search: FuzzySearch,
     max_results: usize,
+    include_ignored: bool,
     results: Vec<SearchResult>,
     stack: Vec<StackEntry>,
     entry_count_per_poll: usize,
@@ -48,6 +51,12 @@ pub struct Search {
 
 pub struct SearchHandle(Arc<()>);
 
+pub struct FilePaths {
+    include_ignored: bool,
+    stack: Vec<(Arc<Entries>, usize)>,
+    path: PathBuf,
+}
+
 struct StackEntry {
     entries: Arc<Entries>,
     entries_index: usize,
@@ -55,14 +64,18 @@ struct StackEntry {
 }
 
 impl Entry {
-    pub fn file(is_symlink: bool) -> Self {
-        Entry::File(Arc::new(FileInner { is_symlink }))
+    pub fn file(is_symlink: bool, is_ignored: bool) -> Self {
+        Entry::File(Arc::new(FileInner {
+            is_symlink,
+            is_ignored,
+        }))
     }
 
-    pub fn dir(is_symlink: bool) -> Self {
+    pub fn dir(is_symlink: bool, is_ignored: bool) -> Self {
         Entry::Dir(Arc::new(RwLock::new(DirInner {
             entries: Arc::new(Vec::new()),
             is_symlink,
+            is_ignored,
         })))
     }
 
@@ -94,14 +107,140 @@ impl Entry {
         }
     }
 
-    pub fn search(&self, query: &str, max_results: usize) -> Result<(Search, NotifyCellObserver<Vec<SearchResult>>)> {
+    pub fn remove<T: AsRef<OsStr>>(&self, name: T) -> Result<Entry> {
+        match *self {
+            Entry::Dir(ref inner) => {
+                let name = name.as_ref();
+
+                let mut inner = inner.write();
+                match find_entry(&inner.entries, name) {
+                    Ok(index) => Ok(Arc::make_mut(&mut inner.entries).remove(index).1),
+                    Err(_) => Err(()), // No entry exists with this name
+                }
+            }
+            Entry::File(_) => Err(()),
+        }
+    }
+
+    /// Moves the child named `old_name` into `new_parent` under `new_name`, replacing any entry
+    /// that already exists with that name as a rename on the underlying file system would.
+    pub fn rename<S, T>(&self, old_name: S, new_parent: &Entry, new_name: T) -> Result<()>
+    where
+        S: AsRef<OsStr>,
+        T: Into<OsString>,
+    {
+        let old_name = old_name.as_ref();
+        let new_name = new_name.into();
+        if !new_parent.is_dir() {
+            return Err(());
+        }
+
+        let entry = self.remove(old_name)?;
+        let _ = new_parent.remove(&new_name);
+        new_parent.insert(new_name, entry)
+    }
+
+    pub fn child<T: AsRef<OsStr>>(&self, name: T) -> Option<Entry> {
+        match *self {
+            Entry::Dir(ref inner) => {
+                let name = name.as_ref();
+                let inner = inner.read();
+                find_entry(&inner.entries, name)
+                    .ok()
+                    .map(|index| inner.entries[index].1.clone())
+            }
+            Entry::File(_) => None,
+        }
+    }
+
+    /// Returns the descendant at the given path relative to this entry.
+    pub fn entry_for_path<T: AsRef<Path>>(&self, path: T) -> Option<Entry> {
+        let mut entry = self.clone();
+        for component in path.as_ref().components() {
+            match component {
+                Component::Normal(name) => entry = entry.child(name)?,
+                Component::CurDir => {}
+                _ => return None,
+            }
+        }
+        Some(entry)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        match self {
+            &Entry::Dir(_) => true,
+            Entry::File(_) => false,
+        }
+    }
+
+    /// Returns whether the entry is excluded by the tree's ignore rules, such as `.gitignore`.
+    pub fn is_ignored(&self) -> bool {
+        match *self {
+            Entry::Dir(ref inner) => inner.read().is_ignored,
+            Entry::File(ref inner) => inner.is_ignored,
+        }
+    }
+
+    /// Returns an iterator over the paths of all files beneath this entry, relative to it.
+    pub fn file_paths(&self, include_ignored: bool) -> FilePaths {
+        let stack = match *self {
+            Entry::Dir(ref inner) => vec![(inner.read().entries.clone(), 0)],
+            Entry::File(_) => Vec::new(),
+        };
+        FilePaths {
+            include_ignored,
+            stack,
+            path: PathBuf::new(),
+        }
+    }
+
+    pub fn search(&self, query: &str, max_results: usize, include_ignored: bool) -> Result<(Search, NotifyCellObserver<Vec<SearchResult>>)> {
         match self {
-            &Entry::Dir(ref inner) => Ok(Search::new(inner, query, max_results)),
+            &Entry::Dir(ref inner) => Ok(Search::new(inner, query, max_results, include_ignored)),
             _ => Err(())
         }
     }
 }
 
+impl Iterator for FilePaths {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let child = match self.stack.last_mut() {
+                Some(last) => {
+                    let child = last.0.get(last.1).cloned();
+                    last.1 += 1;
+                    child
+                }
+                None => return None,
+            };
+
+            match child {
+                Some((name, entry)) => {
+                    if entry.is_ignored() && !self.include_ignored {
+                        continue;
+                    }
+
+                    match entry {
+                        Entry::Dir(inner) => {
+                            self.path.push(name);
+                            self.stack.push((inner.read().entries.clone(), 0));
+                        }
+                        Entry::File(_) => return Some(self.path.join(name)),
+                    }
+                }
+                None => {
+                    self.stack.pop();
+                    if !self.stack.is_empty() {
+                        self.path.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
 impl Stream for Search {
     type Item = ();
     type Error = ();
@@ -121,6 +260,11 @@ impl Stream for Search {
                 if entries_index < entries.len() {
                     let child = &entries[entries_index];
 
+                    if !self.include_ignored && child.1.is_ignored() {
+                        self.stack.last_mut().unwrap().entries_index += 1;
+                        continue;
+                    }
+
                     match child.1 {
                         Entry::Dir(ref inner) => {
                             self.process_entry(&child.0, false);
@@ -162,7 +306,7 @@ impl Stream for Search {
 impl Search {
     const DEFAULT_ENTRY_COUNT_PER_POLL: usize = 100000;
 
-    fn new(dir: &Arc<RwLock<DirInner>>, query: &str, max_results: usize) -> (Self, NotifyCellObserver<Vec<SearchResult>>) {
+    fn new(dir: &Arc<RwLock<DirInner>>, query: &str, max_results: usize, include_ignored: bool) -> (Self, NotifyCellObserver<Vec<SearchResult>>) {
         let (updates, updates_observer) = NotifyCell::weak(Vec::new());
         let mut search = FuzzySearch::new(query);
         search
@@ -172,6 +316,7 @@ impl Search {
         let search = Search {
             search,
             max_results,
+            include_ignored,
             updates,
             results: Vec::new(),
             stack: vec![StackEntry {
@@ -218,6 +363,10 @@ impl Search {
     }
 }
 
+fn find_entry(entries: &Entries, name: &OsStr) -> result::Result<usize, usize> {
+    entries.binary_search_by(|entry| entry.0.as_os_str().cmp(name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -239,14 +388,59 @@ mod tests {
 
     #[test]
     fn test_insert() {
-        let root = Entry::dir(false);
-        assert_eq!(root.insert("a", Entry::file(false)), Ok(()));
-        assert_eq!(root.insert("c", Entry::file(false)), Ok(()));
-        assert_eq!(root.insert("b", Entry::file(false)), Ok(()));
-        assert_eq!(root.insert("a", Entry::file(false)), Err(()));
+        let root = Entry::dir(false, false);
+        assert_eq!(root.insert("a", Entry::file(false, false)), Ok(()));
+        assert_eq!(root.insert("c", Entry::file(false, false)), Ok(()));
+        assert_eq!(root.insert("b", Entry::file(false, false)), Ok(()));
+        assert_eq!(root.insert("a", Entry::file(false, false)), Err(()));
         assert_eq!(root.entry_names(), vec!["a", "b", "c"]);
     }
 
+    #[test]
+    fn test_remove() {
+        let root = build_directory(&json!({
+            "a": null,
+            "b": {
+                "c": null
+            }
+        }));
+        assert!(root.remove("b").unwrap().is_dir());
+        assert!(root.remove("b").is_err());
+        assert!(root.remove("d").is_err());
+        assert_eq!(root.entry_names(), vec!["a"]);
+    }
+
+    #[test]
+    fn test_rename() {
+        let root = build_directory(&json!({
+            "a": null,
+            "b": {
+                "c": null
+            },
+            "d": {}
+        }));
+
+        assert_eq!(root.rename("a", &root, "e"), Ok(()));
+        assert_eq!(root.entry_names(), vec!["b", "d", "e"]);
+
+        let dir = root.entry_for_path("d").unwrap();
+        assert_eq!(root.rename("b", &dir, "f"), Ok(()));
+        assert_eq!(root.entry_names(), vec!["d", "e"]);
+        assert_eq!(dir.entry_names(), vec!["f"]);
+        assert_eq!(root.entry_for_path("d/f").unwrap().entry_names(), vec!["c"]);
+
+        // Renaming over an existing entry replaces it.
+        let file = root.entry_for_path("d/f/c").unwrap();
+        assert_eq!(dir.rename("f", &root, "e"), Ok(()));
+        assert_eq!(root.entry_names(), vec!["d", "e"]);
+        assert!(root.entry_for_path("e").unwrap().is_dir());
+
+        // Renaming into a file fails without losing the entry.
+        assert_eq!(root.rename("d", &file, "g"), Err(()));
+        assert_eq!(root.entry_names(), vec!["d", "e"]);
+        assert_eq!(root.rename("x", &root, "y"), Err(()));
+    }
+
     #[test]
     fn test_search_subword_start_bonus() {
         let root = build_directory(&json!({
@@ -264,23 +458,70 @@ mod tests {
             }
         }));
 
-        let (mut search, results) = root.search("cde", 10).unwrap();
+        let (mut search, results) = root.search("cde", 10, false).unwrap();
         assert_eq!(search.poll(), Ok(Async::Ready(Some(()))));
         assert_eq!(results.get().unwrap()[0].string, "cats/dogs/eagles");
 
-        let (mut search, results) = root.search("og", 10).unwrap();
+        let (mut search, results) = root.search("og", 10, false).unwrap();
         assert_eq!(search.poll(), Ok(Async::Ready(Some(()))));
         assert_eq!(results.get().unwrap()[0].string, "accident/ogre");
     }
 
+    #[test]
+    fn test_search_ignored_entries() {
+        let root = Entry::dir(false, false);
+        let ignored_dir = Entry::dir(false, true);
+        assert_eq!(ignored_dir.insert("ab", Entry::file(false, true)), Ok(()));
+        assert_eq!(root.insert("a", ignored_dir), Ok(()));
+        assert_eq!(root.insert("abc", Entry::file(false, false)), Ok(()));
+        assert_eq!(root.insert("abd", Entry::file(false, true)), Ok(()));
+
+        let (mut search, results) = root.search("ab", 10, false).unwrap();
+        assert_eq!(search.poll(), Ok(Async::Ready(Some(()))));
+        assert_eq!(result_strings(results.get().unwrap()), vec!["abc"]);
+
+        let (mut search, results) = root.search("ab", 10, true).unwrap();
+        assert_eq!(search.poll(), Ok(Async::Ready(Some(()))));
+        let mut strings = result_strings(results.get().unwrap());
+        strings.sort();
+        assert_eq!(strings, vec!["a/ab", "abc", "abd"]);
+    }
+
+    #[test]
+    fn test_file_paths() {
+        let root = build_directory(&json!({
+            "a": {
+                "b": null,
+                "c": {
+                    "d": null
+                },
+                "e": {}
+            },
+            "f": null
+        }));
+        assert!(root.entry_for_path("a").unwrap().insert("g", Entry::file(false, true)).is_ok());
+
+        let paths = root.file_paths(false).collect::<Vec<_>>();
+        assert_eq!(paths, vec![
+            PathBuf::from("a/b"),
+            PathBuf::from("a/c/d"),
+            PathBuf::from("f"),
+        ]);
+        assert_eq!(root.file_paths(true).count(), 4);
+    }
+
+    fn result_strings(results: Vec<SearchResult>) -> Vec<String> {
+        results.into_iter().map(|result| result.string).collect()
+    }
+
     fn build_directory(json: &serde_json::Value) -> Entry {
         let object = json.as_object().unwrap();
-        let result = Entry::dir(false);
+        let result = Entry::dir(false, false);
         for (key, value) in object {
             let child_entry = if value.is_object() {
                 build_directory(value)
             } else {
-                Entry::file(false)
+                Entry::file(false, false)
             };
             assert_eq!(result.insert(key, child_entry), Ok(()));
         }
@@ -0,0 +1,460 @@
+use std::ops::{Add, AddAssign, Range};
+use std::sync::Arc;
+use futures::{Async, Poll, Stream};
+use buffer::{Buffer, Snapshot, Version};
+use notify_cell::{NotifyCell, NotifyCellObserver, WeakNotifyCell};
+use tokenizer::{LineState, Token, Tokenizer};
+use tree::{self, SeekBias, Tree};
+
+/// Stores the highlighting tokens for every row of a buffer. Rows are tokenized by a `Tokenize`
+/// task that runs in the background against a snapshot of the buffer. Edits only invalidate the
+/// rows they touch, which keep their old tokens until the task catches up with the buffer.
+pub struct HighlightMap {
+    tokenizer: Option<Arc<Tokenizer>>,
+    lines: Tree<Line>,
+    version: Version,
+    tokenize_version: Option<Version>,
+    tokenize_updates: Option<NotifyCellObserver<(Version, Tree<Line>)>>,
+}
+
+/// Tokenizes the invalid rows in a snapshot of a buffer, a bounded number of rows each time it is
+/// polled, and publishes the updated rows to its observer. A row that ends in a different state
+/// than before invalidates the row after it. Dropping the observer cancels the task.
+pub struct Tokenize {
+    tokenizer: Arc<Tokenizer>,
+    snapshot: Snapshot,
+    lines: Tree<Line>,
+    row: u32,
+    state: Option<LineState>,
+    row_count_per_poll: usize,
+    done: bool,
+    updates: WeakNotifyCell<(Version, Tree<Line>)>,
+}
+
+/// The tokens on a row, along with the state the tokenizer was in at its end. Invalid rows need
+/// to be tokenized again, and only know the state they ended in if they are the last row of an
+/// edit, in which case it is the state the row after them was tokenized with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Line {
+    tokens: Arc<Vec<Token>>,
+    end_state: Option<LineState>,
+    is_valid: bool,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct LineSummary {
+    rows: u32,
+    invalid_rows: u32,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Row(u32);
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct InvalidRow(u32);
+
+impl HighlightMap {
+    pub fn new(buffer: &Buffer, tokenizer: Option<Arc<Tokenizer>>) -> Self {
+        let mut lines = Tree::new();
+        let row_count = buffer.max_point().row + 1;
+        lines.extend((0..row_count).map(|_| Line::invalid(Arc::new(Vec::new()), None)));
+        Self {
+            tokenizer,
+            lines,
+            version: buffer.current_version(),
+            tokenize_version: None,
+            tokenize_updates: None,
+        }
+    }
+
+    pub fn set_tokenizer(&mut self, buffer: &Buffer, tokenizer: Option<Arc<Tokenizer>>) {
+        *self = Self::new(buffer, tokenizer);
+    }
+
+    /// Invalidates the rows that were edited since the last sync, so that they can be tokenized
+    /// again. The new rows keep the tokens of the rows they replaced in the meantime.
+    pub fn sync(&mut self, buffer: &Buffer) {
+        let version = buffer.current_version();
+        if version == self.version {
+            return;
+        }
+
+        for edit in buffer.edits_since(self.version) {
+            let old_rows = edit.old_range.start.row..edit.old_range.end.row + 1;
+            let new_row_count = edit.new_range.end.row - edit.new_range.start.row + 1;
+            let mut old_lines = Vec::new();
+            let mut cursor = self.lines.cursor();
+            cursor.seek(&Row(old_rows.start), SeekBias::Right);
+            for _ in old_rows.clone() {
+                old_lines.push(cursor.item().unwrap().clone());
+                cursor.next();
+            }
+
+            let last_end_state = old_lines.last().unwrap().end_state;
+            let new_lines = (0..new_row_count as usize).map(|i| {
+                let tokens = match old_lines.get(i) {
+                    Some(line) => line.tokens.clone(),
+                    None => Arc::new(Vec::new()),
+                };
+                if i + 1 == new_row_count as usize {
+                    Line::invalid(tokens, last_end_state)
+                } else {
+                    Line::invalid(tokens, None)
+                }
+            });
+            self.lines = splice_lines(&self.lines, old_rows, new_lines);
+        }
+        self.version = version;
+    }
+
+    /// Returns a task that tokenizes the rows invalidated since the last task was started. If a
+    /// task is already tokenizing the buffer's current version, or there is nothing to tokenize,
+    /// `None` is returned instead. Starting a new task cancels the previous one.
+    pub fn tokenize(&mut self, buffer: &Buffer) -> Option<Tokenize> {
+        self.sync(buffer);
+        let tokenizer = self.tokenizer.clone()?;
+        if self.tokenize_version == Some(self.version)
+            || self.lines.len::<InvalidRow>() == InvalidRow(0)
+        {
+            return None;
+        }
+
+        let (updates, updates_observer) = NotifyCell::weak((self.version, self.lines.clone()));
+        self.tokenize_version = Some(self.version);
+        self.tokenize_updates = Some(updates_observer);
+        Some(Tokenize {
+            tokenizer,
+            snapshot: buffer.snapshot(),
+            lines: self.lines.clone(),
+            row: 0,
+            state: None,
+            row_count_per_poll: Tokenize::DEFAULT_ROW_COUNT_PER_POLL,
+            done: false,
+            updates,
+        })
+    }
+
+    /// Replaces the stored rows with the ones published by the current `Tokenize` task, if it has
+    /// published anything since the last poll. Edits made after the task started are reapplied on
+    /// the next sync. Must be called from within a task.
+    pub fn poll_tokenize(&mut self) -> bool {
+        if let Some(updates) = self.tokenize_updates.as_mut() {
+            if let Ok(Async::Ready(Some((version, lines)))) = updates.poll() {
+                self.version = version;
+                self.lines = lines;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the tokens on each of the given rows.
+    pub fn lines(&self, rows: Range<u32>) -> Vec<&[Token]> {
+        let mut cursor = self.lines.cursor();
+        cursor.seek(&Row(rows.start), SeekBias::Right);
+        let mut lines = Vec::new();
+        while let Some(line) = cursor.item() {
+            if cursor.start::<Row>().0 >= rows.end {
+                break;
+            }
+            lines.push(line.tokens.as_slice());
+            cursor.next();
+        }
+        lines
+    }
+}
+
+impl Tokenize {
+    const DEFAULT_ROW_COUNT_PER_POLL: usize = 200;
+
+    /// Returns the next row that needs to be tokenized, along with the state of the row before it.
+    fn next_row(&self) -> Option<(u32, LineState)> {
+        let row = match self.state {
+            Some(_) => self.row,
+            None => {
+                let mut cursor = self.lines.cursor();
+                cursor.seek(&Row(self.row), SeekBias::Right);
+                let invalid_rows_before = cursor.start::<InvalidRow>();
+                cursor.seek(&invalid_rows_before, SeekBias::Right);
+                cursor.item()?;
+                cursor.start::<Row>().0
+            }
+        };
+
+        let state = match self.state {
+            Some(state) => state,
+            None if row == 0 => 0,
+            None => {
+                let mut cursor = self.lines.cursor();
+                cursor.seek(&Row(row - 1), SeekBias::Right);
+                cursor.item().unwrap().end_state.unwrap()
+            }
+        };
+        Some((row, state))
+    }
+
+    fn line(&self, row: u32) -> &Line {
+        let mut cursor = self.lines.cursor();
+        cursor.seek(&Row(row), SeekBias::Right);
+        cursor.item().unwrap()
+    }
+}
+
+impl Stream for Tokenize {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.done {
+            return Ok(Async::Ready(None));
+        }
+
+        let row_count = self.snapshot.max_point().row + 1;
+        for _ in 0..self.row_count_per_poll {
+            if let Some((row, mut state)) = self.next_row() {
+                let text = self.snapshot.line(row).unwrap();
+                let tokens = self.tokenizer.tokenize_line(&text, &mut state);
+                let old_end_state = self.line(row).end_state;
+                let new_line = Line {
+                    tokens: Arc::new(tokens),
+                    end_state: Some(state),
+                    is_valid: true,
+                };
+                self.lines = splice_lines(&self.lines, row..row + 1, Some(new_line));
+
+                // The following row only needs to be tokenized again if this one ended in a
+                // different state than it used to.
+                self.row = row + 1;
+                self.state = if old_end_state != Some(state) && self.row < row_count {
+                    Some(state)
+                } else {
+                    None
+                };
+            } else {
+                self.done = true;
+                break;
+            }
+        }
+
+        let update = (self.snapshot.version(), self.lines.clone());
+        if self.updates.try_set(update).is_ok() {
+            Ok(Async::Ready(Some(())))
+        } else {
+            self.done = true;
+            Ok(Async::Ready(None))
+        }
+    }
+}
+
+impl Line {
+    fn invalid(tokens: Arc<Vec<Token>>, end_state: Option<LineState>) -> Self {
+        Line {
+            tokens,
+            end_state,
+            is_valid: false,
+        }
+    }
+}
+
+impl tree::Item for Line {
+    type Summary = LineSummary;
+
+    fn summarize(&self) -> Self::Summary {
+        LineSummary {
+            rows: 1,
+            invalid_rows: if self.is_valid { 0 } else { 1 },
+        }
+    }
+}
+
+impl<'a> AddAssign<&'a LineSummary> for LineSummary {
+    fn add_assign(&mut self, other: &Self) {
+        self.rows += other.rows;
+        self.invalid_rows += other.invalid_rows;
+    }
+}
+
+impl tree::Dimension for Row {
+    type Summary = LineSummary;
+
+    fn from_summary(summary: &Self::Summary) -> Self {
+        Row(summary.rows)
+    }
+}
+
+impl<'a> Add<&'a Self> for Row {
+    type Output = Self;
+
+    fn add(self, other: &Self) -> Self {
+        Row(self.0 + other.0)
+    }
+}
+
+impl tree::Dimension for InvalidRow {
+    type Summary = LineSummary;
+
+    fn from_summary(summary: &Self::Summary) -> Self {
+        InvalidRow(summary.invalid_rows)
+    }
+}
+
+impl<'a> Add<&'a Self> for InvalidRow {
+    type Output = Self;
+
+    fn add(self, other: &Self) -> Self {
+        InvalidRow(self.0 + other.0)
+    }
+}
+
+fn splice_lines<I: IntoIterator<Item = Line>>(
+    lines: &Tree<Line>,
+    rows: Range<u32>,
+    new_lines: I,
+) -> Tree<Line> {
+    let mut cursor = lines.cursor();
+    let mut result = cursor.build_prefix(&Row(rows.start), SeekBias::Right);
+    result.extend(new_lines);
+    cursor.seek(&Row(rows.end), SeekBias::Right);
+    result.push_tree(cursor.build_suffix());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate rand;
+
+    use super::*;
+    use self::rand::{Rng, SeedableRng, StdRng};
+    use futures::{future, Future};
+    use tokenizer::RUST;
+
+    #[test]
+    fn test_tokenize() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "fn a() {}\nlet b = 1;\nlet c = 2;\nlet d = 3;");
+        let mut highlight_map = HighlightMap::new(&buffer, Some(RUST.clone()));
+        assert_eq!(run_tokenize(&mut highlight_map, &buffer), Some(4));
+        assert_eq!(
+            scopes(&highlight_map, 0..2),
+            vec![
+                vec!["keyword", "entity.name.function"],
+                vec!["keyword", "constant.numeric"],
+            ]
+        );
+        assert!(highlight_map.tokenize(&buffer).is_none());
+
+        // Editing a row without changing the state it ends in only retokenizes that row.
+        buffer.splice(14..15, "bb");
+        assert_eq!(run_tokenize(&mut highlight_map, &buffer), Some(1));
+
+        // Opening a comment retokenizes every row it affects, and closing it again retokenizes
+        // them back.
+        buffer.splice(0..0, "/*");
+        assert_eq!(run_tokenize(&mut highlight_map, &buffer), Some(4));
+        assert_eq!(
+            scopes(&highlight_map, 0..4),
+            vec![vec!["comment"], vec!["comment"], vec!["comment"], vec!["comment"]]
+        );
+        buffer.splice(16..16, "*/");
+        assert_eq!(run_tokenize(&mut highlight_map, &buffer), Some(3));
+        assert_eq!(
+            scopes(&highlight_map, 1..4),
+            vec![
+                vec!["comment", "constant.numeric"],
+                vec!["keyword", "constant.numeric"],
+                vec!["keyword", "constant.numeric"],
+            ]
+        );
+
+        // Rows inserted by an edit are tokenized along with the edited row.
+        buffer.splice(0..0, "let e = 4;\nlet f = 5;\n");
+        assert_eq!(run_tokenize(&mut highlight_map, &buffer), Some(3));
+
+        // Without a tokenizer, nothing is tokenized.
+        highlight_map.set_tokenizer(&buffer, None);
+        assert!(highlight_map.tokenize(&buffer).is_none());
+    }
+
+    #[test]
+    fn test_random_edits() {
+        for seed in 0..100 {
+            let mut rng = StdRng::from_seed(&[seed]);
+            let mut buffer = Buffer::new(1);
+            let mut highlight_map = HighlightMap::new(&buffer, Some(RUST.clone()));
+
+            for _ in 0..10 {
+                for _ in 0..rng.gen_range(1, 4) {
+                    let end = rng.gen_range(0, buffer.len() + 1);
+                    let start = rng.gen_range(0, end + 1);
+                    let text = random_text(&mut rng);
+                    if rng.gen_weighted_bool(5) {
+                        buffer.undo();
+                    } else {
+                        buffer.splice(start..end, text.as_str());
+                    }
+                }
+
+                // Edits made while a task is running are picked up by the next one.
+                if let Some(task) = highlight_map.tokenize(&buffer) {
+                    if rng.gen_weighted_bool(2) {
+                        let end = rng.gen_range(0, buffer.len() + 1);
+                        buffer.splice(end..end, random_text(&mut rng).as_str());
+                    }
+                    task.for_each(|_| Ok(())).wait().unwrap();
+                    poll_tokenize(&mut highlight_map);
+                }
+            }
+
+            run_tokenize(&mut highlight_map, &buffer);
+            let mut expected = HighlightMap::new(&buffer, Some(RUST.clone()));
+            run_tokenize(&mut expected, &buffer);
+            assert_eq!(
+                highlight_map.lines.iter().collect::<Vec<_>>(),
+                expected.lines.iter().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    /// Runs a task to tokenize the invalid rows in the map, one row per poll, and returns how
+    /// many rows it tokenized.
+    fn run_tokenize(highlight_map: &mut HighlightMap, buffer: &Buffer) -> Option<usize> {
+        let mut task = highlight_map.tokenize(buffer)?;
+        task.row_count_per_poll = 1;
+        let mut row_count = 0;
+        while !task.done {
+            task.poll().unwrap();
+            row_count += 1;
+        }
+        assert!(poll_tokenize(highlight_map));
+        Some(row_count - 1)
+    }
+
+    fn poll_tokenize(highlight_map: &mut HighlightMap) -> bool {
+        future::poll_fn(|| Ok::<_, ()>(Async::Ready(highlight_map.poll_tokenize())))
+            .wait()
+            .unwrap()
+    }
+
+    fn scopes(highlight_map: &HighlightMap, rows: Range<u32>) -> Vec<Vec<&'static str>> {
+        highlight_map
+            .lines(rows)
+            .iter()
+            .map(|tokens| tokens.iter().map(|token| token.scope).collect())
+            .collect()
+    }
+
+    fn random_text<T: Rng>(rng: &mut T) -> String {
+        let len = rng.gen_range(0, 8);
+        (0..len)
+            .map(|_| match rng.gen_range(0, 12) {
+                0 => "\n",
+                1 => "/*",
+                2 => "*/",
+                3 => "\"",
+                4 => "fn",
+                5 => "1",
+                _ => " ",
+            })
+            .collect()
+    }
+}
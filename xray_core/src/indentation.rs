@@ -0,0 +1,125 @@
+use buffer::{Buffer, IndentSettings};
+
+const SPACE: u16 = b' ' as u16;
+const TAB: u16 = b'\t' as u16;
+
+/// Returns the number of spaces and tabs at the start of the given line.
+pub fn leading_whitespace_len(line: &[u16]) -> usize {
+    line.iter()
+        .take_while(|&&code_unit| code_unit == SPACE || code_unit == TAB)
+        .count()
+}
+
+pub fn is_blank(line: &[u16]) -> bool {
+    leading_whitespace_len(line) == line.len()
+}
+
+/// Returns the number of columns spanned by the leading whitespace of the given line, with each
+/// tab advancing to the next tab stop.
+pub fn indentation(line: &[u16], tab_length: u32) -> u32 {
+    line[..leading_whitespace_len(line)]
+        .iter()
+        .fold(0, |columns, &code_unit| {
+            if code_unit == TAB {
+                columns + tab_length - columns % tab_length
+            } else {
+                columns + 1
+            }
+        })
+}
+
+/// Returns whitespace spanning the given number of columns, which consists of tabs followed by
+/// as many spaces as needed unless soft tabs are enabled.
+pub fn whitespace(columns: u32, settings: &IndentSettings) -> Vec<u16> {
+    let mut whitespace = if settings.soft_tabs {
+        Vec::new()
+    } else {
+        vec![TAB; (columns / settings.tab_length) as usize]
+    };
+    while indentation(&whitespace, settings.tab_length) < columns {
+        whitespace.push(SPACE);
+    }
+    whitespace
+}
+
+/// Returns the indentation suggested for a line that follows the given one. The previous line's
+/// indentation is kept, increased by a level if that line ends with an increase indent token and
+/// decreased by a level if the line itself starts with a decrease indent token.
+pub fn suggested_indentation(
+    previous_line: &[u16],
+    line: &[u16],
+    settings: &IndentSettings,
+) -> u32 {
+    let mut indentation = indentation(previous_line, settings.tab_length);
+    let previous_line = trim_end(previous_line);
+    if settings
+        .increase_indent_tokens
+        .iter()
+        .any(|token| previous_line.ends_with(&encode(token)))
+    {
+        indentation += settings.tab_length;
+    }
+
+    let line = &line[leading_whitespace_len(line)..];
+    if settings
+        .decrease_indent_tokens
+        .iter()
+        .any(|token| line.starts_with(&encode(token)))
+    {
+        indentation = indentation.saturating_sub(settings.tab_length);
+    }
+    indentation
+}
+
+/// Returns the indentation suggested for the given row based on the closest non-blank row above
+/// it.
+pub fn suggested_indentation_for_row(buffer: &Buffer, row: u32) -> u32 {
+    let previous_line = (0..row)
+        .rev()
+        .map(|row| buffer.line(row).unwrap())
+        .find(|line| !is_blank(line))
+        .unwrap_or_default();
+    let line = buffer.line(row).unwrap();
+    suggested_indentation(&previous_line, &line, buffer.indent_settings())
+}
+
+fn trim_end(line: &[u16]) -> &[u16] {
+    let len = line.len()
+        - line.iter()
+            .rev()
+            .take_while(|&&code_unit| code_unit == SPACE || code_unit == TAB)
+            .count();
+    &line[..len]
+}
+
+fn encode(token: &str) -> Vec<u16> {
+    token.encode_utf16().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace() {
+        let mut settings = IndentSettings::default();
+        assert_eq!(indentation(&encode("  \t x"), 4), 5);
+        assert_eq!(indentation(&encode("\t\t"), 2), 4);
+        assert_eq!(whitespace(6, &settings), encode("      "));
+        settings.soft_tabs = false;
+        assert_eq!(whitespace(6, &settings), encode("\t  "));
+        assert_eq!(whitespace(8, &settings), encode("\t\t"));
+    }
+
+    #[test]
+    fn test_suggested_indentation() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "fn a() {\n  b(\nc,\n\n      d\n)\n}");
+        assert_eq!(suggested_indentation_for_row(&buffer, 0), 0);
+        assert_eq!(suggested_indentation_for_row(&buffer, 1), 4);
+        assert_eq!(suggested_indentation_for_row(&buffer, 2), 6);
+        assert_eq!(suggested_indentation_for_row(&buffer, 4), 0);
+        assert_eq!(suggested_indentation_for_row(&buffer, 5), 2);
+        assert_eq!(suggested_indentation_for_row(&buffer, 6), 0);
+    }
+}
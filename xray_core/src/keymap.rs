@@ -0,0 +1,579 @@
+use serde_json;
+use std::fmt;
+
+/// A single key press along with the modifiers held during it, written as the modifiers followed
+/// by the key and separated by dashes, such as `cmd-shift-z` or `ctrl--`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Keystroke {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub cmd: bool,
+    pub key: String,
+}
+
+/// Maps sequences of keystrokes to actions. Each binding is scoped to a context selector, which
+/// names a view's component and optionally some flags that must be present in the focus context
+/// the client reports for that view, such as `FindBar.replacement`.
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+/// Describes one of the views a keystroke is resolved against.
+pub struct Context<'a> {
+    pub component_name: &'a str,
+    pub flags: &'a [String],
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Match {
+    None,
+    /// The keystrokes are a prefix of a longer binding, so more keystrokes are needed.
+    Pending,
+    /// The action to dispatch to the view at the given index of the contexts.
+    Action {
+        context_index: usize,
+        action: serde_json::Value,
+    },
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum Error {
+    InvalidKeystroke(String),
+    InvalidSelector(String),
+    InvalidBindings,
+}
+
+struct Binding {
+    keystrokes: Vec<Keystroke>,
+    selector: Selector,
+    /// Bindings without an action disable any bindings they take precedence over.
+    action: Option<serde_json::Value>,
+}
+
+struct Selector {
+    component_name: String,
+    flags: Vec<String>,
+}
+
+impl Keystroke {
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let error = || Error::InvalidKeystroke(source.to_string());
+        let mut components = source.split('-').collect::<Vec<_>>();
+        let mut key = components.pop().ok_or_else(error)?;
+        if key.is_empty() {
+            // A trailing dash is only valid when it is the key itself, as in `ctrl--`.
+            match components.pop() {
+                Some("") => key = "-",
+                _ => return Err(error()),
+            }
+        }
+
+        let mut keystroke = Keystroke {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            cmd: false,
+            key: key.to_string(),
+        };
+        for modifier in components {
+            match modifier {
+                "ctrl" => keystroke.ctrl = true,
+                "alt" => keystroke.alt = true,
+                "shift" => keystroke.shift = true,
+                "cmd" => keystroke.cmd = true,
+                _ => return Err(error()),
+            }
+        }
+        Ok(keystroke)
+    }
+
+    pub fn parse_sequence(source: &str) -> Result<Vec<Self>, Error> {
+        let keystrokes = source
+            .split_whitespace()
+            .map(Self::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keystrokes.is_empty() {
+            Err(Error::InvalidKeystroke(source.to_string()))
+        } else {
+            Ok(keystrokes)
+        }
+    }
+}
+
+impl fmt::Display for Keystroke {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl-")?;
+        }
+        if self.alt {
+            write!(f, "alt-")?;
+        }
+        if self.shift {
+            write!(f, "shift-")?;
+        }
+        if self.cmd {
+            write!(f, "cmd-")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl Keymap {
+    /// Returns a keymap containing the built-in bindings.
+    pub fn new() -> Self {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        let default_bindings = serde_json::from_str(DEFAULT_BINDINGS).unwrap();
+        keymap
+            .add_bindings(&default_bindings)
+            .expect("Default bindings should be valid");
+        keymap
+    }
+
+    /// Adds bindings from a JSON object mapping context selectors to objects that in turn map
+    /// keystroke sequences to actions. Actions are either a serialized action, the name of an
+    /// action without any fields, or null to unbind the keystrokes. Bindings added later take
+    /// precedence over existing ones with an equally specific selector.
+    pub fn add_bindings(&mut self, bindings: &serde_json::Value) -> Result<(), Error> {
+        let mut new_bindings = Vec::new();
+        for (selector, bindings) in bindings.as_object().ok_or(Error::InvalidBindings)? {
+            for (keystrokes, action) in bindings.as_object().ok_or(Error::InvalidBindings)? {
+                let action = match *action {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(ref name) => Some(json!({ "type": name })),
+                    serde_json::Value::Object(_) => Some(action.clone()),
+                    _ => return Err(Error::InvalidBindings),
+                };
+                new_bindings.push(Binding {
+                    keystrokes: Keystroke::parse_sequence(keystrokes)?,
+                    selector: Selector::parse(selector)?,
+                    action,
+                });
+            }
+        }
+        self.bindings.extend(new_bindings);
+        Ok(())
+    }
+
+    /// Resolves a sequence of keystrokes against the given contexts, which are ordered from the
+    /// focused view outward. Bindings for inner contexts take precedence over those for outer
+    /// ones, and a complete binding takes precedence over a longer one it is a prefix of.
+    pub fn match_keystrokes(&self, keystrokes: &[Keystroke], contexts: &[Context]) -> Match {
+        for (context_index, context) in contexts.iter().enumerate() {
+            let mut bindings = self.bindings
+                .iter()
+                .enumerate()
+                .filter(|&(_, binding)| binding.selector.matches(context))
+                .collect::<Vec<_>>();
+            bindings.sort_by_key(|&(index, binding)| (binding.selector.specificity(), index));
+
+            // Only the binding with the highest precedence for each sequence of keystrokes counts.
+            let mut seen_keystrokes: Vec<&[Keystroke]> = Vec::new();
+            let mut is_pending = false;
+            for (_, binding) in bindings.into_iter().rev() {
+                if seen_keystrokes.contains(&binding.keystrokes.as_slice()) {
+                    continue;
+                }
+                seen_keystrokes.push(&binding.keystrokes);
+
+                if let Some(ref action) = binding.action {
+                    if binding.keystrokes.as_slice() == keystrokes {
+                        return Match::Action {
+                            context_index,
+                            action: action.clone(),
+                        };
+                    } else if binding.keystrokes.starts_with(keystrokes) {
+                        is_pending = true;
+                    }
+                }
+            }
+
+            if is_pending {
+                return Match::Pending;
+            }
+        }
+        Match::None
+    }
+
+    /// Returns the keystrokes that dispatch the given action to the view at the given index of
+    /// the contexts, or to a view inside it from which the action would bubble up. Bindings that
+    /// are disabled or shadowed by other bindings are skipped, and among the remaining ones the
+    /// most recently added is preferred.
+    pub fn keystrokes_for_action(
+        &self,
+        action: &serde_json::Value,
+        context_index: usize,
+        contexts: &[Context],
+    ) -> Option<Vec<Keystroke>> {
+        self.bindings
+            .iter()
+            .rev()
+            .filter(|binding| binding.action.as_ref() == Some(action))
+            .find(|binding| match self.match_keystrokes(&binding.keystrokes, contexts) {
+                Match::Action {
+                    context_index: matched_context_index,
+                    action: ref matched_action,
+                } => matched_context_index <= context_index && matched_action == action,
+                _ => false,
+            })
+            .map(|binding| binding.keystrokes.clone())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Selector {
+    fn parse(source: &str) -> Result<Self, Error> {
+        let mut components = source.split('.');
+        let component_name = components.next().unwrap().to_string();
+        let flags = components.map(|flag| flag.to_string()).collect::<Vec<_>>();
+        if component_name.is_empty() || flags.iter().any(|flag| flag.is_empty()) {
+            Err(Error::InvalidSelector(source.to_string()))
+        } else {
+            Ok(Selector {
+                component_name,
+                flags,
+            })
+        }
+    }
+
+    fn matches(&self, context: &Context) -> bool {
+        self.component_name == context.component_name
+            && self.flags.iter().all(|flag| context.flags.contains(flag))
+    }
+
+    fn specificity(&self) -> usize {
+        self.flags.len()
+    }
+}
+
+/// The built-in bindings, in the same format as the bindings users can add.
+const DEFAULT_BINDINGS: &str = r#"{
+    "Workspace": {
+        "cmd-shift-p": "ToggleCommandPalette",
+        "cmd-t": "ToggleFileFinder",
+        "cmd-f": "ToggleFindBar",
+        "cmd-shift-f": "ToggleProjectSearch",
+        "cmd-s": "Save",
+        "cmd-\\": { "type": "Split", "axis": "Horizontal" },
+        "cmd-shift-\\": { "type": "Split", "axis": "Vertical" },
+        "cmd-w": "CloseActiveItem",
+        "cmd-shift-w": "ClosePane",
+        "ctrl-tab": "ActivateNextPane",
+        "ctrl-shift-tab": "ActivatePreviousPane"
+    },
+    "BufferView": {
+        "up": "MoveUp",
+        "shift-up": "SelectUp",
+        "cmd-up": "MoveToBeginningOfBuffer",
+        "cmd-shift-up": "SelectToBeginningOfBuffer",
+        "alt-up": "MoveToPreviousParagraph",
+        "alt-shift-up": "SelectToPreviousParagraph",
+        "ctrl-alt-up": "ExpandSelection",
+        "ctrl-shift-up": "AddSelectionAbove",
+        "down": "MoveDown",
+        "shift-down": "SelectDown",
+        "cmd-down": "MoveToEndOfBuffer",
+        "cmd-shift-down": "SelectToEndOfBuffer",
+        "alt-down": "MoveToNextParagraph",
+        "alt-shift-down": "SelectToNextParagraph",
+        "ctrl-alt-down": "ShrinkSelection",
+        "ctrl-shift-down": "AddSelectionBelow",
+        "left": "MoveLeft",
+        "shift-left": "SelectLeft",
+        "cmd-left": "MoveToBeginningOfLine",
+        "cmd-shift-left": "SelectToBeginningOfLine",
+        "alt-left": "MoveToPreviousWordBoundary",
+        "alt-shift-left": "SelectToPreviousWordBoundary",
+        "ctrl-alt-left": "MoveToPreviousSubwordBoundary",
+        "ctrl-alt-shift-left": "SelectToPreviousSubwordBoundary",
+        "right": "MoveRight",
+        "shift-right": "SelectRight",
+        "cmd-right": "MoveToEndOfLine",
+        "cmd-shift-right": "SelectToEndOfLine",
+        "alt-right": "MoveToNextWordBoundary",
+        "alt-shift-right": "SelectToNextWordBoundary",
+        "ctrl-alt-right": "MoveToNextSubwordBoundary",
+        "ctrl-alt-shift-right": "SelectToNextSubwordBoundary",
+        "pageup": "PageUp",
+        "shift-pageup": "SelectPageUp",
+        "pagedown": "PageDown",
+        "shift-pagedown": "SelectPageDown",
+        "home": "MoveToBeginningOfLine",
+        "shift-home": "SelectToBeginningOfLine",
+        "end": "MoveToEndOfLine",
+        "shift-end": "SelectToEndOfLine",
+        "enter": { "type": "Edit", "text": "\n" },
+        "backspace": "Backspace",
+        "alt-backspace": "DeleteToPreviousWordBoundary",
+        "delete": "Delete",
+        "alt-delete": "DeleteToNextWordBoundary",
+        "ctrl-k": "DeleteToEndOfLine",
+        "ctrl-shift-k": "DeleteLine",
+        "escape": "ClearSelections",
+        "cmd-a": "SelectAll",
+        "cmd-l": "SelectLine",
+        "cmd-c": "Copy",
+        "cmd-x": "Cut",
+        "cmd-v": "Paste",
+        "cmd-z": "Undo",
+        "cmd-shift-z": "Redo",
+        "cmd-[": "Outdent",
+        "cmd-]": "Indent",
+        "alt-cmd-[": "Fold",
+        "alt-shift-cmd-[": "FoldAll",
+        "alt-cmd-]": "Unfold",
+        "alt-shift-cmd-]": "UnfoldAll",
+        "alt-z": "ToggleSoftWrap"
+    },
+    "FileFinder": {
+        "up": "SelectPrevious",
+        "down": "SelectNext",
+        "enter": "Confirm",
+        "escape": "Close",
+        "cmd-i": "ToggleIncludeIgnored"
+    },
+    "CommandPalette": {
+        "up": "SelectPrevious",
+        "down": "SelectNext",
+        "enter": "Confirm",
+        "escape": "Close"
+    },
+    "ProjectSearch": {
+        "up": "SelectPrevious",
+        "down": "SelectNext",
+        "enter": "Confirm",
+        "escape": "Close"
+    },
+    "ProjectSearch.replacement": {
+        "enter": "ReplaceAll"
+    },
+    "FindBar": {
+        "enter": "SelectNext",
+        "shift-enter": "SelectPrevious",
+        "alt-enter": "SelectAll",
+        "escape": "Close"
+    },
+    "FindBar.replacement": {
+        "enter": "ReplaceNext",
+        "shift-enter": null,
+        "alt-enter": "ReplaceAll"
+    }
+}"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystrokes() {
+        let keystroke = Keystroke::parse("cmd-shift-z").unwrap();
+        assert!(keystroke.cmd && keystroke.shift && !keystroke.ctrl && !keystroke.alt);
+        assert_eq!(keystroke.key, "z");
+        assert_eq!(Keystroke::parse("shift-cmd-z").unwrap(), keystroke);
+        assert_eq!(keystroke.to_string(), "shift-cmd-z");
+
+        assert_eq!(Keystroke::parse("ctrl--").unwrap().to_string(), "ctrl--");
+        assert_eq!(Keystroke::parse("-").unwrap().key, "-");
+        assert_eq!(
+            Keystroke::parse("cmd-"),
+            Err(Error::InvalidKeystroke("cmd-".to_string()))
+        );
+        assert_eq!(
+            Keystroke::parse("hyper-a"),
+            Err(Error::InvalidKeystroke("hyper-a".to_string()))
+        );
+        assert_eq!(Keystroke::parse_sequence("ctrl-k  ctrl-u").unwrap().len(), 2);
+        assert!(Keystroke::parse_sequence(" ").is_err());
+    }
+
+    #[test]
+    fn test_matching() {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        keymap
+            .add_bindings(&json!({
+                "Workspace": {
+                    "cmd-t": "ToggleFileFinder",
+                    "ctrl-k ctrl-u": "Upcase",
+                    "ctrl-k": "Kill"
+                },
+                "Editor": {
+                    "enter": { "type": "Edit", "text": "\n" },
+                    "ctrl-k": "DeleteLine",
+                    "ctrl-x ctrl-s": "Save"
+                },
+                "Editor.mini": {
+                    "enter": "Confirm"
+                }
+            }))
+            .unwrap();
+
+        let flags = vec!["mini".to_string()];
+        let editor = [context("Editor", &[]), context("Workspace", &[])];
+        let mini_editor = [context("Editor", &flags), context("Workspace", &[])];
+        let workspace = [context("Workspace", &[])];
+
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("cmd-t"), &editor),
+            action(1, json!({"type": "ToggleFileFinder"}))
+        );
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("enter"), &editor),
+            action(0, json!({"type": "Edit", "text": "\n"}))
+        );
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("enter"), &mini_editor),
+            action(0, json!({"type": "Confirm"}))
+        );
+        assert_eq!(keymap.match_keystrokes(&keystrokes("enter"), &workspace), Match::None);
+
+        // Inner contexts take precedence over outer ones, and complete bindings over longer ones.
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("ctrl-k"), &editor),
+            action(0, json!({"type": "DeleteLine"}))
+        );
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("ctrl-k"), &workspace),
+            action(0, json!({"type": "Kill"}))
+        );
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("ctrl-k ctrl-u"), &editor),
+            action(1, json!({"type": "Upcase"}))
+        );
+        assert_eq!(keymap.match_keystrokes(&keystrokes("ctrl-x"), &editor), Match::Pending);
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("ctrl-x ctrl-s"), &editor),
+            action(0, json!({"type": "Save"}))
+        );
+        assert_eq!(keymap.match_keystrokes(&keystrokes("ctrl-x ctrl-c"), &editor), Match::None);
+
+        // Later bindings override earlier ones, and null unbinds keystrokes.
+        keymap
+            .add_bindings(&json!({
+                "Workspace": { "cmd-t": "ToggleFuzzyFinder" },
+                "Editor": { "ctrl-k": null, "ctrl-x ctrl-s": null }
+            }))
+            .unwrap();
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("cmd-t"), &editor),
+            action(1, json!({"type": "ToggleFuzzyFinder"}))
+        );
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("ctrl-k"), &editor),
+            action(1, json!({"type": "Kill"}))
+        );
+        assert_eq!(keymap.match_keystrokes(&keystrokes("ctrl-x"), &editor), Match::None);
+
+        assert_eq!(
+            keymap.add_bindings(&json!({"Editor.": {"a": "A"}})),
+            Err(Error::InvalidSelector("Editor.".to_string()))
+        );
+        assert_eq!(
+            keymap.add_bindings(&json!({"Editor": {"a": 1}})),
+            Err(Error::InvalidBindings)
+        );
+    }
+
+    #[test]
+    fn test_default_bindings() {
+        let keymap = Keymap::new();
+        let flags = vec!["replacement".to_string()];
+        let find_bar = [context("FindBar", &flags), context("Workspace", &[])];
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("alt-enter"), &find_bar),
+            action(0, json!({"type": "ReplaceAll"}))
+        );
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("escape"), &find_bar),
+            action(0, json!({"type": "Close"}))
+        );
+        assert_eq!(keymap.match_keystrokes(&keystrokes("shift-enter"), &find_bar), Match::None);
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("cmd-f"), &find_bar),
+            action(1, json!({"type": "ToggleFindBar"}))
+        );
+
+        let buffer_view = [context("BufferView", &[]), context("Workspace", &[])];
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("enter"), &buffer_view),
+            action(0, json!({"type": "Edit", "text": "\n"}))
+        );
+        assert_eq!(
+            keymap.match_keystrokes(&keystrokes("cmd-shift-\\"), &buffer_view),
+            action(1, json!({"type": "Split", "axis": "Vertical"}))
+        );
+    }
+
+    #[test]
+    fn test_keystrokes_for_action() {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        keymap
+            .add_bindings(&json!({
+                "Workspace": {
+                    "cmd-t": "ToggleFileFinder",
+                    "cmd-s": "Save",
+                    "ctrl-k": "Kill"
+                },
+                "Editor": {
+                    "ctrl-x ctrl-s": "Save",
+                    "ctrl-k": "DeleteLine"
+                }
+            }))
+            .unwrap();
+
+        let editor = [context("Editor", &[]), context("Workspace", &[])];
+        let keystrokes_for = |action_type: &str, context_index: usize, keymap: &Keymap| {
+            keymap
+                .keystrokes_for_action(&json!({ "type": action_type }), context_index, &editor)
+                .map(|keystrokes| keystrokes.iter().map(|k| k.to_string()).collect::<Vec<_>>())
+        };
+
+        assert_eq!(keystrokes_for("ToggleFileFinder", 1, &keymap), Some(vec!["cmd-t".into()]));
+        assert_eq!(keystrokes_for("ToggleFileFinder", 0, &keymap), None);
+        assert_eq!(
+            keystrokes_for("Save", 0, &keymap),
+            Some(vec!["ctrl-x".into(), "ctrl-s".into()])
+        );
+
+        // Keystrokes that an inner context binds to a different action are skipped.
+        assert_eq!(keystrokes_for("Kill", 1, &keymap), None);
+
+        // Later bindings are preferred, and unbound keystrokes are skipped.
+        keymap
+            .add_bindings(&json!({
+                "Workspace": { "cmd-p": "ToggleFileFinder" },
+                "Editor": { "ctrl-x ctrl-s": null }
+            }))
+            .unwrap();
+        assert_eq!(keystrokes_for("ToggleFileFinder", 1, &keymap), Some(vec!["cmd-p".into()]));
+        assert_eq!(keystrokes_for("Save", 1, &keymap), Some(vec!["cmd-s".into()]));
+    }
+
+    fn context<'a>(component_name: &'a str, flags: &'a [String]) -> Context<'a> {
+        Context {
+            component_name,
+            flags,
+        }
+    }
+
+    fn keystrokes(source: &str) -> Vec<Keystroke> {
+        Keystroke::parse_sequence(source).unwrap()
+    }
+
+    fn action(context_index: usize, action: serde_json::Value) -> Match {
+        Match::Action {
+            context_index,
+            action,
+        }
+    }
+}
@@ -2,21 +2,39 @@
 extern crate lazy_static;
 extern crate futures;
 extern crate parking_lot;
+extern crate regex;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
 extern crate smallvec;
+extern crate tree_sitter;
+extern crate tree_sitter_javascript;
+extern crate tree_sitter_rust;
 
 pub mod buffer;
 pub mod buffer_view;
+pub mod clipboard;
 pub mod fs;
 pub mod notify_cell;
+pub mod project_search;
+pub mod syntax;
+pub mod tokenizer;
 pub mod window;
 pub mod workspace;
 pub mod fuzzy_search;
+pub mod keymap;
+mod command_palette;
 mod file_finder;
+mod find_bar;
 
+mod display_map;
+mod highlight_map;
+mod indentation;
 mod movement;
+mod pane_group;
 mod tree;
+
+#[cfg(test)]
+mod test_helpers;
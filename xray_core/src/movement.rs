@@ -1,5 +1,15 @@
+use std::char;
 use std::cmp;
+use std::ops::Range;
 use buffer::{Buffer, Point};
+use display_map::{Bias, DisplayMap};
+
+#[derive(Eq, PartialEq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
 
 pub fn left(buffer: &Buffer, mut point: Point) -> Point {
     if point.column > 0 {
@@ -22,27 +32,295 @@ pub fn right(buffer: &Buffer, mut point: Point) -> Point {
     point
 }
 
-pub fn up(buffer: &Buffer, mut point: Point, goal_column: Option<u32>) -> (Point, Option<u32>) {
-    let goal_column = goal_column.or(Some(point.column));
-    if point.row > 0 {
-        point.row -= 1;
-        point.column = cmp::min(goal_column.unwrap(), buffer.len_for_row(point.row).unwrap());
+/// Moves up by the given number of display rows, stopping at the first row. Moving up from the
+/// first row moves to the start of the buffer. The goal column is measured in display columns, so
+/// that the cursor stays aligned on screen across tabs and wrapped lines.
+pub fn up_by(
+    buffer: &Buffer,
+    display_map: &DisplayMap,
+    point: Point,
+    goal_column: Option<u32>,
+    rows: u32,
+) -> (Point, Option<u32>) {
+    let mut display_point = display_map.to_display_point(buffer, point);
+    let goal_column = goal_column.or(Some(display_point.column));
+    if display_point.row > 0 {
+        display_point.row = display_point.row.saturating_sub(rows);
+        display_point.column = goal_column.unwrap();
+        let point = display_map.to_buffer_point(buffer, display_point, Bias::Left);
+        (point, goal_column)
+    } else {
+        (Point::new(0, 0), goal_column)
+    }
+}
+
+/// Moves down by the given number of display rows, stopping at the last row. Moving down from
+/// the last row moves to the end of the buffer.
+pub fn down_by(
+    buffer: &Buffer,
+    display_map: &DisplayMap,
+    point: Point,
+    goal_column: Option<u32>,
+    rows: u32,
+) -> (Point, Option<u32>) {
+    let mut display_point = display_map.to_display_point(buffer, point);
+    let goal_column = goal_column.or(Some(display_point.column));
+    let max_row = display_map.row_count() - 1;
+    if display_point.row < max_row {
+        display_point.row = cmp::min(display_point.row + rows, max_row);
+        display_point.column = goal_column.unwrap();
+        let point = display_map.to_buffer_point(buffer, display_point, Bias::Left);
+        (point, goal_column)
+    } else {
+        (buffer.max_point(), goal_column)
+    }
+}
+
+/// Moves to the start of the word preceding the point, skipping any whitespace in between. At the
+/// start of a line, moves to the end of the previous line.
+pub fn prev_word_boundary(buffer: &Buffer, point: Point) -> Point {
+    if point.column == 0 {
+        return left(buffer, point);
+    }
+
+    let line = buffer.line(point.row).unwrap();
+    let mut column = point.column as usize;
+    while column > 0 && char_class(line[column - 1]) == CharClass::Whitespace {
+        column -= 1;
+    }
+    if column > 0 {
+        let class = char_class(line[column - 1]);
+        while column > 0 && char_class(line[column - 1]) == class {
+            column -= 1;
+        }
+    }
+    Point::new(point.row, column as u32)
+}
+
+/// Moves to the end of the word following the point, skipping any whitespace in between. At the
+/// end of a line, moves to the start of the next line.
+pub fn next_word_boundary(buffer: &Buffer, point: Point) -> Point {
+    let line = buffer.line(point.row).unwrap();
+    let mut column = point.column as usize;
+    if column == line.len() {
+        return right(buffer, point);
+    }
+
+    while column < line.len() && char_class(line[column]) == CharClass::Whitespace {
+        column += 1;
+    }
+    if column < line.len() {
+        let class = char_class(line[column]);
+        while column < line.len() && char_class(line[column]) == class {
+            column += 1;
+        }
+    }
+    Point::new(point.row, column as u32)
+}
+
+/// Like `prev_word_boundary`, but also stops within words at camelCase humps and underscores.
+pub fn prev_subword_boundary(buffer: &Buffer, point: Point) -> Point {
+    if point.column == 0 {
+        return left(buffer, point);
+    }
+
+    let line = buffer.line(point.row).unwrap();
+    let mut column = point.column as usize;
+    while column > 0 && char_class(line[column - 1]) == CharClass::Whitespace {
+        column -= 1;
+    }
+    if column == 0 {
+        return Point::new(point.row, 0);
+    }
+
+    let class = char_class(line[column - 1]);
+    if class == CharClass::Word {
+        while column > 0 && is_underscore(line[column - 1]) {
+            column -= 1;
+        }
+        let lowercase_end = column;
+        while column > 0 && is_lowercase_subword_char(line[column - 1]) {
+            column -= 1;
+        }
+        if column < lowercase_end {
+            // Include the capital letter that starts a camelCase hump.
+            if column > 0 && is_uppercase(line[column - 1]) {
+                column -= 1;
+            }
+        } else {
+            while column > 0 && is_uppercase(line[column - 1]) {
+                column -= 1;
+            }
+        }
+    } else {
+        while column > 0 && char_class(line[column - 1]) == class {
+            column -= 1;
+        }
+    }
+    Point::new(point.row, column as u32)
+}
+
+/// Like `next_word_boundary`, but also stops within words at camelCase humps and underscores.
+pub fn next_subword_boundary(buffer: &Buffer, point: Point) -> Point {
+    let line = buffer.line(point.row).unwrap();
+    let mut column = point.column as usize;
+    if column == line.len() {
+        return right(buffer, point);
+    }
+
+    while column < line.len() && char_class(line[column]) == CharClass::Whitespace {
+        column += 1;
+    }
+    if column == line.len() {
+        return Point::new(point.row, column as u32);
+    }
+
+    let class = char_class(line[column]);
+    if class == CharClass::Word {
+        while column < line.len() && is_underscore(line[column]) {
+            column += 1;
+        }
+        let uppercase_start = column;
+        while column < line.len() && is_uppercase(line[column]) {
+            column += 1;
+        }
+        if column - uppercase_start > 1 && column < line.len()
+            && is_lowercase_subword_char(line[column])
+        {
+            // In a run of capitals like "HTMLParser", the last one starts the next hump.
+            column -= 1;
+        } else {
+            while column < line.len() && is_lowercase_subword_char(line[column]) {
+                column += 1;
+            }
+        }
     } else {
-        point = Point::new(0, 0);
+        while column < line.len() && char_class(line[column]) == class {
+            column += 1;
+        }
     }
+    Point::new(point.row, column as u32)
+}
+
+/// Moves to the first non-whitespace character of the line, or to column 0 if the point is
+/// already there.
+pub fn beginning_of_line(buffer: &Buffer, point: Point) -> Point {
+    let line = buffer.line(point.row).unwrap();
+    let indentation = line.iter()
+        .take_while(|c| char_class(**c) == CharClass::Whitespace)
+        .count() as u32;
+    if point.column == indentation {
+        Point::new(point.row, 0)
+    } else {
+        Point::new(point.row, indentation)
+    }
+}
 
-    (point, goal_column)
+pub fn end_of_line(buffer: &Buffer, point: Point) -> Point {
+    Point::new(point.row, buffer.len_for_row(point.row).unwrap())
 }
 
-pub fn down(buffer: &Buffer, mut point: Point, goal_column: Option<u32>) -> (Point, Option<u32>) {
-    let goal_column = goal_column.or(Some(point.column));
+pub fn beginning_of_buffer(_buffer: &Buffer, _point: Point) -> Point {
+    Point::new(0, 0)
+}
+
+pub fn end_of_buffer(buffer: &Buffer, _point: Point) -> Point {
+    buffer.max_point()
+}
+
+/// Moves to the closest blank line above the paragraph preceding the point, or to the start of
+/// the buffer if there is none.
+pub fn prev_paragraph(buffer: &Buffer, point: Point) -> Point {
+    let mut row = point.row;
+    let mut seen_text = !is_blank_row(buffer, row);
+    while row > 0 {
+        row -= 1;
+        if !is_blank_row(buffer, row) {
+            seen_text = true;
+        } else if seen_text {
+            return Point::new(row, 0);
+        }
+    }
+    Point::new(0, 0)
+}
+
+/// Moves to the closest blank line below the paragraph following the point, or to the end of
+/// the buffer if there is none.
+pub fn next_paragraph(buffer: &Buffer, point: Point) -> Point {
     let max_point = buffer.max_point();
-    if point.row < max_point.row {
-        point.row += 1;
-        point.column = cmp::min(goal_column.unwrap(), buffer.len_for_row(point.row).unwrap())
+    let mut row = point.row;
+    let mut seen_text = !is_blank_row(buffer, row);
+    while row < max_point.row {
+        row += 1;
+        if !is_blank_row(buffer, row) {
+            seen_text = true;
+        } else if seen_text {
+            return Point::new(row, 0);
+        }
+    }
+    max_point
+}
+
+/// Returns the range of the word containing the point. Word characters are preferred over
+/// whitespace and punctuation when the point lies between two different kinds of characters.
+pub fn word_range(buffer: &Buffer, point: Point) -> Range<Point> {
+    let line = buffer.line(point.row).unwrap();
+    let column = point.column as usize;
+    let class_before = if column > 0 {
+        Some(char_class(line[column - 1]))
     } else {
-        point = max_point;
+        None
+    };
+    let class_after = line.get(column).map(|c| char_class(*c));
+    let class = match (class_before, class_after) {
+        (_, Some(CharClass::Word)) => CharClass::Word,
+        (Some(CharClass::Word), _) => CharClass::Word,
+        (_, Some(class)) => class,
+        (Some(class), None) => class,
+        (None, None) => return point..point,
+    };
+
+    let mut start = column;
+    while start > 0 && char_class(line[start - 1]) == class {
+        start -= 1;
+    }
+    let mut end = column;
+    while end < line.len() && char_class(line[end]) == class {
+        end += 1;
+    }
+    Point::new(point.row, start as u32)..Point::new(point.row, end as u32)
+}
+
+fn is_blank_row(buffer: &Buffer, row: u32) -> bool {
+    buffer
+        .line(row)
+        .unwrap()
+        .into_iter()
+        .all(|c| char_class(c) == CharClass::Whitespace)
+}
+
+fn is_underscore(code_unit: u16) -> bool {
+    code_unit == b'_' as u16
+}
+
+fn is_uppercase(code_unit: u16) -> bool {
+    match char::from_u32(code_unit as u32) {
+        Some(c) => c.is_uppercase(),
+        None => false,
     }
+}
+
+fn is_lowercase_subword_char(code_unit: u16) -> bool {
+    char_class(code_unit) == CharClass::Word && !is_underscore(code_unit)
+        && !is_uppercase(code_unit)
+}
 
-    (point, goal_column)
+fn char_class(code_unit: u16) -> CharClass {
+    // Surrogates only appear in non-ASCII characters, which we treat as word characters.
+    match char::from_u32(code_unit as u32) {
+        Some(c) if c.is_whitespace() => CharClass::Whitespace,
+        Some(c) if c.is_alphanumeric() || c == '_' => CharClass::Word,
+        Some(_) => CharClass::Punctuation,
+        None => CharClass::Word,
+    }
 }
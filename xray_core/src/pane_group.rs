@@ -0,0 +1,505 @@
+use serde_json;
+use std::cmp;
+use std::collections::HashMap;
+
+pub type PaneId = usize;
+
+/// The direction in which the children of a split are laid out. A horizontal split places its
+/// children side by side, and a vertical split stacks them on top of each other.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// A layout of panes that divide an area between them through nested splits. Each pane holds an
+/// ordered list of items, of which one is active, and one of the panes is active at any time.
+/// There is always at least one pane, even if it's empty.
+pub struct PaneGroup<T> {
+    root: Node,
+    panes: HashMap<PaneId, Pane<T>>,
+    active_pane_id: PaneId,
+    next_pane_id: PaneId,
+}
+
+struct Pane<T> {
+    items: Vec<T>,
+    active_item_index: Option<usize>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Node {
+    Pane(PaneId),
+    Split { axis: Axis, children: Vec<Node> },
+}
+
+impl<T> PaneGroup<T> {
+    pub fn new() -> Self {
+        let mut panes = HashMap::new();
+        panes.insert(0, Pane::new());
+        PaneGroup {
+            root: Node::Pane(0),
+            panes,
+            active_pane_id: 0,
+            next_pane_id: 1,
+        }
+    }
+
+    pub fn active_pane_id(&self) -> PaneId {
+        self.active_pane_id
+    }
+
+    pub fn active_item(&self) -> Option<&T> {
+        self.panes[&self.active_pane_id].active_item()
+    }
+
+    /// Returns the ids of all panes, in the order in which they appear on screen.
+    pub fn pane_ids(&self) -> Vec<PaneId> {
+        let mut pane_ids = Vec::new();
+        self.root.pane_ids(&mut pane_ids);
+        pane_ids
+    }
+
+    /// Returns the items of the given pane along with the index of its active item.
+    #[cfg(test)]
+    pub fn items(&self, pane_id: PaneId) -> Option<(&[T], Option<usize>)> {
+        self.panes
+            .get(&pane_id)
+            .map(|pane| (pane.items.as_slice(), pane.active_item_index))
+    }
+
+    /// Adds an item to the active pane after its active item and activates it.
+    pub fn add_item(&mut self, item: T) {
+        let pane = self.panes.get_mut(&self.active_pane_id).unwrap();
+        let index = pane.active_item_index.map_or(0, |index| index + 1);
+        pane.items.insert(index, item);
+        pane.active_item_index = Some(index);
+    }
+
+    /// Activates the first item in the active pane that satisfies the given predicate, returning
+    /// whether there was one.
+    pub fn activate_item_where<F: Fn(&T) -> bool>(&mut self, predicate: F) -> bool {
+        let pane = self.panes.get_mut(&self.active_pane_id).unwrap();
+        if let Some(index) = pane.items.iter().position(predicate) {
+            pane.active_item_index = Some(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Activates the given pane and the item at the given index within it.
+    pub fn activate_item(&mut self, pane_id: PaneId, index: usize) -> bool {
+        match self.panes.get_mut(&pane_id) {
+            Some(pane) if index < pane.items.len() => {
+                pane.active_item_index = Some(index);
+                self.active_pane_id = pane_id;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Splits the active pane along the given axis, placing a new pane after it and activating
+    /// the new pane.
+    pub fn split(&mut self, axis: Axis) -> PaneId {
+        let pane_id = self.next_pane_id;
+        self.next_pane_id += 1;
+        self.panes.insert(pane_id, Pane::new());
+        self.root.split(self.active_pane_id, pane_id, axis);
+        self.active_pane_id = pane_id;
+        pane_id
+    }
+
+    /// Removes the active item of the active pane. The pane is closed along with its last item
+    /// unless it's the only pane.
+    pub fn close_active_item(&mut self) -> Option<T> {
+        let pane_id = self.active_pane_id;
+        let item = {
+            let pane = self.panes.get_mut(&pane_id).unwrap();
+            let index = pane.active_item_index?;
+            pane.remove_item(index)
+        };
+        if self.panes[&pane_id].items.is_empty() {
+            self.close_pane(pane_id);
+        }
+        Some(item)
+    }
+
+    /// Closes the given pane and returns its items, activating the pane before it if it was
+    /// active. Closing the only pane just removes its items.
+    pub fn close_pane(&mut self, pane_id: PaneId) -> Vec<T> {
+        if !self.panes.contains_key(&pane_id) {
+            return Vec::new();
+        }
+
+        if self.panes.len() == 1 {
+            let pane = self.panes.get_mut(&pane_id).unwrap();
+            pane.active_item_index = None;
+            return pane.items.drain(..).collect();
+        }
+
+        if pane_id == self.active_pane_id {
+            self.activate_adjacent_pane(-1);
+        }
+        self.root.remove(pane_id);
+        self.panes.remove(&pane_id).unwrap().items
+    }
+
+    pub fn activate_next_pane(&mut self) {
+        self.activate_adjacent_pane(1);
+    }
+
+    pub fn activate_previous_pane(&mut self) {
+        self.activate_adjacent_pane(-1);
+    }
+
+    /// Moves an item to the given index of a pane, which may be the pane it's already in. The
+    /// moved item becomes the active item of the active pane, and the pane it came from is closed
+    /// if it is left empty.
+    pub fn move_item(
+        &mut self,
+        from_pane_id: PaneId,
+        from_index: usize,
+        to_pane_id: PaneId,
+        to_index: usize,
+    ) -> bool {
+        let is_valid = self.panes
+            .get(&from_pane_id)
+            .map_or(false, |pane| from_index < pane.items.len())
+            && self.panes.contains_key(&to_pane_id);
+        if !is_valid {
+            return false;
+        }
+
+        let item = self.panes
+            .get_mut(&from_pane_id)
+            .unwrap()
+            .remove_item(from_index);
+        {
+            let pane = self.panes.get_mut(&to_pane_id).unwrap();
+            let to_index = cmp::min(to_index, pane.items.len());
+            pane.items.insert(to_index, item);
+            pane.active_item_index = Some(to_index);
+        }
+        self.active_pane_id = to_pane_id;
+        if self.panes[&from_pane_id].items.is_empty() {
+            self.close_pane(from_pane_id);
+        }
+        true
+    }
+
+    /// Returns the layout as JSON, with each item rendered by the given function.
+    pub fn render<F: Fn(&T) -> serde_json::Value>(&self, render_item: &F) -> serde_json::Value {
+        self.render_node(&self.root, render_item)
+    }
+
+    fn render_node<F>(&self, node: &Node, render_item: &F) -> serde_json::Value
+    where
+        F: Fn(&T) -> serde_json::Value,
+    {
+        match *node {
+            Node::Pane(pane_id) => {
+                let pane = &self.panes[&pane_id];
+                json!({
+                    "type": "Pane",
+                    "id": pane_id,
+                    "items": pane.items.iter().map(render_item).collect::<Vec<_>>(),
+                    "active_item_index": pane.active_item_index,
+                    "is_active": pane_id == self.active_pane_id,
+                })
+            }
+            Node::Split { axis, ref children } => json!({
+                "type": "Split",
+                "axis": axis,
+                "children": children
+                    .iter()
+                    .map(|child| self.render_node(child, render_item))
+                    .collect::<Vec<_>>(),
+            }),
+        }
+    }
+
+    fn activate_adjacent_pane(&mut self, delta: isize) {
+        let pane_ids = self.pane_ids();
+        let index = pane_ids
+            .iter()
+            .position(|pane_id| *pane_id == self.active_pane_id)
+            .unwrap() as isize;
+        let len = pane_ids.len() as isize;
+        self.active_pane_id = pane_ids[((index + delta) % len + len) as usize % len as usize];
+    }
+}
+
+impl<T> Default for PaneGroup<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Pane<T> {
+    fn new() -> Self {
+        Pane {
+            items: Vec::new(),
+            active_item_index: None,
+        }
+    }
+
+    fn active_item(&self) -> Option<&T> {
+        self.active_item_index.map(|index| &self.items[index])
+    }
+
+    /// Removes the item at the given index, keeping the same item active unless it was the one
+    /// removed, in which case the item that took its place becomes active.
+    fn remove_item(&mut self, index: usize) -> T {
+        let item = self.items.remove(index);
+        self.active_item_index = match self.active_item_index {
+            _ if self.items.is_empty() => None,
+            Some(active_index) if active_index > index => Some(active_index - 1),
+            Some(active_index) => Some(cmp::min(active_index, self.items.len() - 1)),
+            None => None,
+        };
+        item
+    }
+}
+
+impl Node {
+    fn pane_ids(&self, pane_ids: &mut Vec<PaneId>) {
+        match *self {
+            Node::Pane(pane_id) => pane_ids.push(pane_id),
+            Node::Split { ref children, .. } => {
+                for child in children {
+                    child.pane_ids(pane_ids);
+                }
+            }
+        }
+    }
+
+    /// Places a new pane after the given one. If the given pane's parent is already split along
+    /// the requested axis, the new pane becomes its sibling. Otherwise the given pane is replaced
+    /// by a split containing both panes.
+    fn split(&mut self, pane_id: PaneId, new_pane_id: PaneId, axis: Axis) -> bool {
+        match *self {
+            Node::Pane(id) if id == pane_id => {
+                *self = Node::Split {
+                    axis,
+                    children: vec![Node::Pane(pane_id), Node::Pane(new_pane_id)],
+                };
+                true
+            }
+            Node::Pane(_) => false,
+            Node::Split {
+                axis: split_axis,
+                ref mut children,
+            } => {
+                if split_axis == axis {
+                    if let Some(index) = children
+                        .iter()
+                        .position(|child| *child == Node::Pane(pane_id))
+                    {
+                        children.insert(index + 1, Node::Pane(new_pane_id));
+                        return true;
+                    }
+                }
+                children
+                    .iter_mut()
+                    .any(|child| child.split(pane_id, new_pane_id, axis))
+            }
+        }
+    }
+
+    /// Removes the given pane, replacing splits that are left with a single child by that child
+    /// and merging splits into parents that are split along the same axis.
+    fn remove(&mut self, pane_id: PaneId) -> bool {
+        let only_child = match *self {
+            Node::Pane(_) => return false,
+            Node::Split {
+                axis,
+                ref mut children,
+            } => {
+                if let Some(index) = children
+                    .iter()
+                    .position(|child| *child == Node::Pane(pane_id))
+                {
+                    children.remove(index);
+                } else if let Some(index) = children
+                    .iter_mut()
+                    .position(|child| child.remove(pane_id))
+                {
+                    let is_same_axis = match children[index] {
+                        Node::Split {
+                            axis: child_axis, ..
+                        } => child_axis == axis,
+                        Node::Pane(_) => false,
+                    };
+                    if is_same_axis {
+                        if let Node::Split {
+                            children: grandchildren,
+                            ..
+                        } = children.remove(index)
+                        {
+                            for (offset, grandchild) in grandchildren.into_iter().enumerate() {
+                                children.insert(index + offset, grandchild);
+                            }
+                        }
+                    }
+                } else {
+                    return false;
+                }
+
+                if children.len() == 1 {
+                    children.pop()
+                } else {
+                    None
+                }
+            }
+        };
+
+        if let Some(only_child) = only_child {
+            *self = only_child;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items() {
+        let mut group = PaneGroup::new();
+        assert_eq!(group.active_item(), None);
+        group.add_item("a");
+        group.add_item("b");
+        group.activate_item(0, 0);
+        group.add_item("c");
+        assert_eq!(items(&group, 0), (vec!["a", "c", "b"], Some(1)));
+
+        assert!(group.activate_item_where(|item| *item == "b"));
+        assert!(!group.activate_item_where(|item| *item == "d"));
+        assert_eq!(group.active_item(), Some(&"b"));
+        assert!(!group.activate_item(0, 3));
+
+        // Reordering items within a pane keeps the moved item active.
+        assert!(group.move_item(0, 2, 0, 0));
+        assert_eq!(items(&group, 0), (vec!["b", "a", "c"], Some(0)));
+
+        assert_eq!(group.close_active_item(), Some("b"));
+        assert_eq!(items(&group, 0), (vec!["a", "c"], Some(0)));
+        group.activate_item(0, 1);
+        assert_eq!(group.close_active_item(), Some("c"));
+        assert_eq!(items(&group, 0), (vec!["a"], Some(0)));
+        assert_eq!(group.close_active_item(), Some("a"));
+        assert_eq!(group.close_active_item(), None);
+        assert_eq!(group.pane_ids(), vec![0]);
+    }
+
+    #[test]
+    fn test_splits() {
+        let mut group = PaneGroup::new();
+        group.add_item("a");
+        let pane_1 = group.split(Axis::Horizontal);
+        group.add_item("b");
+        let pane_2 = group.split(Axis::Vertical);
+        group.add_item("c");
+        let pane_3 = group.split(Axis::Vertical);
+        group.add_item("d");
+        assert_eq!(
+            group.root,
+            split(
+                Axis::Horizontal,
+                vec![
+                    Node::Pane(0),
+                    split(
+                        Axis::Vertical,
+                        vec![Node::Pane(pane_1), Node::Pane(pane_2), Node::Pane(pane_3)],
+                    ),
+                ],
+            )
+        );
+        assert_eq!(group.pane_ids(), vec![0, pane_1, pane_2, pane_3]);
+
+        group.activate_next_pane();
+        assert_eq!(group.active_pane_id(), 0);
+        group.activate_previous_pane();
+        group.activate_previous_pane();
+        assert_eq!(group.active_item(), Some(&"c"));
+
+        // Moving the last item out of a pane closes it.
+        assert!(group.move_item(pane_2, 0, 0, 1));
+        assert_eq!(items(&group, 0), (vec!["a", "c"], Some(1)));
+        assert_eq!(group.active_pane_id(), 0);
+        assert_eq!(group.pane_ids(), vec![0, pane_1, pane_3]);
+
+        // Splits left with a single child are replaced by it, merging splits along the same axis.
+        let pane_4 = group.split(Axis::Vertical);
+        assert_eq!(group.close_pane(pane_1), vec!["b"]);
+        assert_eq!(group.close_pane(pane_3), vec!["d"]);
+        assert_eq!(
+            group.root,
+            split(Axis::Vertical, vec![Node::Pane(0), Node::Pane(pane_4)])
+        );
+        assert_eq!(group.active_pane_id(), pane_4);
+
+        // Closing the active pane activates the one before it.
+        assert!(group.close_pane(pane_4).is_empty());
+        assert_eq!(group.root, Node::Pane(0));
+        assert_eq!(group.active_pane_id(), 0);
+        assert_eq!(
+            group.render(&|item| json!(item)),
+            json!({
+                "type": "Pane",
+                "id": 0,
+                "items": ["a", "c"],
+                "active_item_index": 1,
+                "is_active": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_merging_splits() {
+        let mut group = PaneGroup::<&'static str>::new();
+        let pane_1 = group.split(Axis::Vertical);
+        let pane_2 = group.split(Axis::Horizontal);
+        let pane_3 = group.split(Axis::Vertical);
+        assert_eq!(
+            group.root,
+            split(
+                Axis::Vertical,
+                vec![
+                    Node::Pane(0),
+                    split(
+                        Axis::Horizontal,
+                        vec![
+                            Node::Pane(pane_1),
+                            split(Axis::Vertical, vec![Node::Pane(pane_2), Node::Pane(pane_3)]),
+                        ],
+                    ),
+                ],
+            )
+        );
+
+        group.close_pane(pane_1);
+        assert_eq!(
+            group.root,
+            split(
+                Axis::Vertical,
+                vec![Node::Pane(0), Node::Pane(pane_2), Node::Pane(pane_3)],
+            )
+        );
+    }
+
+    fn items(
+        group: &PaneGroup<&'static str>,
+        pane_id: PaneId,
+    ) -> (Vec<&'static str>, Option<usize>) {
+        let (items, active_item_index) = group.items(pane_id).unwrap();
+        (items.to_vec(), active_item_index)
+    }
+
+    fn split(axis: Axis, children: Vec<Node>) -> Node {
+        Node::Split { axis, children }
+    }
+}
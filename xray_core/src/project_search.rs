@@ -0,0 +1,822 @@
+use futures::{Async, Poll, Stream};
+use notify_cell::{NotifyCell, NotifyCellObserver, WeakNotifyCell};
+use regex::{self, Regex, RegexBuilder};
+use serde_json;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use buffer::{Buffer, SaveError};
+use fs;
+use window::{Command, View, WindowHandle};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SearchQuery {
+    pub pattern: String,
+    #[serde(default)]
+    pub is_regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default)]
+    pub whole_word: bool,
+    #[serde(default)]
+    pub include_ignored: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct SearchMatch {
+    pub path: PathBuf,
+    pub row: u32,
+    pub column_ranges: Vec<Range<u32>>,
+    pub line: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct FileReplacement {
+    pub path: PathBuf,
+    pub replacement_count: usize,
+    pub error: Option<String>,
+}
+
+/// Scans the contents of every file in a set of trees for matches of a query. Like `fs::Search`,
+/// it is a stream that performs a bounded amount of work each time it is polled and publishes the
+/// matches found so far to its observer. Dropping the observer cancels the search.
+pub struct Search {
+    regex: Regex,
+    roots: Vec<(PathBuf, fs::FilePaths)>,
+    root_index: usize,
+    max_results: usize,
+    results: Vec<SearchMatch>,
+    file_count_per_poll: usize,
+    done: bool,
+    updates: WeakNotifyCell<Vec<SearchMatch>>,
+}
+
+pub trait ProjectSearchViewDelegate {
+    fn trees(&self) -> &Vec<Box<fs::Tree>>;
+    fn did_close(&mut self);
+    fn did_confirm(&mut self, path: PathBuf);
+    /// Returns the buffer for the file at the given path if it is already open.
+    fn open_buffer(&self, path: &Path) -> Option<Rc<RefCell<Buffer>>>;
+}
+
+/// Searches the contents of the delegate's trees as the query is edited. Each search runs on the
+/// window's executor, and starting a new one drops the observer of the previous search, which
+/// cancels it.
+pub struct ProjectSearchView<T: ProjectSearchViewDelegate> {
+    delegate: Weak<RefCell<T>>,
+    query: SearchQuery,
+    replacement: String,
+    error: Option<String>,
+    selected_index: usize,
+    matches: Vec<SearchMatch>,
+    replacements: Vec<FileReplacement>,
+    max_results: usize,
+    search_updates: Option<NotifyCellObserver<Vec<SearchMatch>>>,
+    window_handle: Option<WindowHandle>,
+    updates: NotifyCell<()>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+enum ProjectSearchAction {
+    UpdateQuery { query: String },
+    UpdateReplacement { replacement: String },
+    ToggleRegex,
+    ToggleCaseSensitive,
+    ToggleWholeWord,
+    ToggleIncludeIgnored,
+    ReplaceAll,
+    SelectPrevious,
+    SelectNext,
+    Confirm,
+    Close,
+}
+
+impl SearchQuery {
+    pub fn new<T: Into<String>>(pattern: T) -> Self {
+        SearchQuery {
+            pattern: pattern.into(),
+            is_regex: false,
+            case_sensitive: false,
+            whole_word: false,
+            include_ignored: false,
+        }
+    }
+
+    pub fn to_regex(&self) -> Result<Regex, regex::Error> {
+        let mut pattern = if self.is_regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+    }
+
+    /// Converts a replacement string into a template for `Buffer::find_replacements`. Capture
+    /// group references like `$1` are only expanded for regex queries, so `$` is escaped in the
+    /// replacements for literal ones.
+    pub fn replacement_template(&self, replacement: &str) -> String {
+        if self.is_regex {
+            replacement.to_string()
+        } else {
+            replacement.replace('$', "$$")
+        }
+    }
+}
+
+impl Search {
+    const DEFAULT_FILE_COUNT_PER_POLL: usize = 100;
+
+    pub fn new(
+        trees: &[Box<fs::Tree>],
+        query: &SearchQuery,
+        max_results: usize,
+    ) -> Result<(Self, NotifyCellObserver<Vec<SearchMatch>>), regex::Error> {
+        let regex = query.to_regex()?;
+        let (updates, updates_observer) = NotifyCell::weak(Vec::new());
+        let roots = trees
+            .iter()
+            .map(|tree| {
+                let file_paths = tree.root().file_paths(query.include_ignored);
+                (tree.path().to_owned(), file_paths)
+            })
+            .collect();
+
+        let search = Search {
+            regex,
+            roots,
+            root_index: 0,
+            max_results,
+            results: Vec::new(),
+            file_count_per_poll: Self::DEFAULT_FILE_COUNT_PER_POLL,
+            done: false,
+            updates,
+        };
+        Ok((search, updates_observer))
+    }
+
+    fn next_path(&mut self) -> Option<PathBuf> {
+        while self.root_index < self.roots.len() {
+            let root = &mut self.roots[self.root_index];
+            if let Some(path) = root.1.next() {
+                return Some(root.0.join(path));
+            }
+            self.root_index += 1;
+        }
+        None
+    }
+
+    fn search_file(&mut self, path: PathBuf) {
+        let mut contents = Vec::new();
+        if File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut contents))
+            .is_err()
+        {
+            return;
+        }
+
+        if is_binary(&contents) {
+            return;
+        }
+
+        let contents = String::from_utf8_lossy(&contents);
+        for (row, line) in contents.lines().enumerate() {
+            let column_ranges = self.regex
+                .find_iter(line)
+                .filter(|mat| mat.start() < mat.end())
+                .map(|mat| {
+                    let start = utf16_len(&line[..mat.start()]);
+                    start..start + utf16_len(mat.as_str())
+                })
+                .collect::<Vec<_>>();
+
+            if !column_ranges.is_empty() {
+                self.results.push(SearchMatch {
+                    path: path.clone(),
+                    row: row as u32,
+                    column_ranges,
+                    line: line.to_string(),
+                });
+                if self.results.len() >= self.max_results {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Stream for Search {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.done {
+            return Ok(Async::Ready(None));
+        }
+
+        for _ in 0..self.file_count_per_poll {
+            if let Some(path) = self.next_path() {
+                self.search_file(path);
+                if self.results.len() >= self.max_results {
+                    self.done = true;
+                    break;
+                }
+            } else {
+                self.done = true;
+                break;
+            }
+        }
+
+        if self.updates.try_set(self.results.clone()).is_ok() {
+            Ok(Async::Ready(Some(())))
+        } else {
+            self.done = true;
+            Ok(Async::Ready(None))
+        }
+    }
+}
+
+impl<T: ProjectSearchViewDelegate> View for ProjectSearchView<T> {
+    fn component_name(&self) -> &'static str {
+        "ProjectSearch"
+    }
+
+    fn render(&self) -> serde_json::Value {
+        json!({
+            "query": self.query.pattern.as_str(),
+            "replacement": self.replacement.as_str(),
+            "is_regex": self.query.is_regex,
+            "case_sensitive": self.query.case_sensitive,
+            "whole_word": self.query.whole_word,
+            "include_ignored": self.query.include_ignored,
+            "error": self.error,
+            "selected_index": self.selected_index,
+            "matches": self.matches,
+            "replacements": self.replacements,
+        })
+    }
+
+    fn will_mount(&mut self, window_handle: WindowHandle) {
+        self.window_handle = Some(window_handle);
+    }
+
+    fn dispatch_action(&mut self, action: serde_json::Value) -> bool {
+        match serde_json::from_value(action) {
+            Ok(ProjectSearchAction::UpdateQuery { query }) => self.update_query(query),
+            Ok(ProjectSearchAction::UpdateReplacement { replacement }) => {
+                self.replacement = replacement;
+                self.updates.set(());
+            }
+            Ok(ProjectSearchAction::ToggleRegex) => {
+                self.query.is_regex = !self.query.is_regex;
+                self.search();
+            }
+            Ok(ProjectSearchAction::ToggleCaseSensitive) => {
+                self.query.case_sensitive = !self.query.case_sensitive;
+                self.search();
+            }
+            Ok(ProjectSearchAction::ToggleWholeWord) => {
+                self.query.whole_word = !self.query.whole_word;
+                self.search();
+            }
+            Ok(ProjectSearchAction::ToggleIncludeIgnored) => {
+                self.query.include_ignored = !self.query.include_ignored;
+                self.search();
+            }
+            Ok(ProjectSearchAction::ReplaceAll) => self.replace_all(),
+            Ok(ProjectSearchAction::SelectPrevious) => self.select_previous(),
+            Ok(ProjectSearchAction::SelectNext) => self.select_next(),
+            Ok(ProjectSearchAction::Confirm) => self.confirm(),
+            Ok(ProjectSearchAction::Close) => self.close(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new("Toggle Regex", json!({"type": "ToggleRegex"})),
+            Command::new("Toggle Case Sensitive", json!({"type": "ToggleCaseSensitive"})),
+            Command::new("Toggle Whole Word", json!({"type": "ToggleWholeWord"})),
+            Command::new("Toggle Include Ignored", json!({"type": "ToggleIncludeIgnored"})),
+            Command::new("Replace All in Project", json!({"type": "ReplaceAll"})),
+            Command::new("Close Project Search", json!({"type": "Close"})),
+        ]
+    }
+}
+
+impl<T: ProjectSearchViewDelegate> Stream for ProjectSearchView<T> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut matches_updated = false;
+        if let Some(search_updates) = self.search_updates.as_mut() {
+            if let Async::Ready(Some(matches)) = search_updates.poll()? {
+                self.matches = matches;
+                matches_updated = true;
+            }
+        }
+        if matches_updated {
+            self.selected_index = self.selected_index.min(self.matches.len().saturating_sub(1));
+        }
+
+        let updates_poll = self.updates.poll()?;
+        if matches_updated || updates_poll.is_ready() {
+            Ok(Async::Ready(Some(())))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+impl<T: ProjectSearchViewDelegate> ProjectSearchView<T> {
+    const DEFAULT_MAX_RESULTS: usize = 1000;
+
+    pub fn new(delegate: Weak<RefCell<T>>) -> Self {
+        Self {
+            delegate,
+            query: SearchQuery::new(""),
+            replacement: String::new(),
+            error: None,
+            selected_index: 0,
+            matches: Vec::new(),
+            replacements: Vec::new(),
+            max_results: Self::DEFAULT_MAX_RESULTS,
+            search_updates: None,
+            window_handle: None,
+            updates: NotifyCell::new(()),
+        }
+    }
+
+    fn update_query(&mut self, query: String) {
+        if self.query.pattern != query {
+            self.query.pattern = query;
+            self.search();
+        }
+    }
+
+    fn search(&mut self) {
+        // Dropping the previous search's observer cancels it, so it stops publishing matches.
+        self.search_updates = None;
+        self.matches.clear();
+        self.replacements.clear();
+        self.selected_index = 0;
+        self.error = None;
+
+        if !self.query.pattern.is_empty() {
+            let delegate = self.delegate.upgrade().unwrap();
+            let delegate = delegate.borrow();
+            match Search::new(delegate.trees(), &self.query, self.max_results) {
+                Ok((search, search_updates)) => {
+                    self.search_updates = Some(search_updates);
+                    self.window_handle.as_ref().unwrap().spawn(search.for_each(|_| Ok(())));
+                }
+                Err(error) => self.error = Some(error.to_string()),
+            }
+        }
+        self.updates.set(());
+    }
+
+    /// Replaces the query's matches in every file of the delegate's trees, editing the files that
+    /// are open in their buffers. The files are listed again rather than taken from the results,
+    /// which are capped and may still be streaming in. The search is then restarted, since its
+    /// matches are stale.
+    fn replace_all(&mut self) {
+        let result = {
+            let delegate = self.delegate.upgrade().unwrap();
+            let delegate = delegate.borrow();
+            let mut paths = Vec::new();
+            for tree in delegate.trees() {
+                let root_path = tree.path();
+                let file_paths = tree.root().file_paths(self.query.include_ignored);
+                paths.extend(file_paths.map(|path| root_path.join(path)));
+            }
+            replace(&paths, &self.query, &self.replacement, |path| {
+                delegate.open_buffer(path)
+            })
+        };
+        self.search();
+        match result {
+            Ok(replacements) => {
+                self.replacements = replacements
+                    .into_iter()
+                    .filter(|file| file.replacement_count > 0 || file.error.is_some())
+                    .collect()
+            }
+            Err(error) => self.error = Some(error.to_string()),
+        }
+    }
+
+    fn select_previous(&mut self) {
+        if self.selected_index > 0 {
+            self.selected_index -= 1;
+            self.updates.set(());
+        }
+    }
+
+    fn select_next(&mut self) {
+        if self.selected_index + 1 < self.matches.len() {
+            self.selected_index += 1;
+            self.updates.set(());
+        }
+    }
+
+    fn confirm(&mut self) {
+        if let Some(search_match) = self.matches.get(self.selected_index) {
+            let delegate = self.delegate.upgrade().unwrap();
+            delegate.borrow_mut().did_confirm(search_match.path.clone());
+        }
+    }
+
+    fn close(&mut self) {
+        let delegate = self.delegate.upgrade().unwrap();
+        let mut delegate = delegate.borrow_mut();
+        delegate.did_close();
+    }
+}
+
+/// Replaces every match of the query in each of the given files and reports how many matches
+/// were replaced per file. Files that `open_buffer` returns a buffer for are edited in that buffer
+/// and left unsaved, so the edits can be reviewed and undone. All other files are loaded, edited
+/// and saved back to disk.
+pub fn replace<F>(
+    paths: &[PathBuf],
+    query: &SearchQuery,
+    replacement: &str,
+    mut open_buffer: F,
+) -> Result<Vec<FileReplacement>, regex::Error>
+where
+    F: FnMut(&Path) -> Option<Rc<RefCell<Buffer>>>,
+{
+    let regex = query.to_regex()?;
+    let template = query.replacement_template(replacement);
+    Ok(paths
+        .iter()
+        .map(|path| {
+            let (replacement_count, error) = match open_buffer(path) {
+                Some(buffer) => (buffer.borrow_mut().replace_all(&regex, &template), None),
+                None => match replace_in_file(path, &regex, &template) {
+                    Ok(replacement_count) => (replacement_count, None),
+                    Err(error) => (0, Some(error)),
+                },
+            };
+            FileReplacement {
+                path: path.clone(),
+                replacement_count,
+                error,
+            }
+        })
+        .collect())
+}
+
+fn replace_in_file(path: &Path, regex: &Regex, template: &str) -> Result<usize, String> {
+    // Check for matches before loading a buffer, so that binary files and files without matches
+    // are left untouched.
+    let mut contents = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut contents))
+        .map_err(|error| error.to_string())?;
+    if is_binary(&contents) || !regex.is_match(&String::from_utf8_lossy(&contents)) {
+        return Ok(0);
+    }
+
+    let mut buffer = Buffer::load(1, path).map_err(|error| error.to_string())?;
+    let replacement_count = buffer.replace_all(regex, template);
+    if replacement_count > 0 {
+        buffer.save().map_err(|error| match error {
+            SaveError::NoPath => "buffer has no path".to_string(),
+            SaveError::ModifiedOnDisk => "file was modified on disk".to_string(),
+            SaveError::IoError(error) => error.to_string(),
+        })?;
+    }
+    Ok(replacement_count)
+}
+
+// Treats files containing null bytes near the start as binary.
+fn is_binary(contents: &[u8]) -> bool {
+    contents.iter().take(8192).any(|byte| *byte == 0)
+}
+
+fn utf16_len(string: &str) -> u32 {
+    string.chars().map(|c| c.len_utf16() as u32).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs as std_fs;
+    use std::path::Path;
+    use futures::{future, Future};
+    use test_helpers::{read_to_string, temp_dir, write_file, TestDelegate, TestExecutor, TestTree};
+    use window::Window;
+
+    #[test]
+    fn test_search() {
+        let dir = temp_dir("project-search");
+        std_fs::create_dir(dir.join("src")).unwrap();
+        write_file(&dir.join("a.txt"), "foo bar\nfoobar foo\n");
+        write_file(&dir.join("src/b.txt"), "α Foo\nbaz\r\nfoo");
+        write_file(&dir.join("src/binary"), b"foo\0");
+        write_file(&dir.join("ignored.txt"), "foo");
+
+        let tree = TestTree::new(dir.clone(), &["a.txt", "src/b.txt", "src/binary"]);
+        tree.root
+            .insert("ignored.txt", fs::Entry::file(false, true))
+            .unwrap();
+        let trees: Vec<Box<fs::Tree>> = vec![Box::new(tree)];
+
+        let query = SearchQuery::new("foo");
+        assert_eq!(
+            search(&trees, &query, 10),
+            vec![
+                search_match(&dir.join("a.txt"), 0, &[(0, 3)], "foo bar"),
+                search_match(&dir.join("a.txt"), 1, &[(0, 3), (7, 10)], "foobar foo"),
+                search_match(&dir.join("src/b.txt"), 0, &[(2, 5)], "α Foo"),
+                search_match(&dir.join("src/b.txt"), 2, &[(0, 3)], "foo"),
+            ]
+        );
+
+        let mut query = SearchQuery::new("foo");
+        query.case_sensitive = true;
+        query.whole_word = true;
+        assert_eq!(
+            search(&trees, &query, 10),
+            vec![
+                search_match(&dir.join("a.txt"), 0, &[(0, 3)], "foo bar"),
+                search_match(&dir.join("a.txt"), 1, &[(7, 10)], "foobar foo"),
+                search_match(&dir.join("src/b.txt"), 2, &[(0, 3)], "foo"),
+            ]
+        );
+
+        let mut query = SearchQuery::new("ba.");
+        query.is_regex = true;
+        assert_eq!(
+            search(&trees, &query, 2),
+            vec![
+                search_match(&dir.join("a.txt"), 0, &[(4, 7)], "foo bar"),
+                search_match(&dir.join("a.txt"), 1, &[(3, 6)], "foobar foo"),
+            ]
+        );
+
+        let mut query = SearchQuery::new("foo");
+        query.include_ignored = true;
+        assert_eq!(search(&trees, &query, 10).len(), 5);
+
+        let mut query = SearchQuery::new("(");
+        query.is_regex = true;
+        assert!(Search::new(&trees, &query, 10).is_err());
+
+        std_fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_replace() {
+        let dir = temp_dir("project-replace");
+        write_file(&dir.join("a.txt"), "foo(1) foo(2)\n");
+        write_file(&dir.join("b.txt"), "foo(3)\n");
+        write_file(&dir.join("c.txt"), "bar\n");
+        write_file(&dir.join("d.bin"), b"foo(4)\0");
+
+        let open_buffer = Rc::new(RefCell::new(Buffer::load(1, dir.join("b.txt")).unwrap()));
+        let paths = vec![
+            dir.join("a.txt"),
+            dir.join("b.txt"),
+            dir.join("c.txt"),
+            dir.join("d.bin"),
+            dir.join("missing.txt"),
+        ];
+        let mut query = SearchQuery::new(r"foo\((\d)\)");
+        query.is_regex = true;
+        let summary = replace(&paths, &query, "bar($1, $$)", |path| {
+            if path == dir.join("b.txt") {
+                Some(open_buffer.clone())
+            } else {
+                None
+            }
+        }).unwrap();
+
+        assert_eq!(
+            summary
+                .iter()
+                .map(|file| (file.path.clone(), file.replacement_count, file.error.is_some()))
+                .collect::<Vec<_>>(),
+            vec![
+                (dir.join("a.txt"), 2, false),
+                (dir.join("b.txt"), 1, false),
+                (dir.join("c.txt"), 0, false),
+                (dir.join("d.bin"), 0, false),
+                (dir.join("missing.txt"), 0, true),
+            ]
+        );
+
+        // Files without an open buffer are saved, while open buffers are only edited. Binary files
+        // are skipped.
+        assert_eq!(read_to_string(&dir.join("a.txt")), "bar(1, $) bar(2, $)\n");
+        assert_eq!(read_to_string(&dir.join("b.txt")), "foo(3)\n");
+        assert_eq!(read_to_string(&dir.join("d.bin")), "foo(4)\0");
+        assert_eq!(open_buffer.borrow().to_string(), "bar(3, $)\n");
+        assert!(open_buffer.borrow().is_dirty());
+
+        // Replacements for literal queries are inserted verbatim.
+        let summary = replace(&paths[2..3], &SearchQuery::new("bar"), "$1", |_| None).unwrap();
+        assert_eq!(summary[0].replacement_count, 1);
+        assert_eq!(read_to_string(&dir.join("c.txt")), "$1\n");
+
+        std_fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let trees: Vec<Box<fs::Tree>> = vec![Box::new(TestTree::new("/nonexistent", &[]))];
+        let (mut search, updates) = Search::new(&trees, &SearchQuery::new("a"), 10).unwrap();
+        drop(updates);
+        assert_eq!(search.poll(), Ok(Async::Ready(None)));
+    }
+
+    #[test]
+    fn test_view() {
+        let dir = temp_dir("project-search-view");
+        write_file(&dir.join("a.txt"), "foo\nbar\n");
+        write_file(&dir.join("b.txt"), "bar baz\n");
+
+        let tree = TestTree::new(dir.clone(), &["a.txt", "b.txt"]);
+        let delegate = Rc::new(RefCell::new(TestDelegate::new(vec![tree])));
+
+        let executor = TestExecutor::new();
+        let mut window = Window::new(Some(Box::new(executor.clone())), 100.0);
+        let view = Rc::new(RefCell::new(ProjectSearchView::new(Rc::downgrade(&delegate))));
+        let view_handle = window.handle().add_shared_view(view.clone());
+        let view_id = view_handle.view_id;
+
+        // A search that is superseded before it runs never publishes its matches.
+        window.dispatch_action(view_id, json!({"type": "UpdateQuery", "query": "foo"}));
+        window.dispatch_action(view_id, json!({"type": "UpdateQuery", "query": "ba"}));
+        let mut searches = executor.take();
+        assert_eq!(searches.len(), 2);
+        searches.remove(0).wait().unwrap();
+        poll_view(&view);
+        assert_eq!(rendered_matches(&view), Vec::<(PathBuf, u32)>::new());
+
+        searches.remove(0).wait().unwrap();
+        poll_view(&view);
+        assert_eq!(
+            rendered_matches(&view),
+            vec![(dir.join("a.txt"), 1), (dir.join("b.txt"), 0)]
+        );
+        assert_eq!(
+            view.borrow().render()["matches"][1]["column_ranges"],
+            json!([{"start": 0, "end": 2}, {"start": 4, "end": 6}])
+        );
+
+        window.dispatch_action(view_id, json!({"type": "SelectNext"}));
+        window.dispatch_action(view_id, json!({"type": "Confirm"}));
+        assert_eq!(delegate.borrow().confirmed_paths, vec![dir.join("b.txt")]);
+
+        // Invalid regexes are reported without starting a search.
+        window.dispatch_action(view_id, json!({"type": "ToggleRegex"}));
+        window.dispatch_action(view_id, json!({"type": "UpdateQuery", "query": "("}));
+        assert!(view.borrow().render()["error"].is_string());
+        assert_eq!(rendered_matches(&view), Vec::<(PathBuf, u32)>::new());
+        assert_eq!(executor.take().len(), 1);
+
+        std_fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_view_replace_all() {
+        let dir = temp_dir("project-replace-view");
+        write_file(&dir.join("a.txt"), "foo(1)\nfoo(2)\n");
+        write_file(&dir.join("b.txt"), "foo(3)\n");
+        write_file(&dir.join("c.txt"), "bar\n");
+
+        let tree = TestTree::new(dir.clone(), &["a.txt", "b.txt", "c.txt"]);
+        let open_buffer = Rc::new(RefCell::new(Buffer::load(1, dir.join("b.txt")).unwrap()));
+        let mut delegate = TestDelegate::new(vec![tree]);
+        delegate.open_buffers.push(open_buffer.clone());
+        let delegate = Rc::new(RefCell::new(delegate));
+
+        let executor = TestExecutor::new();
+        let mut window = Window::new(Some(Box::new(executor.clone())), 100.0);
+        let view = Rc::new(RefCell::new(ProjectSearchView::new(Rc::downgrade(&delegate))));
+        let view_handle = window.handle().add_shared_view(view.clone());
+        let view_id = view_handle.view_id;
+
+        window.dispatch_action(view_id, json!({"type": "ToggleRegex"}));
+        window.dispatch_action(view_id, json!({"type": "UpdateQuery", "query": r"foo\((\d)\)"}));
+        window.dispatch_action(view_id, json!({"type": "UpdateReplacement", "replacement": "$1"}));
+        for search in executor.take() {
+            search.wait().unwrap();
+        }
+        poll_view(&view);
+        assert_eq!(rendered_matches(&view).len(), 3);
+
+        // Open files are edited in their buffers, and the rest are saved to disk. Files without
+        // matches are left out of the replacements.
+        window.dispatch_action(view_id, json!({"type": "ReplaceAll"}));
+        assert_eq!(read_to_string(&dir.join("a.txt")), "1\n2\n");
+        assert_eq!(read_to_string(&dir.join("b.txt")), "foo(3)\n");
+        assert_eq!(open_buffer.borrow().to_string(), "3\n");
+        assert_eq!(
+            view.borrow().render()["replacements"],
+            json!([
+                {"path": dir.join("a.txt"), "replacement_count": 2, "error": null},
+                {"path": dir.join("b.txt"), "replacement_count": 1, "error": null},
+            ])
+        );
+
+        // The search is restarted, and its replacements are cleared when the query changes.
+        for search in executor.take() {
+            search.wait().unwrap();
+        }
+        poll_view(&view);
+        assert_eq!(rendered_matches(&view), vec![(dir.join("b.txt"), 0)]);
+        window.dispatch_action(view_id, json!({"type": "UpdateQuery", "query": "3"}));
+        assert_eq!(view.borrow().render()["replacements"], json!([]));
+
+        std_fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_view_replace_all_beyond_max_results() {
+        let dir = temp_dir("project-replace-view-max-results");
+        let file_names = ["a.txt", "b.txt", "c.txt"];
+        for file_name in &file_names {
+            write_file(&dir.join(file_name), "foo\n");
+        }
+
+        let tree = TestTree::new(dir.clone(), &file_names);
+        let delegate = Rc::new(RefCell::new(TestDelegate::new(vec![tree])));
+        let executor = TestExecutor::new();
+        let mut window = Window::new(Some(Box::new(executor.clone())), 100.0);
+        let view = Rc::new(RefCell::new(ProjectSearchView::new(Rc::downgrade(&delegate))));
+        view.borrow_mut().max_results = 2;
+        let view_handle = window.handle().add_shared_view(view.clone());
+        let view_id = view_handle.view_id;
+
+        window.dispatch_action(view_id, json!({"type": "UpdateQuery", "query": "foo"}));
+        window.dispatch_action(view_id, json!({"type": "UpdateReplacement", "replacement": "bar"}));
+        executor.run();
+        poll_view(&view);
+        assert_eq!(rendered_matches(&view).len(), 2);
+
+        // Files beyond the capped results are replaced too.
+        window.dispatch_action(view_id, json!({"type": "ReplaceAll"}));
+        for file_name in &file_names {
+            assert_eq!(read_to_string(&dir.join(file_name)), "bar\n");
+        }
+        assert_eq!(
+            view.borrow().render()["replacements"].as_array().unwrap().len(),
+            3
+        );
+
+        std_fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn poll_view(view: &Rc<RefCell<ProjectSearchView<TestDelegate>>>) {
+        future::poll_fn(|| Ok::<_, ()>(Async::Ready(view.borrow_mut().poll())))
+            .wait()
+            .unwrap()
+            .unwrap();
+    }
+
+    fn rendered_matches(
+        view: &Rc<RefCell<ProjectSearchView<TestDelegate>>>,
+    ) -> Vec<(PathBuf, u32)> {
+        view.borrow().render()["matches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|search_match| {
+                (
+                    PathBuf::from(search_match["path"].as_str().unwrap()),
+                    search_match["row"].as_u64().unwrap() as u32,
+                )
+            })
+            .collect()
+    }
+
+    fn search(trees: &[Box<fs::Tree>], query: &SearchQuery, max_results: usize) -> Vec<SearchMatch> {
+        let (search, updates) = Search::new(trees, query, max_results).unwrap();
+        assert_eq!(search.wait().count(), 1);
+        updates.get().unwrap()
+    }
+
+    fn search_match(path: &Path, row: u32, column_ranges: &[(u32, u32)], line: &str) -> SearchMatch {
+        SearchMatch {
+            path: path.to_owned(),
+            row,
+            column_ranges: column_ranges.iter().map(|&(start, end)| start..end).collect(),
+            line: line.to_string(),
+        }
+    }
+}
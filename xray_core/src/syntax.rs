@@ -0,0 +1,265 @@
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use tree_sitter::{self, InputEdit, Language, Node, Parser};
+use tree_sitter_javascript;
+use tree_sitter_rust;
+use buffer::{Edit, Point};
+use tokenizer;
+
+/// Parses the text of a buffer with tree-sitter. Edits are applied to the previous tree as soon as
+/// they are made, but the text is only parsed again the next time the tree is requested. Parsing
+/// reuses the parts of the previous tree that the edits didn't touch.
+pub struct Syntax {
+    parser: Parser,
+    tree: Option<tree_sitter::Tree>,
+    is_stale: bool,
+}
+
+/// The syntax tree of a buffer at a particular version. Cloning a tree is cheap.
+#[derive(Clone)]
+pub struct SyntaxTree(tree_sitter::Tree);
+
+#[derive(Clone, Copy)]
+pub struct SyntaxNode<'a>(Node<'a>);
+
+/// Returns the bundled tree-sitter language for the file at the given path, if there is one.
+pub fn language_for_path(path: &Path) -> Option<Language> {
+    match tokenizer::language_name_for_path(path) {
+        Some("Rust") => Some(tree_sitter_rust::language()),
+        Some("JavaScript") => Some(tree_sitter_javascript::language()),
+        _ => None,
+    }
+}
+
+impl Syntax {
+    pub fn new(language: Language) -> Self {
+        let mut parser = Parser::new();
+        parser
+            .set_language(language)
+            .expect("Language was generated by an incompatible version of tree-sitter");
+        Syntax {
+            parser,
+            tree: None,
+            is_stale: true,
+        }
+    }
+
+    pub fn edit(&mut self, edit: &Edit) {
+        if let Some(tree) = self.tree.as_mut() {
+            tree.edit(&InputEdit {
+                start_byte: edit.old_offset_range.start * 2,
+                old_end_byte: edit.old_offset_range.end * 2,
+                new_end_byte: edit.new_offset_range.end * 2,
+                start_position: to_ts_point(edit.old_range.start),
+                old_end_position: to_ts_point(edit.old_range.end),
+                new_end_position: to_ts_point(edit.new_range.end),
+            });
+        }
+        self.is_stale = true;
+    }
+
+    /// Returns the syntax tree for the current text, which `text` is only called to retrieve if
+    /// the text needs to be parsed again.
+    pub fn tree<F: FnOnce() -> Vec<u16>>(&mut self, text: F) -> Option<SyntaxTree> {
+        if self.is_stale {
+            self.tree = self.parser.parse_utf16(text(), self.tree.as_ref());
+            self.is_stale = false;
+        }
+        self.tree.clone().map(SyntaxTree)
+    }
+}
+
+impl fmt::Debug for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Syntax")
+            .field("is_stale", &self.is_stale)
+            .finish()
+    }
+}
+
+impl SyntaxTree {
+    pub fn root_node<'a>(&'a self) -> SyntaxNode<'a> {
+        SyntaxNode(self.0.root_node())
+    }
+
+    /// Returns the smallest named node that contains the given range.
+    pub fn node_for_range<'a>(&'a self, range: Range<Point>) -> Option<SyntaxNode<'a>> {
+        self.0
+            .root_node()
+            .named_descendant_for_point_range(to_ts_point(range.start), to_ts_point(range.end))
+            .map(SyntaxNode)
+    }
+
+    /// Returns the smallest named node that contains the given point.
+    pub fn node_at<'a>(&'a self, point: Point) -> Option<SyntaxNode<'a>> {
+        self.node_for_range(point..point)
+    }
+}
+
+impl<'a> SyntaxNode<'a> {
+    pub fn kind(&self) -> &'static str {
+        self.0.kind()
+    }
+
+    pub fn range(&self) -> Range<Point> {
+        from_ts_point(self.0.start_position())..from_ts_point(self.0.end_position())
+    }
+
+    pub fn parent(&self) -> Option<SyntaxNode<'a>> {
+        self.0.parent().map(SyntaxNode)
+    }
+
+    /// Returns the node's parent, its parent's parent and so on up to the root of the tree.
+    pub fn ancestors(&self) -> Vec<SyntaxNode<'a>> {
+        let mut ancestors = Vec::new();
+        let mut node = self.parent();
+        while let Some(ancestor) = node {
+            node = ancestor.parent();
+            ancestors.push(ancestor);
+        }
+        ancestors
+    }
+
+    pub fn named_children(&self) -> Vec<SyntaxNode<'a>> {
+        (0..self.0.named_child_count())
+            .filter_map(|index| self.0.named_child(index))
+            .map(SyntaxNode)
+            .collect()
+    }
+}
+
+impl<'a> PartialEq for SyntaxNode<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<'a> fmt::Debug for SyntaxNode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:?}", self.kind(), self.range())
+    }
+}
+
+// Tree-sitter measures UTF-16 text in bytes, so its columns are twice as wide as ours.
+fn to_ts_point(point: Point) -> tree_sitter::Point {
+    tree_sitter::Point::new(point.row as usize, point.column as usize * 2)
+}
+
+fn from_ts_point(point: tree_sitter::Point) -> Point {
+    Point::new(point.row as u32, point.column as u32 / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate rand;
+
+    use super::*;
+    use self::rand::{Rng, SeedableRng, StdRng};
+    use buffer::Buffer;
+
+    #[test]
+    fn test_queries() {
+        let mut buffer = Buffer::new(1);
+        buffer.splice(0..0, "fn a() {\n    b(1, c);\n}");
+        buffer.set_language(Some(tree_sitter_rust::language()));
+        let tree = buffer.syntax_tree().unwrap();
+
+        let node = tree.node_at(Point::new(1, 6)).unwrap();
+        assert_eq!(node.kind(), "integer_literal");
+        assert_eq!(node.range(), Point::new(1, 6)..Point::new(1, 7));
+        assert_eq!(
+            node.ancestors()
+                .iter()
+                .map(|node| node.kind())
+                .collect::<Vec<_>>(),
+            vec![
+                "arguments",
+                "call_expression",
+                "expression_statement",
+                "block",
+                "function_item",
+                "source_file",
+            ]
+        );
+
+        let arguments = node.parent().unwrap();
+        assert_eq!(
+            arguments
+                .named_children()
+                .iter()
+                .map(|node| (node.kind(), node.range()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("integer_literal", Point::new(1, 6)..Point::new(1, 7)),
+                ("identifier", Point::new(1, 9)..Point::new(1, 10)),
+            ]
+        );
+
+        let node = tree
+            .node_for_range(Point::new(1, 4)..Point::new(1, 8))
+            .unwrap();
+        assert_eq!(node.kind(), "call_expression");
+        assert_eq!(tree.root_node().range(), Point::new(0, 0)..Point::new(2, 1));
+
+        // Edits are reflected the next time the tree is requested.
+        buffer.splice(13..14, "ä");
+        let tree = buffer.syntax_tree().unwrap();
+        let node = tree.node_at(Point::new(1, 4)).unwrap();
+        assert_eq!(node.kind(), "identifier");
+        assert_eq!(node.range(), Point::new(1, 4)..Point::new(1, 5));
+        assert_eq!(node.parent().unwrap().kind(), "call_expression");
+
+        buffer.set_language(None);
+        assert!(buffer.syntax_tree().is_none());
+    }
+
+    #[test]
+    fn test_random_edits() {
+        for seed in 0..50 {
+            let mut rng = StdRng::from_seed(&[seed]);
+            let mut buffer = Buffer::new(1);
+            buffer.set_language(Some(tree_sitter_rust::language()));
+
+            for _ in 0..10 {
+                for _ in 0..rng.gen_range(1, 4) {
+                    let end = rng.gen_range(0, buffer.len() + 1);
+                    let start = rng.gen_range(0, end + 1);
+                    let text = random_text(&mut rng);
+                    if rng.gen_weighted_bool(5) {
+                        buffer.undo();
+                    } else {
+                        buffer.splice(start..end, text.as_str());
+                    }
+                }
+
+                let tree = buffer.syntax_tree().unwrap();
+                let mut expected = Syntax::new(tree_sitter_rust::language());
+                let expected = expected.tree(|| buffer.to_u16_chars()).unwrap();
+                assert_eq!(
+                    tree.0.root_node().to_sexp(),
+                    expected.0.root_node().to_sexp()
+                );
+            }
+        }
+    }
+
+    fn random_text<T: Rng>(rng: &mut T) -> String {
+        let len = rng.gen_range(0, 6);
+        (0..len)
+            .map(|_| match rng.gen_range(0, 12) {
+                0 => "\n",
+                1 => "fn a() ",
+                2 => "{",
+                3 => "}",
+                4 => "(",
+                5 => ")",
+                6 => "let b = ",
+                7 => "1;",
+                8 => "\"",
+                9 => "ä",
+                _ => " ",
+            })
+            .collect()
+    }
+}
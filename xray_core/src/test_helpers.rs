@@ -0,0 +1,177 @@
+//! Fixtures shared by the crate's tests, such as temporary directories and in-memory trees.
+
+extern crate rand;
+
+use self::rand::Rng;
+use buffer::Buffer;
+use file_finder::FileFinderViewDelegate;
+use fs;
+use futures::{future, Future, Stream};
+use notify_cell::NotifyCell;
+use project_search::ProjectSearchViewDelegate;
+use std::cell::RefCell;
+use std::env;
+use std::fs as std_fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+type BoxedSendableFuture = Box<Future<Item = (), Error = ()> + Send>;
+
+/// Queues spawned futures so that tests can choose when they run.
+#[derive(Clone)]
+pub struct TestExecutor(Rc<RefCell<Vec<BoxedSendableFuture>>>);
+
+/// A tree whose entries are built in memory, regardless of what exists on disk at its path.
+pub struct TestTree {
+    pub path: PathBuf,
+    pub root: fs::Entry,
+    pub updates: Rc<NotifyCell<()>>,
+}
+
+pub struct TestDelegate {
+    pub trees: Vec<Box<fs::Tree>>,
+    pub open_buffers: Vec<Rc<RefCell<Buffer>>>,
+    pub confirmed_paths: Vec<PathBuf>,
+}
+
+/// Creates an empty directory with a unique name in the system's temporary directory.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!(
+        "xray-{}-test-{}",
+        name,
+        rand::thread_rng().gen::<u64>()
+    ));
+    std_fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn write_file<T: AsRef<[u8]>>(path: &Path, contents: T) {
+    std_fs::File::create(path)
+        .unwrap()
+        .write_all(contents.as_ref())
+        .unwrap();
+}
+
+pub fn read_to_string(path: &Path) -> String {
+    let mut contents = String::new();
+    std_fs::File::open(path)
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    contents
+}
+
+impl TestExecutor {
+    pub fn new() -> Self {
+        TestExecutor(Rc::new(RefCell::new(Vec::new())))
+    }
+
+    /// Removes the futures spawned so far without running them.
+    pub fn take(&self) -> Vec<BoxedSendableFuture> {
+        self.0.borrow_mut().drain(..).collect()
+    }
+
+    /// Runs the futures spawned so far to completion.
+    pub fn run(&self) {
+        for future in self.take() {
+            future.wait().unwrap();
+        }
+    }
+}
+
+impl future::Executor<BoxedSendableFuture> for TestExecutor {
+    fn execute(
+        &self,
+        future: BoxedSendableFuture,
+    ) -> Result<(), future::ExecuteError<BoxedSendableFuture>> {
+        self.0.borrow_mut().push(future);
+        Ok(())
+    }
+}
+
+impl TestTree {
+    /// Builds a tree containing files at the given relative paths, along with their parent
+    /// directories.
+    pub fn new<T: Into<PathBuf>>(path: T, file_paths: &[&str]) -> Self {
+        let root = fs::Entry::dir(false, false);
+        for file_path in file_paths {
+            let mut components = Path::new(file_path).iter().peekable();
+            let mut dir = root.clone();
+            while let Some(name) = components.next() {
+                if components.peek().is_some() {
+                    if dir.child(name).is_none() {
+                        dir.insert(name, fs::Entry::dir(false, false)).unwrap();
+                    }
+                    dir = dir.child(name).unwrap();
+                } else {
+                    dir.insert(name, fs::Entry::file(false, false)).unwrap();
+                }
+            }
+        }
+
+        TestTree {
+            path: path.into(),
+            root,
+            updates: Rc::new(NotifyCell::new(())),
+        }
+    }
+}
+
+impl fs::Tree for TestTree {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn root(&self) -> &fs::Entry {
+        &self.root
+    }
+
+    fn updates(&self) -> Box<Stream<Item = (), Error = ()>> {
+        Box::new(self.updates.observe())
+    }
+}
+
+impl TestDelegate {
+    pub fn new(trees: Vec<TestTree>) -> Self {
+        TestDelegate {
+            trees: trees
+                .into_iter()
+                .map(|tree| Box::new(tree) as Box<fs::Tree>)
+                .collect(),
+            open_buffers: Vec::new(),
+            confirmed_paths: Vec::new(),
+        }
+    }
+}
+
+impl FileFinderViewDelegate for TestDelegate {
+    fn trees(&self) -> &Vec<Box<fs::Tree>> {
+        &self.trees
+    }
+
+    fn did_close(&mut self) {}
+
+    fn did_confirm(&mut self, path: PathBuf) {
+        self.confirmed_paths.push(path);
+    }
+}
+
+impl ProjectSearchViewDelegate for TestDelegate {
+    fn trees(&self) -> &Vec<Box<fs::Tree>> {
+        &self.trees
+    }
+
+    fn did_close(&mut self) {}
+
+    fn did_confirm(&mut self, path: PathBuf) {
+        self.confirmed_paths.push(path);
+    }
+
+    fn open_buffer(&self, path: &Path) -> Option<Rc<RefCell<Buffer>>> {
+        self.open_buffers
+            .iter()
+            .find(|buffer| buffer.borrow().path() == Some(path))
+            .cloned()
+    }
+}
@@ -0,0 +1,489 @@
+use std::path::Path;
+use std::sync::Arc;
+
+/// The state of a tokenizer at the end of a line, which is all it needs to resume tokenizing at
+/// the start of the next one. The first line is tokenized starting from state 0. After an edit,
+/// lines only need to be tokenized again until one ends in the same state as it did before.
+pub type LineState = u32;
+
+/// A range of columns on a line, in UTF-16 code units, along with the name of the scope it is
+/// highlighted as, such as `keyword` or `string`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Token {
+    pub start: u32,
+    pub end: u32,
+    pub scope: &'static str,
+}
+
+/// Splits lines of source code into scoped tokens. Tokenizers are invoked on a background thread,
+/// one line at a time, starting from the state the previous line ended in.
+pub trait Tokenizer: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns the scoped tokens on the given line in order and updates `state` to the state at the
+    /// end of the line. Text that isn't covered by a token is left unscoped.
+    fn tokenize_line(&self, line: &[u16], state: &mut LineState) -> Vec<Token>;
+}
+
+/// A tokenizer for languages with C-like comments, strings and identifiers, which is configured
+/// with the keywords of a particular language.
+struct CLikeTokenizer {
+    name: &'static str,
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    constants: &'static [&'static str],
+    variables: &'static [&'static str],
+    definition_keywords: &'static [&'static str],
+    nested_comments: bool,
+    multiline_strings: bool,
+    raw_strings: bool,
+    template_strings: bool,
+    char_literals: bool,
+    macros: bool,
+}
+
+const NORMAL: LineState = 0;
+const BLOCK_COMMENT: LineState = 1;
+const STRING: LineState = 2;
+const RAW_STRING: LineState = 3;
+
+lazy_static! {
+    pub static ref RUST: Arc<Tokenizer> = Arc::new(CLikeTokenizer {
+        name: "Rust",
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+            "mut", "pub", "ref", "return", "static", "struct", "super", "trait", "type",
+            "unsafe", "use", "where", "while",
+        ],
+        types: &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16",
+            "i32", "i64", "i128", "isize", "f32", "f64", "Self",
+        ],
+        constants: &["true", "false"],
+        variables: &["self"],
+        definition_keywords: &["fn"],
+        nested_comments: true,
+        multiline_strings: true,
+        raw_strings: true,
+        template_strings: false,
+        char_literals: true,
+        macros: true,
+    });
+    pub static ref JAVASCRIPT: Arc<Tokenizer> = Arc::new(CLikeTokenizer {
+        name: "JavaScript",
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue",
+            "debugger", "default", "delete", "do", "else", "export", "extends", "finally", "for",
+            "from", "function", "if", "import", "in", "instanceof", "let", "new", "of",
+            "return", "static", "super", "switch", "throw", "try", "typeof", "var", "void",
+            "while", "with", "yield",
+        ],
+        types: &[],
+        constants: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+        variables: &["this"],
+        definition_keywords: &["function", "class"],
+        nested_comments: false,
+        multiline_strings: false,
+        raw_strings: false,
+        template_strings: true,
+        char_literals: false,
+        macros: false,
+    });
+}
+
+/// Returns the name of the language of the file at the given path, judging by its extension. This
+/// is the only place extensions are mapped to languages, so tokenizers and parsers agree.
+pub fn language_name_for_path(path: &Path) -> Option<&'static str> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("rs") => Some("Rust"),
+        Some("js") | Some("jsx") | Some("mjs") => Some("JavaScript"),
+        _ => None,
+    }
+}
+
+/// Returns the bundled tokenizer for the language of the file at the given path, if there is one.
+pub fn tokenizer_for_path(path: &Path) -> Option<Arc<Tokenizer>> {
+    match language_name_for_path(path) {
+        Some("Rust") => Some(RUST.clone()),
+        Some("JavaScript") => Some(JAVASCRIPT.clone()),
+        _ => None,
+    }
+}
+
+impl Tokenizer for CLikeTokenizer {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn tokenize_line(&self, line: &[u16], state: &mut LineState) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut push = |start: usize, end: usize, scope| {
+            if start < end {
+                tokens.push(Token {
+                    start: start as u32,
+                    end: end as u32,
+                    scope,
+                });
+            }
+        };
+
+        // Finish the comment or string that the previous line ended in, if any.
+        let mut i = match *state & 0xff {
+            BLOCK_COMMENT => {
+                let end = self.scan_block_comment(line, 0, state);
+                push(0, end, "comment");
+                end
+            }
+            STRING => {
+                let end = self.scan_string(line, 0, state);
+                push(0, end, "string");
+                end
+            }
+            RAW_STRING => {
+                let end = scan_raw_string(line, 0, state);
+                push(0, end, "string");
+                end
+            }
+            _ => 0,
+        };
+
+        let mut after_definition_keyword = false;
+        while i < line.len() {
+            let c = line[i];
+            let next = line.get(i + 1).cloned();
+            if c == b'/' as u16 && next == Some(b'/' as u16) {
+                push(i, line.len(), "comment");
+                break;
+            } else if c == b'/' as u16 && next == Some(b'*' as u16) {
+                *state = BLOCK_COMMENT | (1 << 8);
+                let end = self.scan_block_comment(line, i + 2, state);
+                push(i, end, "comment");
+                i = end;
+            } else if c == b'"' as u16
+                || (c == b'\'' as u16 && !self.char_literals)
+                || (c == b'`' as u16 && self.template_strings)
+            {
+                *state = STRING | ((c as u32) << 8);
+                let end = self.scan_string(line, i + 1, state);
+                push(i, end, "string");
+                i = end;
+            } else if c == b'\'' as u16 {
+                if let Some(end) = scan_char_literal(line, i) {
+                    push(i, end, "string");
+                    i = end;
+                } else if next.map(is_identifier_start) == Some(true) {
+                    let end = scan_identifier(line, i + 1);
+                    push(i, end, "storage.modifier.lifetime");
+                    i = end;
+                } else {
+                    i += 1;
+                }
+            } else if is_digit(c) {
+                let end = scan_number(line, i);
+                push(i, end, "constant.numeric");
+                i = end;
+            } else if is_identifier_start(c) {
+                let end = scan_identifier(line, i);
+                let word = String::from_utf16_lossy(&line[i..end]);
+                let hash_count = line[end..].iter().take_while(|&&c| c == b'#' as u16).count();
+                if self.raw_strings
+                    && (word == "r" || word == "br")
+                    && line.get(end + hash_count) == Some(&(b'"' as u16))
+                {
+                    *state = RAW_STRING | ((hash_count as u32) << 8);
+                    let string_end = scan_raw_string(line, end + hash_count + 1, state);
+                    push(i, string_end, "string");
+                    i = string_end;
+                    continue;
+                }
+
+                let scope = if self.keywords.contains(&word.as_str()) {
+                    Some("keyword")
+                } else if self.types.contains(&word.as_str()) {
+                    Some("storage.type")
+                } else if self.constants.contains(&word.as_str()) {
+                    Some("constant.language")
+                } else if self.variables.contains(&word.as_str()) {
+                    Some("variable.language")
+                } else if self.macros && line.get(end) == Some(&(b'!' as u16)) {
+                    push(i, end + 1, "entity.name.function.macro");
+                    i = end + 1;
+                    continue;
+                } else if after_definition_keyword
+                    || next_non_whitespace(line, end) == Some(b'(' as u16)
+                {
+                    Some("entity.name.function")
+                } else if line[i] >= b'A' as u16 && line[i] <= b'Z' as u16 {
+                    Some("entity.name.type")
+                } else {
+                    None
+                };
+                if let Some(scope) = scope {
+                    push(i, end, scope);
+                }
+                after_definition_keyword = self.definition_keywords.contains(&word.as_str());
+                i = end;
+            } else {
+                i += 1;
+            }
+        }
+
+        tokens
+    }
+}
+
+impl CLikeTokenizer {
+    /// Returns the end of the block comment that `state` is inside of, or the end of the line if
+    /// the comment continues onto the next line.
+    fn scan_block_comment(&self, line: &[u16], start: usize, state: &mut LineState) -> usize {
+        let mut depth = *state >> 8;
+        let mut i = start;
+        while i < line.len() {
+            let next = line.get(i + 1).cloned();
+            if line[i] == b'*' as u16 && next == Some(b'/' as u16) {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    *state = NORMAL;
+                    return i;
+                }
+            } else if self.nested_comments && line[i] == b'/' as u16 && next == Some(b'*' as u16) {
+                depth += 1;
+                i += 2;
+            } else {
+                i += 1;
+            }
+        }
+        *state = BLOCK_COMMENT | (depth << 8);
+        line.len()
+    }
+
+    /// Returns the end of the string that `state` is inside of, or the end of the line if the
+    /// string continues onto the next line.
+    fn scan_string(&self, line: &[u16], start: usize, state: &mut LineState) -> usize {
+        let quote = (*state >> 8) as u16;
+        let mut i = start;
+        while i < line.len() {
+            if line[i] == b'\\' as u16 {
+                i += 2;
+            } else if line[i] == quote {
+                *state = NORMAL;
+                return i + 1;
+            } else {
+                i += 1;
+            }
+        }
+
+        let escaped_newline = i > line.len();
+        if !(self.multiline_strings || quote == b'`' as u16 || escaped_newline) {
+            *state = NORMAL;
+        }
+        line.len()
+    }
+}
+
+/// Returns the end of the raw string that `state` is inside of, which is closed by a quote
+/// followed by as many hashes as it was opened with.
+fn scan_raw_string(line: &[u16], start: usize, state: &mut LineState) -> usize {
+    let hash_count = (*state >> 8) as usize;
+    for i in start..line.len() {
+        if line[i] == b'"' as u16
+            && line[i + 1..]
+                .iter()
+                .take(hash_count)
+                .filter(|&&c| c == b'#' as u16)
+                .count() == hash_count
+        {
+            *state = NORMAL;
+            return i + 1 + hash_count;
+        }
+    }
+    line.len()
+}
+
+fn scan_char_literal(line: &[u16], start: usize) -> Option<usize> {
+    if line.get(start + 1) == Some(&(b'\\' as u16)) {
+        let mut i = start + 3;
+        while i < line.len() && i < start + 12 {
+            if line[i] == b'\'' as u16 {
+                return Some(i + 1);
+            }
+            i += 1;
+        }
+        None
+    } else {
+        let char_len = match line.get(start + 1) {
+            Some(&c) if (0xd800..0xdc00).contains(&c) => 2,
+            Some(_) => 1,
+            None => return None,
+        };
+        if line.get(start + 1 + char_len) == Some(&(b'\'' as u16)) {
+            Some(start + 2 + char_len)
+        } else {
+            None
+        }
+    }
+}
+
+fn scan_identifier(line: &[u16], start: usize) -> usize {
+    let mut i = start;
+    while i < line.len() && (is_identifier_start(line[i]) || is_digit(line[i])) {
+        i += 1;
+    }
+    i
+}
+
+fn scan_number(line: &[u16], start: usize) -> usize {
+    let mut i = start;
+    while i < line.len() {
+        let c = line[i];
+        let continues_fraction =
+            c == b'.' as u16 && line.get(i + 1).cloned().map(is_digit) == Some(true);
+        if is_identifier_start(c) || is_digit(c) || continues_fraction {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    i
+}
+
+fn next_non_whitespace(line: &[u16], start: usize) -> Option<u16> {
+    line[start..]
+        .iter()
+        .cloned()
+        .find(|&c| c != b' ' as u16 && c != b'\t' as u16)
+}
+
+fn is_identifier_start(c: u16) -> bool {
+    (c >= b'a' as u16 && c <= b'z' as u16)
+        || (c >= b'A' as u16 && c <= b'Z' as u16)
+        || c == b'_' as u16
+        || c == b'$' as u16
+        || c > 0x7f
+}
+
+fn is_digit(c: u16) -> bool {
+    c >= b'0' as u16 && c <= b'9' as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust() {
+        assert_tokens(
+            &RUST,
+            &["fn main() -> u32 {", "    println!(\"{}\", 'a');", "}"],
+            vec![
+                vec![
+                    ("fn", "keyword"),
+                    ("main", "entity.name.function"),
+                    ("u32", "storage.type"),
+                ],
+                vec![
+                    ("println!", "entity.name.function.macro"),
+                    ("\"{}\"", "string"),
+                    ("'a'", "string"),
+                ],
+                vec![],
+            ]
+        );
+
+        assert_tokens(
+            &RUST,
+            &[
+                "impl<'a> Foo<'a> { /* a /* nested */",
+                "comment */ const X: f64 = 1.5e3; }",
+                "let s = r#\"a \"raw\"",
+                "string\"#; // done",
+            ],
+            vec![
+                vec![
+                    ("impl", "keyword"),
+                    ("'a", "storage.modifier.lifetime"),
+                    ("Foo", "entity.name.type"),
+                    ("'a", "storage.modifier.lifetime"),
+                    ("/* a /* nested */", "comment"),
+                ],
+                vec![
+                    ("comment */", "comment"),
+                    ("const", "keyword"),
+                    ("X", "entity.name.type"),
+                    ("f64", "storage.type"),
+                    ("1.5e3", "constant.numeric"),
+                ],
+                vec![("let", "keyword"), ("r#\"a \"raw\"", "string")],
+                vec![("string\"#", "string"), ("// done", "comment")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_javascript() {
+        assert_tokens(
+            &JAVASCRIPT,
+            &[
+                "function foo(a) { return this.bar('x', `y ${a}",
+                "z`) /* c */ }",
+                "const s = \"unterminated",
+                "null",
+            ],
+            vec![
+                vec![
+                    ("function", "keyword"),
+                    ("foo", "entity.name.function"),
+                    ("return", "keyword"),
+                    ("this", "variable.language"),
+                    ("bar", "entity.name.function"),
+                    ("'x'", "string"),
+                    ("`y ${a}", "string"),
+                ],
+                vec![("z`", "string"), ("/* c */", "comment")],
+                vec![("const", "keyword"), ("\"unterminated", "string")],
+                vec![("null", "constant.language")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizer_for_path() {
+        assert_eq!(tokenizer_for_path(Path::new("a/b.rs")).unwrap().name(), "Rust");
+        assert_eq!(tokenizer_for_path(Path::new("a.js")).unwrap().name(), "JavaScript");
+        assert!(tokenizer_for_path(Path::new("a.txt")).is_none());
+        assert_eq!(language_name_for_path(Path::new("a.mjs")), Some("JavaScript"));
+    }
+
+    fn assert_tokens(
+        tokenizer: &Arc<Tokenizer>,
+        lines: &[&str],
+        expected_tokens: Vec<Vec<(&str, &'static str)>>,
+    ) {
+        let mut state = NORMAL;
+        let tokens = lines
+            .iter()
+            .map(|line| {
+                let line = line.encode_utf16().collect::<Vec<_>>();
+                tokenizer
+                    .tokenize_line(&line, &mut state)
+                    .into_iter()
+                    .map(|token| {
+                        let text = &line[token.start as usize..token.end as usize];
+                        (String::from_utf16_lossy(text), token.scope)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let expected_tokens = expected_tokens
+            .into_iter()
+            .map(|line| {
+                line.into_iter()
+                    .map(|(text, scope)| (text.to_string(), scope))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, expected_tokens);
+    }
+}
@@ -6,6 +6,7 @@ use std::rc::{Rc, Weak};
 use futures::{Async, Future, Poll, Stream};
 use futures::task::{self, Task};
 use futures::future;
+use keymap::{self, Keymap, Keystroke};
 
 type BoxedSendableFuture = Box<Future<Item = (), Error = ()> + Send + 'static>;
 type BoxedExecutor = Box<future::Executor<BoxedSendableFuture>>;
@@ -16,7 +17,34 @@ pub trait View: Stream<Item = (), Error = ()> {
     fn component_name(&self) -> &'static str;
     fn will_mount(&mut self, _handle: WindowHandle) {}
     fn render(&self) -> serde_json::Value;
-    fn dispatch_action(&mut self, serde_json::Value) {}
+    /// Returns whether the view handled the action. Unhandled actions bubble up to the view's
+    /// parent.
+    fn dispatch_action(&mut self, serde_json::Value) -> bool {
+        false
+    }
+    fn did_focus(&mut self) {}
+    fn did_blur(&mut self) {}
+    /// The commands the view offers to users by name, such as in the command palette. These are
+    /// queried once when the view is added to its window.
+    fn commands(&self) -> Vec<Command> {
+        Vec::new()
+    }
+}
+
+/// An action that a view offers under a human-readable name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    pub name: &'static str,
+    pub action: serde_json::Value,
+}
+
+/// A command available from the focused view or one of its ancestors, along with the view it
+/// should be dispatched to and the keystrokes bound to it, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AvailableCommand {
+    pub view_id: ViewId,
+    pub command: Command,
+    pub keystrokes: Option<Vec<Keystroke>>,
 }
 
 pub struct Window(Rc<RefCell<Inner>>, Option<ViewHandle>);
@@ -34,10 +62,27 @@ pub struct Inner {
     height: f64,
     update_stream_counter: usize,
     update_stream_task: Option<Task>,
-    executor: Option<BoxedExecutor>
+    executor: Option<BoxedExecutor>,
+    keymap: Rc<Keymap>,
+    pending_keystrokes: Vec<Keystroke>,
+    /// Maps each view to the view whose handle added it, if any.
+    parents: HashMap<ViewId, ViewId>,
+    focused: Option<ViewId>,
+    focus_changed: bool,
+    root_view: Option<ViewId>,
+    /// Describes each view when it is added, so the description is available while the view
+    /// itself is borrowed.
+    descriptions: HashMap<ViewId, ViewDescription>,
 }
 
-pub struct WindowHandle(Weak<RefCell<Inner>>, ViewId);
+struct ViewDescription {
+    component_name: &'static str,
+    commands: Vec<Command>,
+}
+
+/// Allows a view to interact with its window. Views added through a view's handle become
+/// children of that view, whereas the window's own handle adds views without a parent.
+pub struct WindowHandle(Weak<RefCell<Inner>>, Option<ViewId>);
 
 pub struct ViewHandle {
     pub view_id: ViewId,
@@ -48,6 +93,9 @@ pub struct ViewHandle {
 pub struct WindowUpdate {
     updated: Vec<ViewUpdate>,
     removed: Vec<ViewId>,
+    /// The focused view, which is only included when focus has changed since the last update.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    focused: Option<ViewId>,
 }
 
 #[derive(Serialize, Debug)]
@@ -68,15 +116,92 @@ impl Window {
                 height: height,
                 update_stream_counter: 0,
                 update_stream_task: None,
-                executor
+                executor,
+                keymap: Rc::new(Keymap::new()),
+                pending_keystrokes: Vec::new(),
+                parents: HashMap::new(),
+                focused: None,
+                focus_changed: false,
+                root_view: None,
+                descriptions: HashMap::new(),
             })),
             None,
         )
     }
 
     pub fn dispatch_action(&self, view_id: ViewId, action: serde_json::Value) {
-        let view = self.0.borrow().get_view(view_id);
-        view.map(|view| view.borrow_mut().dispatch_action(action));
+        dispatch_action(&self.0, view_id, action);
+    }
+
+    /// Dispatches an action to the focused view, or to the root view if no view is focused.
+    pub fn dispatch_focused_action(&self, action: serde_json::Value) {
+        if let Some(view_id) = self.focused_view_id() {
+            dispatch_action(&self.0, view_id, action);
+        }
+    }
+
+    /// Resolves a keystroke against the keymap and dispatches the resulting action. The keystroke
+    /// is resolved against the focused view and then each of its ancestors, with the given flags
+    /// describing what is focused within the focused view. Keystrokes that begin a longer
+    /// binding are held until a later keystroke completes or breaks the sequence.
+    pub fn dispatch_keystroke(&self, keystroke: Keystroke, flags: Vec<String>) {
+        let view_ids = match self.focused_view_id() {
+            Some(view_id) => self.0.borrow().ancestors(view_id),
+            None => return,
+        };
+
+        let dispatch = {
+            let mut inner = self.0.borrow_mut();
+            let component_names = inner.component_names(&view_ids);
+            let no_flags = Vec::new();
+            let contexts = component_names
+                .iter()
+                .enumerate()
+                .map(|(index, component_name)| keymap::Context {
+                    component_name,
+                    flags: if index == 0 { &flags } else { &no_flags },
+                })
+                .collect::<Vec<_>>();
+
+            inner.pending_keystrokes.push(keystroke.clone());
+            let mut result = inner
+                .keymap
+                .match_keystrokes(&inner.pending_keystrokes, &contexts);
+            if result == keymap::Match::None && inner.pending_keystrokes.len() > 1 {
+                // The keystroke broke a pending sequence, so resolve it on its own instead.
+                inner.pending_keystrokes = vec![keystroke];
+                result = inner
+                    .keymap
+                    .match_keystrokes(&inner.pending_keystrokes, &contexts);
+            }
+
+            match result {
+                keymap::Match::Pending => None,
+                keymap::Match::None => {
+                    inner.pending_keystrokes.clear();
+                    None
+                }
+                keymap::Match::Action {
+                    context_index,
+                    action,
+                } => {
+                    inner.pending_keystrokes.clear();
+                    Some((view_ids[context_index], action))
+                }
+            }
+        };
+
+        if let Some((view_id, action)) = dispatch {
+            dispatch_action(&self.0, view_id, action);
+        }
+    }
+
+    pub fn focus(&self, view_id: ViewId) {
+        focus(&self.0, view_id);
+    }
+
+    fn focused_view_id(&self) -> Option<ViewId> {
+        self.0.borrow().focused_view_id()
     }
 
     pub fn updates(&mut self) -> WindowUpdateStream {
@@ -94,12 +219,25 @@ impl Window {
         inner.height = height;
     }
 
+    pub fn set_keymap(&mut self, keymap: Rc<Keymap>) {
+        let mut inner = self.0.borrow_mut();
+        inner.keymap = keymap;
+        inner.pending_keystrokes.clear();
+    }
+
     pub fn set_root_view(&mut self, root_view: ViewHandle) {
+        self.0.borrow_mut().root_view = Some(root_view.view_id);
         self.1 = Some(root_view);
     }
 
     pub fn handle(&mut self) -> WindowHandle {
-        WindowHandle(Rc::downgrade(&self.0), 0)
+        WindowHandle(Rc::downgrade(&self.0), None)
+    }
+}
+
+impl Command {
+    pub fn new(name: &'static str, action: serde_json::Value) -> Self {
+        Command { name, action }
     }
 }
 
@@ -125,6 +263,11 @@ impl Stream for WindowUpdateStream {
                 window_update = WindowUpdate {
                     updated: Vec::new(),
                     removed: inner.removed.iter().cloned().collect(),
+                    focused: if inner.focus_changed {
+                        inner.focused
+                    } else {
+                        None
+                    },
                 };
 
                 for id in inner.inserted.iter() {
@@ -156,6 +299,7 @@ impl Stream for WindowUpdateStream {
                 window_update = WindowUpdate {
                     updated: Vec::new(),
                     removed: Vec::new(),
+                    focused: inner.focused,
                 };
 
                 for (id, ref view) in inner.views.iter() {
@@ -175,8 +319,11 @@ impl Stream for WindowUpdateStream {
         let mut inner = inner_ref.borrow_mut();
         inner.inserted.clear();
         inner.removed.clear();
+        inner.focus_changed = false;
 
-        if window_update.removed.is_empty() && window_update.updated.is_empty() {
+        if window_update.removed.is_empty() && window_update.updated.is_empty()
+            && window_update.focused.is_none()
+        {
             inner.update_stream_task = Some(task::current());
             Ok(Async::NotReady)
         } else {
@@ -189,6 +336,78 @@ impl Inner {
     fn get_view(&self, id: ViewId) -> Option<Rc<RefCell<View<Item = (), Error = ()>>>> {
         self.views.get(&id).map(|view| view.clone())
     }
+
+    /// Returns the focused view, or the root view if no view is focused.
+    fn focused_view_id(&self) -> Option<ViewId> {
+        self.focused.or(self.root_view)
+    }
+
+    /// Returns the given view followed by each of its ancestors that is still in the window.
+    fn ancestors(&self, view_id: ViewId) -> Vec<ViewId> {
+        let mut ancestors = Vec::new();
+        let mut next_view_id = Some(view_id);
+        while let Some(view_id) = next_view_id {
+            if self.views.contains_key(&view_id) {
+                ancestors.push(view_id);
+            }
+            next_view_id = self.parents.get(&view_id).cloned();
+        }
+        ancestors
+    }
+
+    fn component_names(&self, view_ids: &[ViewId]) -> Vec<&'static str> {
+        view_ids
+            .iter()
+            .map(|view_id| self.descriptions[view_id].component_name)
+            .collect()
+    }
+}
+
+/// Dispatches an action to the given view, bubbling it up through the view's ancestors until one
+/// of them handles it. Ancestors that are already borrowed are skipped, since they are the ones
+/// dispatching the action, such as a parent that forwards an action to its child.
+fn dispatch_action(inner: &Rc<RefCell<Inner>>, view_id: ViewId, action: serde_json::Value) {
+    let view_ids = inner.borrow().ancestors(view_id);
+    for view_id in view_ids {
+        let view = inner.borrow().get_view(view_id);
+        if let Some(view) = view {
+            if let Ok(mut view) = view.try_borrow_mut() {
+                if view.dispatch_action(action.clone()) {
+                    return;
+                }
+            }
+        }
+    }
+    eprintln!("Unhandled action {}", action);
+}
+
+/// Moves focus to the given view. The blur and focus hooks are skipped for views that are
+/// already borrowed, which is the case when a view moves focus while handling an action.
+fn focus(inner: &Rc<RefCell<Inner>>, view_id: ViewId) {
+    let (blurred_view, focused_view) = {
+        let mut inner = inner.borrow_mut();
+        if inner.focused == Some(view_id) || !inner.views.contains_key(&view_id) {
+            return;
+        }
+
+        let blurred_view = inner.focused.and_then(|view_id| inner.get_view(view_id));
+        inner.focused = Some(view_id);
+        inner.focus_changed = true;
+        inner.pending_keystrokes.clear();
+        inner.update_stream_task.take().map(|task| task.notify());
+        (blurred_view, inner.get_view(view_id))
+    };
+
+    if let Some(view) = blurred_view {
+        if let Ok(mut view) = view.try_borrow_mut() {
+            view.did_blur();
+        }
+    }
+    if let Some(view) = focused_view {
+        if let Ok(mut view) = view.try_borrow_mut() {
+            view.did_focus();
+        }
+    }
 }
 
 impl WindowHandle {
@@ -204,7 +423,63 @@ impl WindowHandle {
         inner.height
     }
 
-    pub fn add_view<T: 'static + View>(&self, mut view: T) -> ViewHandle {
+    pub fn dispatch_action(&self, view_id: ViewId, action: serde_json::Value) {
+        dispatch_action(&self.0.upgrade().unwrap(), view_id, action);
+    }
+
+    pub fn focus(&self, view_id: ViewId) {
+        focus(&self.0.upgrade().unwrap(), view_id);
+    }
+
+    /// Returns the commands of the focused view and its ancestors, ordered from the focused view
+    /// outward. When several views offer the same action, only the innermost one is included,
+    /// since it is the view that would handle the action.
+    pub fn available_commands(&self) -> Vec<AvailableCommand> {
+        let inner = self.0.upgrade().unwrap();
+        let inner = inner.borrow();
+        let view_ids = match inner.focused_view_id() {
+            Some(view_id) => inner.ancestors(view_id),
+            None => return Vec::new(),
+        };
+        let component_names = inner.component_names(&view_ids);
+        let contexts = component_names
+            .iter()
+            .map(|component_name| keymap::Context {
+                component_name,
+                flags: &[],
+            })
+            .collect::<Vec<_>>();
+
+        let mut available_commands: Vec<AvailableCommand> = Vec::new();
+        for (context_index, view_id) in view_ids.iter().enumerate() {
+            for command in &inner.descriptions[view_id].commands {
+                if available_commands
+                    .iter()
+                    .any(|available| available.command.action == command.action)
+                {
+                    continue;
+                }
+                available_commands.push(AvailableCommand {
+                    view_id: *view_id,
+                    command: command.clone(),
+                    keystrokes: inner.keymap.keystrokes_for_action(
+                        &command.action,
+                        context_index,
+                        &contexts,
+                    ),
+                });
+            }
+        }
+        available_commands
+    }
+
+    pub fn add_view<T: 'static + View>(&self, view: T) -> ViewHandle {
+        self.add_shared_view(Rc::new(RefCell::new(view)))
+    }
+
+    /// Adds a view that the caller retains a reference to, so that other views can interact with
+    /// it directly rather than only through serialized actions.
+    pub fn add_shared_view<T: 'static + View>(&self, view: Rc<RefCell<T>>) -> ViewHandle {
         let view_id = {
             let inner = self.0.upgrade().unwrap();
             let mut inner = inner.borrow_mut();
@@ -212,11 +487,23 @@ impl WindowHandle {
             inner.next_view_id - 1
         };
 
-        view.will_mount(WindowHandle(self.0.clone(), view_id));
+        view.borrow_mut()
+            .will_mount(WindowHandle(self.0.clone(), Some(view_id)));
+        let description = {
+            let view = view.borrow();
+            ViewDescription {
+                component_name: view.component_name(),
+                commands: view.commands(),
+            }
+        };
 
         let inner = self.0.upgrade().unwrap();
         let mut inner = inner.borrow_mut();
-        inner.views.insert(view_id, Rc::new(RefCell::new(view)));
+        if let Some(parent_id) = self.1 {
+            inner.parents.insert(view_id, parent_id);
+        }
+        inner.descriptions.insert(view_id, description);
+        inner.views.insert(view_id, view);
         inner.inserted.insert(view_id);
         inner.update_stream_task.take().map(|task| task.notify());
         ViewHandle {
@@ -238,6 +525,16 @@ impl Drop for ViewHandle {
             let mut inner = inner.borrow_mut();
             _removed_view = inner.views.remove(&self.view_id);
             inner.removed.insert(self.view_id);
+            if inner.focused == Some(self.view_id) {
+                // Hand focus to the nearest remaining ancestor without invoking its focus hook,
+                // since the ancestor is likely borrowed while it drops this view.
+                let ancestors = inner.ancestors(self.view_id);
+                inner.focused = ancestors.first().cloned();
+                inner.focus_changed = true;
+                inner.pending_keystrokes.clear();
+            }
+            inner.parents.remove(&self.view_id);
+            inner.descriptions.remove(&self.view_id);
             inner.update_stream_task.take().map(|task| task.notify());
         }
     }
@@ -254,9 +551,173 @@ mod tests {
         window.handle().add_view(TestView::new(true));
     }
 
+    #[test]
+    fn test_focus_and_bubbling() {
+        let mut window = Window::new(None, 100.0);
+        let root_view = Rc::new(RefCell::new(TestView::new(true)));
+        let root_view_handle = window.handle().add_shared_view(root_view.clone());
+        let root_view_id = root_view_handle.view_id;
+        window.set_root_view(root_view_handle);
+        let child_view = root_view.borrow().child.clone().unwrap();
+        let child_view_id = root_view.borrow().handle.as_ref().unwrap().view_id;
+
+        // Actions the child doesn't handle bubble up to the root.
+        window.dispatch_action(child_view_id, json!({"type": "ChildA"}));
+        window.dispatch_action(child_view_id, json!({"type": "A"}));
+        assert_eq!(child_view.borrow().actions, vec!["ChildA"]);
+        assert_eq!(root_view.borrow().actions, vec!["A"]);
+
+        // Without a focused view, focused actions go to the root.
+        window.dispatch_focused_action(json!({"type": "B"}));
+        assert_eq!(root_view.borrow().actions, vec!["A", "B"]);
+
+        window.focus(child_view_id);
+        window.focus(child_view_id);
+        window.dispatch_focused_action(json!({"type": "ChildB"}));
+        assert_eq!(child_view.borrow().actions, vec!["ChildA", "ChildB"]);
+        assert_eq!(child_view.borrow().focus_events, vec!["focus"]);
+
+        window.focus(root_view_id);
+        assert_eq!(child_view.borrow().focus_events, vec!["focus", "blur"]);
+        assert_eq!(root_view.borrow().focus_events, vec!["focus"]);
+
+        // Removing the focused view moves focus to its parent.
+        window.focus(child_view_id);
+        root_view.borrow_mut().handle.take();
+        assert_eq!(window.0.borrow().focused, Some(root_view_id));
+        window.dispatch_focused_action(json!({"type": "C"}));
+        assert_eq!(root_view.borrow().actions, vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn test_bubbling_past_dispatching_views() {
+        let mut window = Window::new(None, 100.0);
+        let root_view = Rc::new(RefCell::new(TestView::new(true)));
+        let root_view_handle = window.handle().add_shared_view(root_view.clone());
+        let root_view_id = root_view_handle.view_id;
+        window.set_root_view(root_view_handle);
+        let child_view = root_view.borrow().child.clone().unwrap();
+
+        // The root forwards this to its child, which doesn't handle what it is given. The root is
+        // still handling the original action, so the forwarded one doesn't bubble back up to it.
+        window.dispatch_action(root_view_id, json!({"type": "Forward"}));
+        assert_eq!(root_view.borrow().actions, vec!["Forward"]);
+        assert_eq!(child_view.borrow().actions, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_dispatch_keystroke() {
+        let mut window = Window::new(None, 100.0);
+        let mut keymap = Keymap::new();
+        keymap
+            .add_bindings(&json!({
+                "TestView": {
+                    "ctrl-a": "A",
+                    "ctrl-x ctrl-b": "ChildB"
+                },
+                "TestView.child": {
+                    "ctrl-a": "ChildA"
+                }
+            }))
+            .unwrap();
+        window.set_keymap(Rc::new(keymap));
+
+        let root_view = Rc::new(RefCell::new(TestView::new(true)));
+        let root_view_handle = window.handle().add_shared_view(root_view.clone());
+        let root_view_id = root_view_handle.view_id;
+        window.set_root_view(root_view_handle);
+        let child_view = root_view.borrow().child.clone().unwrap();
+        let child_view_id = root_view.borrow().handle.as_ref().unwrap().view_id;
+
+        window.focus(child_view_id);
+        window.dispatch_keystroke(keystroke("ctrl-a"), vec!["child".to_string()]);
+        window.dispatch_keystroke(keystroke("ctrl-a"), Vec::new());
+        assert_eq!(child_view.borrow().actions, vec!["ChildA"]);
+        assert_eq!(root_view.borrow().actions, vec!["A"]);
+
+        window.dispatch_keystroke(keystroke("ctrl-x"), Vec::new());
+        assert_eq!(child_view.borrow().actions.len(), 1);
+        window.dispatch_keystroke(keystroke("ctrl-b"), Vec::new());
+        assert_eq!(child_view.borrow().actions, vec!["ChildA", "ChildB"]);
+
+        // A keystroke that breaks a pending sequence is resolved on its own.
+        window.dispatch_keystroke(keystroke("ctrl-x"), Vec::new());
+        window.dispatch_keystroke(keystroke("ctrl-a"), Vec::new());
+        assert_eq!(root_view.borrow().actions, vec!["A", "A"]);
+
+        // Changing focus discards pending keystrokes.
+        window.dispatch_keystroke(keystroke("ctrl-x"), Vec::new());
+        window.focus(root_view_id);
+        window.dispatch_keystroke(keystroke("ctrl-b"), Vec::new());
+        assert_eq!(root_view.borrow().actions, vec!["A", "A"]);
+    }
+
+    #[test]
+    fn test_available_commands() {
+        let mut window = Window::new(None, 100.0);
+        let mut keymap = Keymap::new();
+        keymap
+            .add_bindings(&json!({
+                "TestView": {
+                    "ctrl-a": "A",
+                    "ctrl-b": "ChildB"
+                },
+                "TestView.child": {
+                    "ctrl-a": "ChildA"
+                }
+            }))
+            .unwrap();
+        window.set_keymap(Rc::new(keymap));
+
+        let root_view = Rc::new(RefCell::new(TestView::new(true)));
+        let root_view_handle = window.handle().add_shared_view(root_view.clone());
+        let root_view_id = root_view_handle.view_id;
+        window.set_root_view(root_view_handle);
+        let child_view_id = root_view.borrow().handle.as_ref().unwrap().view_id;
+
+        // Without a focused view, the root view's commands are available.
+        assert_eq!(
+            window
+                .handle()
+                .available_commands()
+                .iter()
+                .map(|command| command.command.name)
+                .collect::<Vec<_>>(),
+            vec!["Shared", "A"]
+        );
+
+        // Commands are listed from the focused view outward, and an action offered by several
+        // views is only listed for the innermost one.
+        window.focus(child_view_id);
+        let commands = window.handle().available_commands();
+        assert_eq!(
+            commands
+                .iter()
+                .map(|command| (
+                    command.view_id,
+                    command.command.name,
+                    command.keystrokes.as_ref().map(|keystrokes| keystrokes[0].to_string())
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (child_view_id, "Child B", Some("ctrl-b".to_string())),
+                (child_view_id, "Shared", None),
+                (root_view_id, "A", Some("ctrl-a".to_string())),
+            ]
+        );
+    }
+
+    fn keystroke(source: &str) -> Keystroke {
+        Keystroke::parse(source).unwrap()
+    }
+
     struct TestView {
         add_child: bool,
+        window_handle: Option<WindowHandle>,
         handle: Option<ViewHandle>,
+        child: Option<Rc<RefCell<TestView>>>,
+        actions: Vec<String>,
+        focus_events: Vec<&'static str>,
         updates: NotifyCell<()>,
     }
 
@@ -266,7 +727,11 @@ mod tests {
         fn new(add_child: bool) -> Self {
             TestView {
                 add_child,
+                window_handle: None,
                 handle: None,
+                child: None,
+                actions: Vec::new(),
+                focus_events: Vec::new(),
                 updates: NotifyCell::new(()),
             }
         }
@@ -283,8 +748,48 @@ mod tests {
 
         fn will_mount(&mut self, window_handle: WindowHandle) {
             if self.add_child {
-                self.handle = Some(window_handle.add_view(TestView::new(false)));
+                let child = Rc::new(RefCell::new(TestView::new(false)));
+                self.handle = Some(window_handle.add_shared_view(child.clone()));
+                self.child = Some(child);
+            }
+            self.window_handle = Some(window_handle);
+        }
+
+        // Child views only handle actions prefixed with "Child", whereas root views handle all
+        // actions.
+        fn dispatch_action(&mut self, action: serde_json::Value) -> bool {
+            let action_type = action["type"].as_str().unwrap();
+            if self.add_child || action_type.starts_with("Child") {
+                self.actions.push(action_type.to_string());
+                if action_type == "Forward" {
+                    let child_id = self.handle.as_ref().unwrap().view_id;
+                    self.window_handle
+                        .as_ref()
+                        .unwrap()
+                        .dispatch_action(child_id, json!({"type": "Forwarded"}));
+                }
+                true
+            } else {
+                false
+            }
+        }
+
+        fn did_focus(&mut self) {
+            self.focus_events.push("focus");
+        }
+
+        fn did_blur(&mut self) {
+            self.focus_events.push("blur");
+        }
+
+        fn commands(&self) -> Vec<Command> {
+            let mut commands = vec![Command::new("Shared", json!({"type": "Shared"}))];
+            if self.add_child {
+                commands.push(Command::new("A", json!({"type": "A"})));
+            } else {
+                commands.insert(0, Command::new("Child B", json!({"type": "ChildB"})));
             }
+            commands
         }
     }
 
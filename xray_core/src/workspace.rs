@@ -1,47 +1,118 @@
 use futures::{Poll, Stream};
 use serde_json;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::io;
 use std::rc::{Rc, Weak};
-use std::fs::File;
-use std::io::BufReader;
-use std::io::prelude::*;
-use window::{View, ViewHandle, WindowHandle};
+use window::{Command, View, ViewHandle, WindowHandle};
 use buffer::Buffer;
 use buffer_view::BufferView;
+use clipboard::Clipboard;
+use command_palette::{CommandPaletteView, CommandPaletteViewDelegate};
 use notify_cell::NotifyCell;
 use fs;
 use file_finder::{FileFinderView, FileFinderViewDelegate};
+use find_bar::{FindBarView, FindBarViewDelegate};
+use project_search::{ProjectSearchView, ProjectSearchViewDelegate};
+use pane_group::{Axis, PaneGroup, PaneId};
 
 pub struct WorkspaceView(Rc<RefCell<WorkspaceViewState>>);
 
+/// Hands out a single buffer per file, so that every view of a file edits the same buffer. Buffers
+/// are held weakly, so each one is dropped along with the last view referencing it.
+#[derive(Default)]
+pub struct BufferRegistry {
+    buffers: HashMap<PathBuf, Weak<RefCell<Buffer>>>,
+}
+
 struct WorkspaceViewState {
     roots: Rc<Vec<Box<fs::Tree>>>,
+    clipboard: Clipboard,
+    buffers: BufferRegistry,
     window_handle: Option<WindowHandle>,
     modal_panel: Option<ViewHandle>,
-    center_pane: Option<ViewHandle>,
+    center: PaneGroup<PaneItem>,
+    find_bar: Option<ViewHandle>,
     updates: NotifyCell<()>,
 }
 
+/// An editor shown in one of the workspace's panes. Dropping it removes the editor from the window.
+struct PaneItem {
+    view: ViewHandle,
+    buffer_view: Weak<RefCell<BufferView>>,
+    path: PathBuf,
+}
+
 #[derive(Deserialize)]
 #[serde(tag = "type")]
 enum WorkspaceViewAction {
+    ToggleCommandPalette,
     ToggleFileFinder,
+    ToggleFindBar,
+    ToggleProjectSearch,
+    Save,
+    Split {
+        axis: Axis,
+    },
+    CloseActiveItem,
+    ClosePane,
+    ActivateNextPane,
+    ActivatePreviousPane,
+    ActivateItem {
+        pane_id: PaneId,
+        index: usize,
+    },
+    MoveItem {
+        from_pane_id: PaneId,
+        from_index: usize,
+        to_pane_id: PaneId,
+        to_index: usize,
+    },
 }
 
 impl WorkspaceView {
-    pub fn new(roots: Vec<Box<fs::Tree>>) -> Self {
+    pub fn new(roots: Vec<Box<fs::Tree>>, clipboard: Clipboard) -> Self {
         WorkspaceView(Rc::new(RefCell::new(WorkspaceViewState {
             roots: Rc::new(roots),
+            clipboard,
+            buffers: BufferRegistry::new(),
             modal_panel: None,
-            center_pane: None,
+            center: PaneGroup::new(),
+            find_bar: None,
             window_handle: None,
             updates: NotifyCell::new(()),
         })))
     }
 }
 
+impl BufferRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the open buffer for the file at the given path, loading it if it isn't open. Paths
+    /// are canonicalized so that every path to a file maps to the same buffer.
+    pub fn open(&mut self, path: &Path) -> io::Result<Rc<RefCell<Buffer>>> {
+        let path = path.canonicalize()?;
+        if let Some(buffer) = self.buffers.get(&path).and_then(Weak::upgrade) {
+            return Ok(buffer);
+        }
+
+        let buffer = Rc::new(RefCell::new(Buffer::load(1, &path)?));
+        self.buffers.retain(|_, buffer| buffer.upgrade().is_some());
+        self.buffers.insert(path, Rc::downgrade(&buffer));
+        Ok(buffer)
+    }
+
+    /// Returns the buffer for the file at the given path if it is open, without loading it.
+    pub fn get(&self, path: &Path) -> Option<Rc<RefCell<Buffer>>> {
+        let path = path.canonicalize().ok()?;
+        self.buffers.get(&path).and_then(Weak::upgrade)
+    }
+}
+
 impl View for WorkspaceView {
     fn component_name(&self) -> &'static str {
         "Workspace"
@@ -49,9 +120,18 @@ impl View for WorkspaceView {
 
     fn render(&self) -> serde_json::Value {
         let state = self.0.borrow();
+        let render_item = |item: &PaneItem| {
+            json!({
+                "view_id": item.view.view_id,
+                "title": item.path
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().into_owned()),
+            })
+        };
         json!({
             "modal": state.modal_panel.as_ref().map(|view_handle| view_handle.view_id),
-            "center_pane": state.center_pane.as_ref().map(|view_handle| view_handle.view_id)
+            "center": state.center.render(&render_item),
+            "find_bar": state.find_bar.as_ref().map(|view_handle| view_handle.view_id)
         })
     }
 
@@ -65,43 +145,205 @@ impl View for WorkspaceView {
         let react_js_path =
             src_path.join("xray_electron/node_modules/react/cjs/react.development.js");
 
-        state.center_pane = Some(window_handle.add_view(state.open_path(react_js_path)));
         state.window_handle = Some(window_handle);
+        state.open_path(react_js_path);
     }
 
-    fn dispatch_action(&mut self, action: serde_json::Value) {
+    fn dispatch_action(&mut self, action: serde_json::Value) -> bool {
         let delegate = Rc::downgrade(&self.0);
         let mut state = self.0.borrow_mut();
         match serde_json::from_value(action) {
+            Ok(WorkspaceViewAction::ToggleCommandPalette) => {
+                state.toggle_command_palette(delegate)
+            }
             Ok(WorkspaceViewAction::ToggleFileFinder) => state.toggle_file_finder(delegate),
-            _ => eprintln!("Unrecognized action"),
+            Ok(WorkspaceViewAction::ToggleFindBar) => state.toggle_find_bar(delegate),
+            Ok(WorkspaceViewAction::ToggleProjectSearch) => state.toggle_project_search(delegate),
+            Ok(WorkspaceViewAction::Save) => state.save_active_buffer(),
+            Ok(WorkspaceViewAction::Split { axis }) => state.split(axis),
+            Ok(WorkspaceViewAction::CloseActiveItem) => state.update_center(|center| {
+                center.close_active_item();
+            }),
+            Ok(WorkspaceViewAction::ClosePane) => state.update_center(|center| {
+                let pane_id = center.active_pane_id();
+                center.close_pane(pane_id);
+            }),
+            Ok(WorkspaceViewAction::ActivateNextPane) => {
+                state.update_center(|center| center.activate_next_pane())
+            }
+            Ok(WorkspaceViewAction::ActivatePreviousPane) => {
+                state.update_center(|center| center.activate_previous_pane())
+            }
+            Ok(WorkspaceViewAction::ActivateItem { pane_id, index }) => {
+                state.update_center(|center| {
+                    center.activate_item(pane_id, index);
+                })
+            }
+            Ok(WorkspaceViewAction::MoveItem {
+                from_pane_id,
+                from_index,
+                to_pane_id,
+                to_index,
+            }) => state.update_center(|center| {
+                center.move_item(from_pane_id, from_index, to_pane_id, to_index);
+            }),
+            _ => return false,
         }
+        true
+    }
+
+    fn commands(&self) -> Vec<Command> {
+        vec![
+            Command::new("Toggle File Finder", json!({"type": "ToggleFileFinder"})),
+            Command::new("Toggle Find Bar", json!({"type": "ToggleFindBar"})),
+            Command::new("Toggle Project Search", json!({"type": "ToggleProjectSearch"})),
+            Command::new("Save", json!({"type": "Save"})),
+            Command::new("Split Horizontally", json!({"type": "Split", "axis": "Horizontal"})),
+            Command::new("Split Vertically", json!({"type": "Split", "axis": "Vertical"})),
+            Command::new("Close Active Item", json!({"type": "CloseActiveItem"})),
+            Command::new("Close Pane", json!({"type": "ClosePane"})),
+            Command::new("Activate Next Pane", json!({"type": "ActivateNextPane"})),
+            Command::new("Activate Previous Pane", json!({"type": "ActivatePreviousPane"})),
+        ]
     }
 }
 
 impl WorkspaceViewState {
+    /// Opens the command palette, listing the commands available from the view that was focused
+    /// before the palette took focus.
+    fn toggle_command_palette(&mut self, delegate: Weak<RefCell<WorkspaceViewState>>) {
+        if self.modal_panel.is_some() {
+            self.modal_panel = None;
+            self.focus_active_item();
+        } else {
+            let window_handle = self.window_handle.as_ref().unwrap();
+            let commands = window_handle.available_commands();
+            let modal_panel = window_handle.add_view(CommandPaletteView::new(delegate, commands));
+            window_handle.focus(modal_panel.view_id);
+            self.modal_panel = Some(modal_panel);
+        }
+        self.updates.set(());
+    }
+
     fn toggle_file_finder(&mut self, delegate: Weak<RefCell<WorkspaceViewState>>) {
-        let ref mut window_handle = self.window_handle.as_mut().unwrap();
         if self.modal_panel.is_some() {
             self.modal_panel = None;
+            self.focus_active_item();
         } else {
-            self.modal_panel = Some(window_handle.add_view(FileFinderView::new(delegate)));
+            let window_handle = self.window_handle.as_ref().unwrap();
+            let modal_panel = window_handle.add_view(FileFinderView::new(delegate));
+            window_handle.focus(modal_panel.view_id);
+            self.modal_panel = Some(modal_panel);
+        }
+        self.updates.set(());
+    }
+
+    fn toggle_find_bar(&mut self, delegate: Weak<RefCell<WorkspaceViewState>>) {
+        if self.find_bar.is_some() {
+            self.find_bar = None;
+            self.focus_active_item();
+        } else if let Some(item) = self.center.active_item() {
+            let window_handle = self.window_handle.as_ref().unwrap();
+            let find_bar = FindBarView::new(delegate, item.buffer_view.clone());
+            let find_bar = window_handle.add_view(find_bar);
+            window_handle.focus(find_bar.view_id);
+            self.find_bar = Some(find_bar);
         }
         self.updates.set(());
     }
 
-    fn open_path(&self, path: PathBuf) -> BufferView {
-        let file = File::open(path).unwrap();
-        let mut buf_reader = BufReader::new(file);
-        let mut contents = String::new();
-        buf_reader.read_to_string(&mut contents).unwrap();
+    fn toggle_project_search(&mut self, delegate: Weak<RefCell<WorkspaceViewState>>) {
+        if self.modal_panel.is_some() {
+            self.modal_panel = None;
+            self.focus_active_item();
+        } else {
+            let window_handle = self.window_handle.as_ref().unwrap();
+            let modal_panel = window_handle.add_view(ProjectSearchView::new(delegate));
+            window_handle.focus(modal_panel.view_id);
+            self.modal_panel = Some(modal_panel);
+        }
+        self.updates.set(());
+    }
+
+    fn save_active_buffer(&self) {
+        if let Some(item) = self.center.active_item() {
+            let window_handle = self.window_handle.as_ref().unwrap();
+            window_handle.dispatch_action(item.view.view_id, json!({"type": "Save"}));
+        }
+    }
+
+    /// Activates the editor for the given path in the active pane, opening one if there isn't
+    /// one already.
+    fn open_path(&mut self, path: PathBuf) {
+        let result = path.canonicalize().and_then(|path| {
+            if self.center.activate_item_where(|item| item.path == path) {
+                Ok(())
+            } else {
+                self.build_pane_item(path)
+                    .map(|item| self.center.add_item(item))
+            }
+        });
+        match result {
+            Ok(()) => {
+                self.find_bar = None;
+                self.focus_active_item();
+                self.updates.set(());
+            }
+            Err(error) => eprintln!("Error opening path: {}", error),
+        }
+    }
+
+    /// Splits the active pane, opening another editor for the active item in the new pane.
+    fn split(&mut self, axis: Axis) {
+        let path = self.center.active_item().map(|item| item.path.clone());
+        let item = match path.map(|path| self.build_pane_item(path)) {
+            Some(Ok(item)) => Some(item),
+            Some(Err(error)) => {
+                eprintln!("Error splitting pane: {}", error);
+                return;
+            }
+            None => None,
+        };
+        self.update_center(|center| {
+            center.split(axis);
+            if let Some(item) = item {
+                center.add_item(item);
+            }
+        });
+    }
+
+    /// Applies the given change to the panes, closing the find bar if the change activated a
+    /// different editor.
+    fn update_center<F: FnOnce(&mut PaneGroup<PaneItem>)>(&mut self, f: F) {
+        let active_view_id = self.center.active_item().map(|item| item.view.view_id);
+        f(&mut self.center);
+        if self.center.active_item().map(|item| item.view.view_id) != active_view_id {
+            self.find_bar = None;
+        }
+        self.focus_active_item();
+        self.updates.set(());
+    }
 
-        let mut buffer = Buffer::new(1);
-        buffer.splice(0..0, contents.as_str());
+    fn focus_active_item(&self) {
+        if let Some(item) = self.center.active_item() {
+            let window_handle = self.window_handle.as_ref().unwrap();
+            window_handle.focus(item.view.view_id);
+        }
+    }
 
-        let mut buffer_view = BufferView::new(Rc::new(RefCell::new(buffer)));
-        buffer_view.set_line_height(20.0);
+    fn build_pane_item(&mut self, path: PathBuf) -> io::Result<PaneItem> {
+        let buffer = self.buffers.open(&path)?;
+        let mut buffer_view = BufferView::new(buffer);
         buffer_view
+            .set_line_height(20.0)
+            .set_clipboard(self.clipboard.clone());
+        let buffer_view = Rc::new(RefCell::new(buffer_view));
+        let window_handle = self.window_handle.as_ref().unwrap();
+        Ok(PaneItem {
+            buffer_view: Rc::downgrade(&buffer_view),
+            view: window_handle.add_shared_view(buffer_view),
+            path,
+        })
     }
 }
 
@@ -112,14 +354,53 @@ impl FileFinderViewDelegate for WorkspaceViewState {
 
     fn did_close(&mut self) {
         self.modal_panel = None;
+        self.focus_active_item();
+        self.updates.set(());
+    }
+
+    fn did_confirm(&mut self, path: PathBuf) {
+        self.open_path(path);
+        self.modal_panel = None;
+        self.updates.set(());
+    }
+}
+
+impl CommandPaletteViewDelegate for WorkspaceViewState {
+    fn did_close(&mut self) {
+        self.modal_panel = None;
+        self.focus_active_item();
+        self.updates.set(());
+    }
+}
+
+impl ProjectSearchViewDelegate for WorkspaceViewState {
+    fn trees(&self) -> &Vec<Box<fs::Tree>> {
+        &self.roots
+    }
+
+    fn did_close(&mut self) {
+        self.modal_panel = None;
+        self.focus_active_item();
         self.updates.set(());
     }
 
     fn did_confirm(&mut self, path: PathBuf) {
-        self.center_pane = Some(self.window_handle.as_ref().unwrap().add_view(self.open_path(path)));
+        self.open_path(path);
         self.modal_panel = None;
         self.updates.set(());
     }
+
+    fn open_buffer(&self, path: &Path) -> Option<Rc<RefCell<Buffer>>> {
+        self.buffers.get(path)
+    }
+}
+
+impl FindBarViewDelegate for WorkspaceViewState {
+    fn did_close(&mut self) {
+        self.find_bar = None;
+        self.focus_active_item();
+        self.updates.set(());
+    }
 }
 
 impl Stream for WorkspaceView {
@@ -131,3 +412,43 @@ impl Stream for WorkspaceView {
         state.updates.poll()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs as std_fs;
+    use test_helpers::{temp_dir, write_file};
+
+    #[test]
+    fn test_buffer_registry() {
+        let dir = temp_dir("workspace");
+        std_fs::create_dir(dir.join("sub")).unwrap();
+        write_file(&dir.join("a"), "abc");
+        write_file(&dir.join("b"), "def");
+
+        let mut registry = BufferRegistry::new();
+        let buffer_a = registry.open(&dir.join("a")).unwrap();
+        buffer_a.borrow_mut().splice(3..3, "!");
+
+        // Every path to an open file resolves to the same buffer, including its unsaved edits.
+        let buffer_a_2 = registry.open(&dir.join("sub").join("..").join("a")).unwrap();
+        assert!(Rc::ptr_eq(&buffer_a, &buffer_a_2));
+        assert_eq!(buffer_a_2.borrow().to_string(), "abc!");
+        let buffer_b = registry.open(&dir.join("b")).unwrap();
+        assert!(!Rc::ptr_eq(&buffer_a, &buffer_b));
+        let buffer_a_3 = registry.get(&dir.join("sub/../a")).unwrap();
+        assert!(Rc::ptr_eq(&buffer_a, &buffer_a_3));
+        drop(buffer_a_3);
+
+        // Buffers are loaded again once every reference to them has been dropped.
+        drop(buffer_a);
+        assert_eq!(registry.open(&dir.join("a")).unwrap().borrow().to_string(), "abc!");
+        drop(buffer_a_2);
+        assert!(registry.get(&dir.join("a")).is_none());
+        assert_eq!(registry.open(&dir.join("a")).unwrap().borrow().to_string(), "abc");
+
+        assert!(registry.open(&dir.join("c")).is_err());
+
+        std_fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-const {app, BrowserWindow} = require('electron');
+const {app, BrowserWindow, clipboard} = require('electron');
 const {spawn} = require('child_process');
 const path = require('path');
 const url = require('url');
@@ -57,6 +57,10 @@ class XrayApplication {
         this._createWindow(message.window_id);
         break;
       }
+      case 'SetClipboard': {
+        clipboard.writeText(message.text);
+        break;
+      }
     }
   }
 
@@ -0,0 +1,73 @@
+const React = require("react");
+const { styled } = require("styletron-react");
+const highlightMatches = require("./highlight_matches");
+const $ = React.createElement;
+
+const Root = styled("div", {
+  boxShadow: "0 0 8px black",
+  backgroundColor: "white",
+  width: 500 + "px",
+  padding: "10px"
+});
+
+const QueryInput = styled("input", {
+  width: "100%",
+  boxSizing: "border-box"
+});
+
+const CommandList = styled("ol", {
+  listStyleType: "none",
+  maxHeight: "300px",
+  overflow: "auto",
+  padding: 0
+});
+
+const CommandListItem = styled("li", {
+  display: "flex",
+  justifyContent: "space-between",
+  marginTop: "10px"
+});
+
+const SelectedCommandListItem = styled(CommandListItem, {
+  backgroundColor: "blue"
+});
+
+const Keystrokes = styled("kbd", {
+  marginLeft: "10px",
+  color: "gray"
+});
+
+module.exports = class CommandPalette extends React.Component {
+  constructor() {
+    super();
+    this.didChangeQuery = this.didChangeQuery.bind(this);
+  }
+
+  render() {
+    return $(Root, { onFocus: this.props.dispatchFocus },
+      $(QueryInput, {
+        value: this.props.query,
+        onChange: this.didChangeQuery,
+        onKeyDown: this.props.dispatchKeyDown,
+      }),
+      $(CommandList, {}, ...this.props.results.map((result, i) =>
+        this.renderCommand(result, i === this.props.selected_index)
+      ))
+    );
+  }
+
+  renderCommand(result, isSelected) {
+    const item = isSelected ? SelectedCommandListItem : CommandListItem;
+    return $(item, null,
+      $("span", null, ...highlightMatches(result.name, result.match_indices)),
+      result.keystrokes ? $(Keystrokes, null, result.keystrokes) : null
+    );
+  }
+
+  didChangeQuery(event) {
+    this.props.dispatch({
+      type: "UpdateQuery",
+      query: event.target.value
+    });
+  }
+};
@@ -0,0 +1,90 @@
+const React = require("react");
+const { styled } = require("styletron-react");
+const $ = React.createElement;
+
+const Root = styled("div", {
+  position: "absolute",
+  right: 0,
+  bottom: 0,
+  left: 0,
+  display: "flex",
+  alignItems: "center",
+  backgroundColor: "white",
+  borderTop: "1px solid #ccc",
+  padding: "5px 10px"
+});
+
+const QueryInput = styled("input", {
+  flex: 1,
+  boxSizing: "border-box"
+});
+
+const ReplacementInput = styled(QueryInput, {
+  marginLeft: "5px"
+});
+
+const Option = styled("button", {
+  marginLeft: "5px"
+});
+
+const ActiveOption = styled(Option, {
+  fontWeight: "bold"
+});
+
+const Status = styled("span", {
+  marginLeft: "10px",
+  whiteSpace: "nowrap"
+});
+
+module.exports = class FindBar extends React.Component {
+  constructor() {
+    super();
+    this.didChangeQuery = this.didChangeQuery.bind(this);
+    this.didChangeReplacement = this.didChangeReplacement.bind(this);
+  }
+
+  render() {
+    const status = this.props.error
+      ? this.props.error
+      : `${this.props.match_count} matches`;
+
+    return $(Root, { onFocus: this.props.dispatchFocus },
+      $(QueryInput, {
+        value: this.props.query,
+        onChange: this.didChangeQuery,
+        onKeyDown: this.props.dispatchKeyDown,
+      }),
+      $(ReplacementInput, {
+        value: this.props.replacement,
+        placeholder: "Replace",
+        onChange: this.didChangeReplacement,
+        onKeyDown: event => this.props.dispatchKeyDown(event, ['replacement']),
+      }),
+      this.renderOption(".*", this.props.is_regex, "ToggleRegex"),
+      this.renderOption("Aa", this.props.case_sensitive, "ToggleCaseSensitive"),
+      this.renderOption("\\b", this.props.whole_word, "ToggleWholeWord"),
+      $(Status, null, status)
+    );
+  }
+
+  renderOption(label, isActive, actionType) {
+    const option = isActive ? ActiveOption : Option;
+    return $(option, {
+      onClick: () => this.props.dispatch({type: actionType})
+    }, label);
+  }
+
+  didChangeQuery(event) {
+    this.props.dispatch({
+      type: "UpdateQuery",
+      query: event.target.value
+    });
+  }
+
+  didChangeReplacement(event) {
+    this.props.dispatch({
+      type: "UpdateReplacement",
+      replacement: event.target.value
+    });
+  }
+};
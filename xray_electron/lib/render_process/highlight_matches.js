@@ -0,0 +1,25 @@
+const React = require("react");
+const $ = React.createElement;
+
+// Splits the string into plain text and bold elements for the characters at the given indices,
+// which are the characters a fuzzy search matched against the query.
+module.exports = function highlightMatches(string, matchIndices) {
+  let stringIndex = 0;
+  let queryIndex = 0;
+  const children = [];
+  while (true) {
+    if (stringIndex === matchIndices[queryIndex]) {
+      children.push($("b", null, string[stringIndex]));
+      stringIndex++;
+      queryIndex++;
+    } else if (queryIndex < matchIndices.length) {
+      const nextStringIndex = matchIndices[queryIndex];
+      children.push(string.slice(stringIndex, nextStringIndex));
+      stringIndex = nextStringIndex;
+    } else {
+      children.push(string.slice(stringIndex));
+      break;
+    }
+  }
+  return children;
+};
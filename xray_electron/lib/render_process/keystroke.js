@@ -0,0 +1,63 @@
+const KEY_NAMES_BY_CODE = {
+  ArrowUp: "up",
+  ArrowDown: "down",
+  ArrowLeft: "left",
+  ArrowRight: "right",
+  PageUp: "pageup",
+  PageDown: "pagedown",
+  Home: "home",
+  End: "end",
+  Enter: "enter",
+  NumpadEnter: "enter",
+  Escape: "escape",
+  Backspace: "backspace",
+  Delete: "delete",
+  Tab: "tab",
+  Space: "space",
+  BracketLeft: "[",
+  BracketRight: "]",
+  Backslash: "\\",
+  Slash: "/",
+  Comma: ",",
+  Period: ".",
+  Semicolon: ";",
+  Quote: "'",
+  Backquote: "`",
+  Minus: "-",
+  Equal: "="
+};
+
+const MODIFIER_KEYS = new Set(["Shift", "Control", "Alt", "Meta"]);
+
+// Returns whether the event produces text rather than a keystroke to resolve against the keymap.
+function isTextInput(event) {
+  return (
+    event.key.length === 1 && !event.ctrlKey && !event.altKey && !event.metaKey
+  );
+}
+
+// Describes the event in the form the server's keymap expects, such as `cmd-shift-z`. Keys are
+// named by their physical position so that modifiers like alt don't change the key's name.
+function keystrokeForEvent(event) {
+  if (MODIFIER_KEYS.has(event.key)) return null;
+
+  let key = KEY_NAMES_BY_CODE[event.code];
+  if (!key) {
+    if (event.code.startsWith("Key")) {
+      key = event.code.slice(3).toLowerCase();
+    } else if (event.code.startsWith("Digit")) {
+      key = event.code.slice(5);
+    } else {
+      key = event.key.toLowerCase();
+    }
+  }
+
+  let keystroke = "";
+  if (event.ctrlKey) keystroke += "ctrl-";
+  if (event.altKey) keystroke += "alt-";
+  if (event.shiftKey) keystroke += "shift-";
+  if (event.metaKey) keystroke += "cmd-";
+  return keystroke + key;
+}
+
+module.exports = { isTextInput, keystrokeForEvent };
@@ -1,7 +1,10 @@
 process.env.NODE_ENV = "production";
 
 const App = require("./app");
+const CommandPalette = require("./command_palette");
 const FileFinder = require("./file_finder");
+const FindBar = require("./find_bar");
+const ProjectSearch = require("./project_search");
 const QueryString = require("querystring");
 const React = require("react");
 const ReactDOM = require("react-dom");
@@ -49,10 +52,21 @@ function buildViewRegistry(client) {
     onAction: action => {
       action.type = "Action";
       client.sendMessage(action);
+    },
+    onKeyDown: keyDown => {
+      keyDown.type = "KeyDown";
+      client.sendMessage(keyDown);
+    },
+    onFocus: focus => {
+      focus.type = "Focus";
+      client.sendMessage(focus);
     }
   });
   viewRegistry.addComponent("Workspace", Workspace);
+  viewRegistry.addComponent("CommandPalette", CommandPalette);
   viewRegistry.addComponent("FileFinder", FileFinder);
+  viewRegistry.addComponent("FindBar", FindBar);
+  viewRegistry.addComponent("ProjectSearch", ProjectSearch);
   viewRegistry.addComponent("BufferView", TextEditorView);
   return viewRegistry;
 }
@@ -0,0 +1,137 @@
+const React = require("react");
+const { styled } = require("styletron-react");
+const View = require("./view");
+const $ = React.createElement;
+
+const TAB_DATA_TYPE = "xray/tab";
+
+const SplitContainer = styled("div", ({ $axis }) => ({
+  display: "flex",
+  flexDirection: $axis === "Horizontal" ? "row" : "column",
+  flex: 1,
+  minWidth: 0,
+  minHeight: 0
+}));
+
+const PaneContainer = styled("div", ({ $isActive }) => ({
+  display: "flex",
+  flexDirection: "column",
+  flex: 1,
+  minWidth: 0,
+  minHeight: 0,
+  outline: $isActive ? "1px solid #9ab" : "1px solid #ddd"
+}));
+
+const TabBar = styled("div", {
+  display: "flex",
+  flexShrink: 0,
+  backgroundColor: "#eee",
+  borderBottom: "1px solid #ccc",
+  overflow: "hidden"
+});
+
+const Tab = styled("div", ({ $isActive }) => ({
+  padding: "4px 10px",
+  whiteSpace: "nowrap",
+  cursor: "default",
+  borderRight: "1px solid #ccc",
+  backgroundColor: $isActive ? "white" : "transparent"
+}));
+
+const PaneItem = styled("div", {
+  position: "relative",
+  flex: 1,
+  minHeight: 0
+});
+
+function PaneGroup({ node, dispatch }) {
+  if (node.type === "Split") {
+    return $(
+      SplitContainer,
+      { $axis: node.axis },
+      ...node.children.map((child, index) =>
+        $(PaneGroup, { key: index, node: child, dispatch })
+      )
+    );
+  } else {
+    return $(Pane, { pane: node, dispatch });
+  }
+}
+
+class Pane extends React.Component {
+  render() {
+    const { pane } = this.props;
+    const activeItem =
+      pane.active_item_index == null ? null : pane.items[pane.active_item_index];
+
+    return $(
+      PaneContainer,
+      {
+        $isActive: pane.is_active,
+        onMouseDown: () => {
+          if (!pane.is_active && pane.active_item_index != null) {
+            this.activateItem(pane.active_item_index);
+          }
+        }
+      },
+      $(
+        TabBar,
+        {
+          onDragOver: event => event.preventDefault(),
+          onDrop: event => this.didDrop(event, pane.items.length)
+        },
+        ...pane.items.map((item, index) =>
+          $(
+            Tab,
+            {
+              key: item.view_id,
+              $isActive: index === pane.active_item_index,
+              draggable: true,
+              onMouseDown: event => {
+                event.stopPropagation();
+                this.activateItem(index);
+              },
+              onDragStart: event => this.didDragStart(event, index),
+              onDragOver: event => event.preventDefault(),
+              onDrop: event => this.didDrop(event, index)
+            },
+            item.title || "untitled"
+          )
+        )
+      ),
+      $(PaneItem, null, activeItem ? $(View, { id: activeItem.view_id }) : null)
+    );
+  }
+
+  activateItem(index) {
+    this.props.dispatch({
+      type: "ActivateItem",
+      pane_id: this.props.pane.id,
+      index
+    });
+  }
+
+  didDragStart(event, index) {
+    event.dataTransfer.setData(
+      TAB_DATA_TYPE,
+      JSON.stringify({ pane_id: this.props.pane.id, index })
+    );
+  }
+
+  didDrop(event, index) {
+    const data = event.dataTransfer.getData(TAB_DATA_TYPE);
+    if (!data) return;
+    event.preventDefault();
+    event.stopPropagation();
+    const { pane_id, index: from_index } = JSON.parse(data);
+    this.props.dispatch({
+      type: "MoveItem",
+      from_pane_id: pane_id,
+      from_index,
+      to_pane_id: this.props.pane.id,
+      to_index: index
+    });
+  }
+}
+
+module.exports = PaneGroup;
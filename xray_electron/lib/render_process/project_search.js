@@ -0,0 +1,154 @@
+const React = require("react");
+const { styled } = require("styletron-react");
+const $ = React.createElement;
+
+const Root = styled("div", {
+  boxShadow: "0 0 8px black",
+  backgroundColor: "white",
+  width: 700 + "px",
+  padding: "10px"
+});
+
+const QueryRow = styled("div", {
+  display: "flex",
+  alignItems: "center"
+});
+
+const QueryInput = styled("input", {
+  flex: 1,
+  boxSizing: "border-box"
+});
+
+const ReplacementInput = styled(QueryInput, {
+  marginLeft: "5px"
+});
+
+const Option = styled("button", {
+  marginLeft: "5px"
+});
+
+const ActiveOption = styled(Option, {
+  fontWeight: "bold"
+});
+
+const Status = styled("div", {
+  marginTop: "5px"
+});
+
+const MatchList = styled("ol", {
+  listStyleType: "none",
+  maxHeight: "300px",
+  overflow: "auto",
+  padding: 0
+});
+
+const MatchListItem = styled("li", {
+  marginTop: "10px",
+  whiteSpace: "pre"
+});
+
+const SelectedMatchListItem = styled(MatchListItem, {
+  backgroundColor: "blue"
+});
+
+const ReplacementList = styled("ul", {
+  listStyleType: "none",
+  padding: 0
+});
+
+const Location = styled("span", {
+  marginRight: "10px",
+  color: "gray"
+});
+
+module.exports = class ProjectSearch extends React.Component {
+  constructor() {
+    super();
+    this.didChangeQuery = this.didChangeQuery.bind(this);
+    this.didChangeReplacement = this.didChangeReplacement.bind(this);
+  }
+
+  render() {
+    return $(Root, { onFocus: this.props.dispatchFocus },
+      $(QueryRow, null,
+        $(QueryInput, {
+          value: this.props.query,
+          onChange: this.didChangeQuery,
+          onKeyDown: this.props.dispatchKeyDown,
+        }),
+        this.renderOption(".*", this.props.is_regex, "ToggleRegex"),
+        this.renderOption("Aa", this.props.case_sensitive, "ToggleCaseSensitive"),
+        this.renderOption("\\b", this.props.whole_word, "ToggleWholeWord"),
+        this.renderOption("Ignored", this.props.include_ignored, "ToggleIncludeIgnored")
+      ),
+      $(QueryRow, null,
+        $(ReplacementInput, {
+          value: this.props.replacement,
+          placeholder: "Replace",
+          onChange: this.didChangeReplacement,
+          onKeyDown: event => this.props.dispatchKeyDown(event, ['replacement']),
+        }),
+        $(Option, {
+          onClick: () => this.props.dispatch({type: "ReplaceAll"})
+        }, "Replace All")
+      ),
+      $(Status, null, this.props.error || `${this.props.matches.length} matches`),
+      $(ReplacementList, {}, ...this.props.replacements.map(replacement =>
+        this.renderReplacement(replacement)
+      )),
+      $(MatchList, {}, ...this.props.matches.map((match, i) =>
+        this.renderMatch(match, i === this.props.selected_index)
+      ))
+    );
+  }
+
+  renderOption(label, isActive, actionType) {
+    const option = isActive ? ActiveOption : Option;
+    return $(option, {
+      onClick: () => this.props.dispatch({type: actionType})
+    }, label);
+  }
+
+  renderReplacement(replacement) {
+    const summary = replacement.error
+      ? replacement.error
+      : `${replacement.replacement_count} replacements`;
+    return $("li", null, `${replacement.path}: ${summary}`);
+  }
+
+  renderMatch(match, isSelected) {
+    const item = isSelected ? SelectedMatchListItem : MatchListItem;
+    return $(item, null,
+      $(Location, null, `${match.path}:${match.row + 1}`),
+      ...highlightRanges(match.line, match.column_ranges)
+    );
+  }
+
+  didChangeQuery(event) {
+    this.props.dispatch({
+      type: "UpdateQuery",
+      query: event.target.value
+    });
+  }
+
+  didChangeReplacement(event) {
+    this.props.dispatch({
+      type: "UpdateReplacement",
+      replacement: event.target.value
+    });
+  }
+};
+
+// Splits the line into plain text and bold elements for the given ranges, whose UTF-16 offsets
+// index into JavaScript strings directly.
+function highlightRanges(line, ranges) {
+  const children = [];
+  let index = 0;
+  for (const range of ranges) {
+    if (index < range.start) children.push(line.slice(index, range.start));
+    children.push($("b", null, line.slice(range.start, range.end)));
+    index = range.end;
+  }
+  if (index < line.length) children.push(line.slice(index));
+  return children;
+}
@@ -0,0 +1,50 @@
+const React = require("react");
+const { styled } = require("styletron-react");
+const $ = React.createElement;
+
+const Root = styled("div", {
+  position: "absolute",
+  top: 0,
+  right: 0,
+  display: "flex",
+  alignItems: "center",
+  backgroundColor: "white",
+  padding: "2px 8px"
+});
+
+const DirtyIndicator = styled("span", {
+  color: "gray"
+});
+
+const ConflictPrompt = styled("span", {
+  marginLeft: "10px",
+  color: "#b00"
+});
+
+const OverwriteButton = styled("button", {
+  marginLeft: "5px"
+});
+
+// Shows whether the buffer has unsaved changes and, if saving failed because the file changed on
+// disk, offers to overwrite it.
+module.exports = class SaveStatus extends React.Component {
+  render() {
+    if (!this.props.dirty && !this.props.saveConflict) return null;
+
+    let conflictPrompt;
+    if (this.props.saveConflict) {
+      conflictPrompt = $(ConflictPrompt, null,
+        "The file has changed on disk.",
+        $(OverwriteButton, {
+          onClick: () => this.props.dispatch({type: "ForceSave"})
+        }, "Overwrite")
+      );
+    }
+
+    // Keep clicks from reaching the editor, which would place a cursor beneath the status.
+    return $(Root, { onMouseDown: event => event.stopPropagation() },
+      this.props.dirty ? $(DirtyIndicator, null, "Modified") : null,
+      conflictPrompt
+    );
+  }
+};
@@ -3,13 +3,16 @@ const ReactDOM = require("react-dom");
 const PropTypes = require("prop-types");
 const { styled } = require("styletron-react");
 const TextPlane = require("./text_plane");
+const SaveStatus = require("./save_status");
 const debounce = require('../debounce');
+const { isTextInput } = require('../keystroke');
 const $ = React.createElement;
 
 const CURSOR_BLINK_RESUME_DELAY = 300;
 const CURSOR_BLINK_PERIOD = 800;
 
 const Root = styled("div", {
+  position: "relative",
   width: "100%",
   height: "100%",
   overflow: "hidden"
@@ -19,6 +22,9 @@ class TextEditor extends React.Component {
   constructor(props) {
     super(props);
     this.handleMouseWheel = this.handleMouseWheel.bind(this);
+    this.handleMouseDown = this.handleMouseDown.bind(this);
+    this.handleMouseMove = this.handleMouseMove.bind(this);
+    this.handleMouseUp = this.handleMouseUp.bind(this);
     this.handleKeyDown = this.handleKeyDown.bind(this);
     this.debouncedStartCursorBlinking = debounce(
       this.startCursorBlinking.bind(this),
@@ -46,6 +52,14 @@ class TextEditor extends React.Component {
 
     element.addEventListener('wheel', this.handleMouseWheel, {passive: true});
 
+    const {fontFamily, fontSize} = this.context.theme.editor;
+    const measurementContext = document.createElement('canvas').getContext('2d');
+    measurementContext.font = `${fontSize}px ${fontFamily}`;
+    this.props.dispatch({
+      type: 'SetCharWidth',
+      char_width: measurementContext.measureText('x').width
+    });
+
     this.startCursorBlinking();
   }
 
@@ -53,6 +67,8 @@ class TextEditor extends React.Component {
     this.stopCursorBlinking();
     const element = ReactDOM.findDOMNode(this);
     element.removeEventListener('wheel', this.handleMouseWheel, {passive: true});
+    window.removeEventListener('mousemove', this.handleMouseMove);
+    window.removeEventListener('mouseup', this.handleMouseUp);
     this.resizeObserver.disconnect();
   }
 
@@ -69,48 +85,78 @@ class TextEditor extends React.Component {
       Root,
       {
         tabIndex: -1,
-        onKeyDown: this.handleKeyDown
+        onKeyDown: this.handleKeyDown,
+        onFocus: this.props.dispatchFocus,
+        onMouseDown: this.handleMouseDown
       },
       $(TextPlane, {
-        showCursors: this.state.showCursors,
+        showCursors: this.props.focused && this.state.showCursors,
         lineHeight: this.props.line_height,
         scrollTop: this.props.scroll_top,
+        scrollLeft: this.props.scroll_left,
         height: this.props.height,
         width: this.props.width,
         selections: this.props.selections,
+        findMatches: this.props.find_matches,
         firstVisibleRow: this.props.first_visible_row,
-        lines: this.props.lines
+        lines: this.props.lines,
+        tokens: this.props.tokens
+      }),
+      $(SaveStatus, {
+        dirty: this.props.dirty,
+        saveConflict: this.props.save_conflict,
+        dispatch: this.props.dispatch
       })
     );
   }
 
   handleMouseWheel(event) {
-    this.props.dispatch({type: 'UpdateScrollTop', delta: event.deltaY});
+    if (event.deltaY !== 0) {
+      this.props.dispatch({type: 'UpdateScrollTop', delta: event.deltaY});
+    }
+    if (event.deltaX !== 0) {
+      this.props.dispatch({type: 'UpdateScrollLeft', delta: event.deltaX});
+    }
+  }
+
+  handleMouseDown(event) {
+    if (event.button !== 0) return;
+    this.pauseCursorBlinking();
+    const {x, y} = this.positionForMouseEvent(event);
+    this.props.dispatch({
+      type: 'MouseDown',
+      x,
+      y,
+      click_count: event.detail,
+      modifiers: {alt: event.altKey, shift: event.shiftKey}
+    });
+    window.addEventListener('mousemove', this.handleMouseMove);
+    window.addEventListener('mouseup', this.handleMouseUp);
+  }
+
+  handleMouseMove(event) {
+    this.pauseCursorBlinking();
+    const {x, y} = this.positionForMouseEvent(event);
+    this.props.dispatch({type: 'MouseDrag', x, y});
+  }
+
+  handleMouseUp(event) {
+    window.removeEventListener('mousemove', this.handleMouseMove);
+    window.removeEventListener('mouseup', this.handleMouseUp);
+    this.props.dispatch({type: 'MouseUp'});
+  }
+
+  positionForMouseEvent(event) {
+    const rect = ReactDOM.findDOMNode(this).getBoundingClientRect();
+    return {x: event.clientX - rect.left, y: event.clientY - rect.top};
   }
 
   handleKeyDown(event) {
-    if (event.key.length === 1 && !event.metaKey) {
+    this.pauseCursorBlinking();
+    if (isTextInput(event)) {
       this.props.dispatch({type: 'Edit', text: event.key});
-      return;
-    }
-
-    switch (event.key) {
-      case 'ArrowUp':
-        this.pauseCursorBlinking();
-        this.props.dispatch({type: 'MoveUp'});
-        break;
-      case 'ArrowDown':
-        this.pauseCursorBlinking();
-        this.props.dispatch({type: 'MoveDown'});
-        break;
-      case 'ArrowLeft':
-        this.pauseCursorBlinking();
-        this.props.dispatch({type: 'MoveLeft'});
-        break;
-      case 'ArrowRight':
-        this.pauseCursorBlinking();
-        this.props.dispatch({type: 'MoveRight'});
-        break;
+    } else {
+      this.props.dispatchKeyDown(event);
     }
   }
 
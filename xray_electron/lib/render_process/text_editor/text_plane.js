@@ -54,9 +54,12 @@ class TextPlane extends React.Component {
       canvasWidth: this.props.width * window.devicePixelRatio,
       canvasHeight: this.props.height * window.devicePixelRatio,
       scrollTop: this.props.scrollTop,
+      scrollLeft: this.props.scrollLeft || 0,
       firstVisibleRow: this.props.firstVisibleRow,
       lines: this.props.lines,
+      tokens: this.props.tokens || [],
       selections: this.props.selections,
+      findMatches: this.props.findMatches || [],
       showCursors: this.props.showCursors,
       computedLineHeight,
     });
@@ -76,6 +79,16 @@ const MAX_GLYPH_INSTANCES = 1 << 16;
 const GLYPH_INSTANCE_SIZE_IN_BYTES = 12 * Float32Array.BYTES_PER_ELEMENT;
 const SOLID_INSTANCE_SIZE_IN_BYTES = 8 * Float32Array.BYTES_PER_ELEMENT;
 const SUBPIXEL_DIVISOR = 4;
+const SCOPE_COLORS = [
+  ["comment", {r: 140, g: 140, b: 140, a: 255}],
+  ["string", {r: 24, g: 128, b: 56, a: 255}],
+  ["constant", {r: 0, g: 92, b: 197, a: 255}],
+  ["keyword", {r: 167, g: 29, b: 93, a: 255}],
+  ["storage", {r: 167, g: 29, b: 93, a: 255}],
+  ["variable.language", {r: 0, g: 92, b: 197, a: 255}],
+  ["entity.name.function", {r: 111, g: 66, b: 193, a: 255}],
+  ["entity.name.type", {r: 227, g: 98, b: 9, a: 255}]
+];
 
 class Renderer {
   constructor(gl, style) {
@@ -302,19 +315,23 @@ class Renderer {
     return vao
   }
 
-  draw({ canvasHeight, canvasWidth, scrollTop, firstVisibleRow, lines, selections, showCursors }) {
+  draw({ canvasHeight, canvasWidth, scrollTop, scrollLeft, firstVisibleRow, lines, tokens, selections, findMatches, showCursors }) {
     const { dpiScale } = this.style;
     const viewportScaleX = 2 / canvasWidth;
     const viewportScaleY = -2 / canvasHeight;
 
     const textColor = {r: 0, g: 0, b: 0, a: 255};
     const selectionColor = {r: 255, g: 255, b: 0, a: 255};
+    const findMatchColor = {r: 255, g: 200, b: 120, a: 255};
     const cursorColor = {r: 0, g: 0, b: 0, a: 255};
     const cursorWidth = 2;
 
     const selectionPositions = new Float32Array(selections.length * 2);
-    const glyphCount = this.populateGlyphInstances(scrollTop, firstVisibleRow, lines, selections, textColor, selectionPositions);
-    const {selectionSolidCount, cursorSolidCount} = this.populateSelectionSolidInstances(scrollTop, canvasWidth, selections, selectionPositions, selectionColor, cursorColor, cursorWidth);
+    const findMatchPositions = new Float32Array(findMatches.length * 2);
+    const glyphCount = this.populateGlyphInstances(scrollTop, scrollLeft, firstVisibleRow, lines, tokens, selections, findMatches, textColor, selectionPositions, findMatchPositions);
+    // Find matches are never empty, so they only produce solids drawn beneath the selections.
+    const findMatchSolidCount = this.populateSelectionSolidInstances(scrollTop, canvasWidth, findMatches, findMatchPositions, findMatchColor, cursorColor, cursorWidth, 0).selectionSolidCount;
+    const {selectionSolidCount, cursorSolidCount} = this.populateSelectionSolidInstances(scrollTop, canvasWidth, selections, selectionPositions, selectionColor, cursorColor, cursorWidth, findMatchSolidCount);
     this.atlas.uploadTexture()
 
     this.gl.clearColor(1, 1, 1, 1);
@@ -426,18 +443,21 @@ class Renderer {
     );
   }
 
-  populateGlyphInstances(scrollTop, firstVisibleRow, lines, selections, textColor, selectionPositions) {
+  populateGlyphInstances(scrollTop, scrollLeft, firstVisibleRow, lines, tokens, selections, findMatches, textColor, selectionPositions, findMatchPositions) {
     const firstVisibleRowY = firstVisibleRow * this.style.computedLineHeight;
 
     let glyphCount = 0;
     let selectionIndex = 0;
+    let findMatchIndex = 0;
     let y = Math.round((firstVisibleRowY - scrollTop) * this.style.dpiScale);
     const position = {}
 
     for (var i = 0; i < lines.length; i++) {
       position.row = firstVisibleRow + i;
-      let x = 0;
+      let x = -scrollLeft * this.style.dpiScale;
       const line = lines[i];
+      const lineTokens = tokens[i] || [];
+      let tokenIndex = 0;
 
       for (position.column = 0; position.column <= line.length; position.column++) {
         const selection = selections[selectionIndex];
@@ -452,12 +472,32 @@ class Renderer {
           }
         }
 
+        const findMatch = findMatches[findMatchIndex];
+        if (findMatch) {
+          if (comparePoints(position, findMatch.start) === 0) {
+            findMatchPositions[findMatchIndex * 2] = x;
+          }
+
+          if (comparePoints(position, findMatch.end) === 0) {
+            findMatchPositions[findMatchIndex * 2 + 1] = x;
+            findMatchIndex++;
+          }
+        }
+
         if (position.column < line.length) {
           const char = line[position.column];
           const variantIndex = Math.round(x * SUBPIXEL_DIVISOR) % SUBPIXEL_DIVISOR;
           const glyph = this.atlas.getGlyph(char, variantIndex);
 
-          this.updateGlyphInstance(glyphCount++, Math.round(x - glyph.variantOffset), y, glyph, textColor);
+          while (tokenIndex < lineTokens.length && lineTokens[tokenIndex].end <= position.column) {
+            tokenIndex++;
+          }
+          const token = lineTokens[tokenIndex];
+          const color = token && token.start <= position.column
+            ? colorForScope(token.scope, textColor)
+            : textColor;
+
+          this.updateGlyphInstance(glyphCount++, Math.round(x - glyph.variantOffset), y, glyph, color);
 
           x += glyph.subpixelWidth;
         }
@@ -490,10 +530,10 @@ class Renderer {
     this.glyphInstances[11 + startOffset] = glyph.textureHeight;
   }
 
-  populateSelectionSolidInstances(scrollTop, canvasWidth, selections, selectionPositions, selectionColor, cursorColor, cursorWidth) {
+  populateSelectionSolidInstances(scrollTop, canvasWidth, selections, selectionPositions, selectionColor, cursorColor, cursorWidth, firstSelectionSolidIndex) {
     const { dpiScale, computedLineHeight } = this.style;
 
-    let selectionSolidCount = 0;
+    let selectionSolidCount = firstSelectionSolidIndex;
     let cursorSolidCount = 0;
 
     for (var i = 0; i < selections.length; i++) {
@@ -732,6 +772,14 @@ class Atlas {
   }
 }
 
+function colorForScope(scope, defaultColor) {
+  for (let i = 0; i < SCOPE_COLORS.length; i++) {
+    const [prefix, color] = SCOPE_COLORS[i];
+    if (scope === prefix || scope.startsWith(prefix + ".")) return color;
+  }
+  return defaultColor;
+}
+
 function comparePoints(a, b) {
   return (a.row - b.row) || (a.column - b.column)
 }
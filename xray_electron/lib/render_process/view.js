@@ -1,7 +1,11 @@
 const propTypes = require("prop-types");
 const React = require("react");
+const ReactDOM = require("react-dom");
 const $ = React.createElement;
 const ViewRegistry = require("./view_registry");
+const { isTextInput, keystrokeForEvent } = require("./keystroke");
+
+const FOCUSABLE_SELECTOR = "input, [tabindex]";
 
 class View extends React.Component {
   constructor(props, context) {
@@ -27,17 +31,55 @@ class View extends React.Component {
     }
   }
 
+  componentDidMount() {
+    this.focusIfNeeded();
+  }
+
+  componentDidUpdate() {
+    this.focusIfNeeded();
+  }
+
   componentWillUnmount() {
     if (this.disposePropsWatch) this.disposePropsWatch();
   }
 
+  // Moves DOM focus into this view when the server has focused it, unless focus is already
+  // somewhere within the view.
+  focusIfNeeded() {
+    if (!this.context.viewRegistry.isFocused(this.props.id)) return;
+    const element = ReactDOM.findDOMNode(this);
+    if (element && !element.contains(document.activeElement)) {
+      const focusable = element.matches(FOCUSABLE_SELECTOR)
+        ? element
+        : element.querySelector(FOCUSABLE_SELECTOR);
+      if (focusable) focusable.focus();
+    }
+  }
+
   render() {
     const { viewRegistry } = this.context;
     const { id } = this.props;
     const component = viewRegistry.getComponent(id);
     const props = viewRegistry.getProps(id);
     const dispatch = action => viewRegistry.dispatchAction(id, action);
-    return $(component, Object.assign({ dispatch }, props));
+    const dispatchKeyDown = (event, context) => {
+      if (isTextInput(event)) return;
+      const keystroke = keystrokeForEvent(event);
+      if (keystroke) {
+        // Only the innermost view reports the keystroke, since the server resolves it against the
+        // focused view's ancestors too.
+        event.stopPropagation();
+        viewRegistry.dispatchKeyDown(keystroke, context);
+      }
+    };
+    const dispatchFocus = event => {
+      event.stopPropagation();
+      viewRegistry.focus(id);
+    };
+    return $(
+      component,
+      Object.assign({ dispatch, dispatchKeyDown, dispatchFocus }, props)
+    );
   }
 }
 
@@ -1,11 +1,14 @@
 const assert = require("assert");
 
 module.exports = class ViewRegistry {
-  constructor({ onAction } = {}) {
+  constructor({ onAction, onKeyDown, onFocus } = {}) {
     this.onAction = onAction;
+    this.onKeyDown = onKeyDown;
+    this.onFocus = onFocus;
     this.componentsByName = new Map();
     this.viewsById = new Map();
     this.propListenersByViewId = new Map();
+    this.focusedViewId = null;
   }
 
   addComponent(name, component) {
@@ -25,17 +28,11 @@ module.exports = class ViewRegistry {
     this.componentsByName.delete(name);
   }
 
-  update({ updated, removed }) {
+  update({ updated, removed, focused }) {
     for (let i = 0; i < updated.length; i++) {
       const view = updated[i];
       this.viewsById.set(view.view_id, view);
-
-      const listeners = this.propListenersByViewId.get(view.view_id);
-      if (listeners) {
-        for (let i = 0; i < listeners.length; i++) {
-          listeners[i]();
-        }
-      }
+      this.notifyPropListeners(view.view_id);
     }
 
     for (var i = 0; i < removed.length; i++) {
@@ -43,6 +40,8 @@ module.exports = class ViewRegistry {
       this.viewsById.delete(viewId);
       this.propListenersByViewId.delete(viewId);
     }
+
+    if (focused != null) this.setFocusedViewId(focused);
   }
 
   getProps(id) {
@@ -72,4 +71,35 @@ module.exports = class ViewRegistry {
     assert(this.viewsById.has(id));
     this.onAction({ view_id: id, action });
   }
+
+  dispatchKeyDown(keystroke, context = []) {
+    this.onKeyDown({ keystroke, context });
+  }
+
+  isFocused(id) {
+    return this.focusedViewId === id;
+  }
+
+  focus(id) {
+    assert(this.viewsById.has(id));
+    if (this.setFocusedViewId(id)) this.onFocus({ view_id: id });
+  }
+
+  setFocusedViewId(id) {
+    if (this.focusedViewId === id) return false;
+    const previousFocusedViewId = this.focusedViewId;
+    this.focusedViewId = id;
+    this.notifyPropListeners(previousFocusedViewId);
+    this.notifyPropListeners(id);
+    return true;
+  }
+
+  notifyPropListeners(id) {
+    const listeners = this.propListenersByViewId.get(id);
+    if (listeners) {
+      for (let i = 0; i < listeners.length; i++) {
+        listeners[i]();
+      }
+    }
+  }
 };
@@ -1,6 +1,7 @@
 const React = require("react");
 const { styled } = require("styletron-react");
 const Modal = require("./modal");
+const PaneGroup = require("./pane_group");
 const View = require("./view");
 const $ = React.createElement;
 
@@ -9,42 +10,37 @@ const Root = styled("div", {
   width: "100%",
   height: "100%",
   padding: 0,
-  margin: 0
+  margin: 0,
+  display: "flex"
 });
 
 module.exports = class Workspace extends React.Component {
-  constructor() {
-    super()
-    this.didKeyDown = this.didKeyDown.bind(this)
-  }
-
   render() {
     let modal;
     if (this.props.modal) {
       modal = $(Modal, null, $(View, { id: this.props.modal }));
     }
 
-    let centerItem
-    if (this.props.center_pane) {
-      centerItem = $(View, { id: this.props.center_pane });
+    const center = $(PaneGroup, {
+      node: this.props.center,
+      dispatch: this.props.dispatch
+    });
+
+    let findBar
+    if (this.props.find_bar) {
+      findBar = $(View, { id: this.props.find_bar });
     }
 
     return $(
       Root,
       {
         tabIndex: -1,
-        onKeyDown: this.didKeyDown
+        onKeyDown: this.props.dispatchKeyDown,
+        onFocus: this.props.dispatchFocus
       },
-      centerItem,
+      center,
+      findBar,
       modal
     );
   }
-
-  didKeyDown(event) {
-    if (event.metaKey) {
-      if (event.key === 't') {
-        this.props.dispatch({type: 'ToggleFileFinder'})
-      }
-    }
-  }
 };
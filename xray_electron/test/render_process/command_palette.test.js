@@ -0,0 +1,35 @@
+const assert = require("assert");
+const {mount, setProps} = require("./helpers/component_helpers");
+const CommandPalette = require("../../lib/render_process/command_palette");
+const $ = require("react").createElement;
+
+suite("CommandPaletteView", () => {
+  test("basic rendering", async () => {
+    const commandPalette = mount($(CommandPalette, {
+      query: '',
+      results: []
+    }));
+
+    assert.equal(commandPalette.find("ol li").length, 0);
+
+    await setProps(commandPalette, {
+      query: 'sw',
+      results: [
+        {name: 'Toggle Soft Wrap', match_indices: [7, 12], keystrokes: 'alt-z'},
+        {name: 'Select Word', match_indices: [0, 7], keystrokes: null},
+      ]
+    });
+
+    assert.deepEqual(
+      commandPalette.find("ol li span").map(item => item.getDOMNode().innerHTML),
+      [
+        'Toggle <b>S</b>oft <b>W</b>rap',
+        '<b>S</b>elect <b>W</b>ord'
+      ]
+    );
+    assert.deepEqual(
+      commandPalette.find("ol li kbd").map(item => item.getDOMNode().textContent),
+      ['alt-z']
+    );
+  });
+});
@@ -0,0 +1,43 @@
+const assert = require("assert");
+const {
+  isTextInput,
+  keystrokeForEvent
+} = require("../../lib/render_process/keystroke");
+
+suite("keystroke", () => {
+  test("keystrokeForEvent", () => {
+    assert.equal(keystrokeForEvent(event({ key: "z", code: "KeyZ" })), "z");
+    assert.equal(
+      keystrokeForEvent(event({ key: "Z", code: "KeyZ", shiftKey: true, metaKey: true })),
+      "shift-cmd-z"
+    );
+    assert.equal(
+      keystrokeForEvent(event({ key: "Ω", code: "KeyZ", altKey: true })),
+      "alt-z"
+    );
+    assert.equal(
+      keystrokeForEvent(event({ key: "ArrowUp", code: "ArrowUp", ctrlKey: true, altKey: true })),
+      "ctrl-alt-up"
+    );
+    assert.equal(
+      keystrokeForEvent(event({ key: "{", code: "BracketLeft", shiftKey: true, metaKey: true })),
+      "shift-cmd-["
+    );
+    assert.equal(keystrokeForEvent(event({ key: "F5", code: "F5" })), "f5");
+    assert.equal(keystrokeForEvent(event({ key: "Meta", code: "MetaLeft", metaKey: true })), null);
+  });
+
+  test("isTextInput", () => {
+    assert(isTextInput(event({ key: "a", code: "KeyA" })));
+    assert(isTextInput(event({ key: "A", code: "KeyA", shiftKey: true })));
+    assert(!isTextInput(event({ key: "a", code: "KeyA", metaKey: true })));
+    assert(!isTextInput(event({ key: "Enter", code: "Enter" })));
+  });
+});
+
+function event(properties) {
+  return Object.assign(
+    { ctrlKey: false, altKey: false, shiftKey: false, metaKey: false },
+    properties
+  );
+}
@@ -0,0 +1,60 @@
+const assert = require("assert");
+const {mount, setProps} = require("./helpers/component_helpers");
+const ProjectSearch = require("../../lib/render_process/project_search");
+const $ = require("react").createElement;
+
+suite("ProjectSearchView", () => {
+  test("basic rendering", async () => {
+    const projectSearch = mount($(ProjectSearch, {
+      query: '',
+      replacement: '',
+      error: null,
+      matches: [],
+      replacements: []
+    }));
+
+    assert.equal(projectSearch.find("ol li").length, 0);
+
+    await setProps(projectSearch, {
+      query: 'ba',
+      matches: [
+        {path: '/a.txt', row: 1, line: 'bar', column_ranges: [{start: 0, end: 2}]},
+        {
+          path: '/b.txt',
+          row: 0,
+          line: 'bar baz',
+          column_ranges: [{start: 0, end: 2}, {start: 4, end: 6}]
+        },
+      ]
+    });
+
+    assert.deepEqual(
+      projectSearch.find("ol li").map(item => item.getDOMNode().textContent),
+      ['/a.txt:2bar', '/b.txt:1bar baz']
+    );
+    assert.deepEqual(
+      projectSearch.find("ol li b").map(item => item.getDOMNode().textContent),
+      ['ba', 'ba', 'ba']
+    );
+
+    await setProps(projectSearch, {
+      matches: [],
+      replacements: [
+        {path: '/a.txt', replacement_count: 1, error: null},
+        {path: '/c.txt', replacement_count: 0, error: 'permission denied'},
+      ]
+    });
+    assert.deepEqual(
+      projectSearch.find("ul li").map(item => item.getDOMNode().textContent),
+      ['/a.txt: 1 replacements', '/c.txt: permission denied']
+    );
+
+    await setProps(projectSearch, {
+      query: '(',
+      error: 'regex parse error',
+      matches: [],
+      replacements: []
+    });
+    assert(projectSearch.getDOMNode().textContent.includes('regex parse error'));
+  });
+});
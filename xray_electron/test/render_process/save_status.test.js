@@ -0,0 +1,26 @@
+const assert = require("assert");
+const {mount, setProps} = require("./helpers/component_helpers");
+const SaveStatus = require("../../lib/render_process/text_editor/save_status");
+const $ = require("react").createElement;
+
+suite("SaveStatus", () => {
+  test("dirty indicator and save conflict prompt", async () => {
+    const actions = [];
+    const saveStatus = mount($(SaveStatus, {
+      dirty: false,
+      saveConflict: false,
+      dispatch: action => actions.push(action)
+    }));
+
+    assert.equal(saveStatus.html(), null);
+
+    await setProps(saveStatus, {dirty: true});
+    assert.equal(saveStatus.text(), "Modified");
+    assert.equal(saveStatus.find("button").length, 0);
+
+    await setProps(saveStatus, {saveConflict: true});
+    assert(saveStatus.text().includes("The file has changed on disk."));
+    saveStatus.find("button").simulate("click");
+    assert.deepEqual(actions, [{type: "ForceSave"}]);
+  });
+});
@@ -106,4 +106,49 @@ suite("ViewRegistry", () => {
       { view_id: 2, action: { c: 3 } }
     ]);
   });
+
+  test("dispatching key downs", () => {
+    const keyDowns = [];
+    const registry = new ViewRegistry({ onKeyDown: k => keyDowns.push(k) });
+
+    registry.dispatchKeyDown("cmd-t");
+    registry.dispatchKeyDown("enter", ["replacement"]);
+
+    assert.deepEqual(keyDowns, [
+      { keystroke: "cmd-t", context: [] },
+      { keystroke: "enter", context: ["replacement"] }
+    ]);
+  });
+
+  test("focus", () => {
+    const focusEvents = [];
+    const registry = new ViewRegistry({ onFocus: f => focusEvents.push(f) });
+    registry.update({
+      updated: [
+        { component_name: "component-1", view_id: 1, props: {} },
+        { component_name: "component-2", view_id: 2, props: {} }
+      ],
+      removed: []
+    });
+
+    let view1Notifications = 0;
+    let view2Notifications = 0;
+    registry.watchProps(1, () => view1Notifications++);
+    registry.watchProps(2, () => view2Notifications++);
+
+    // Focus changes from the server are not reported back to it.
+    registry.update({ updated: [], removed: [], focused: 1 });
+    assert(registry.isFocused(1));
+    assert.equal(view1Notifications, 1);
+
+    registry.focus(2);
+    registry.focus(2);
+    assert(!registry.isFocused(1));
+    assert(registry.isFocused(2));
+    assert.equal(view1Notifications, 2);
+    assert.equal(view2Notifications, 1);
+    assert.throws(() => registry.focus(3));
+
+    assert.deepEqual(focusEvents, [{ view_id: 2 }]);
+  });
 });
@@ -4,11 +4,15 @@ use futures_cpupool::CpuPool;
 use messages::{IncomingMessage, OutgoingMessage};
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::io;
-use std::path::PathBuf;
+use std::env;
+use std::fs as std_fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use serde_json;
 use xray_core;
+use xray_core::clipboard::Clipboard;
+use xray_core::keymap::{Keymap, Keystroke};
 use xray_core::workspace::WorkspaceView;
 use xray_core::window::{ViewId, Window};
 use tokio_core::reactor;
@@ -25,6 +29,8 @@ struct Inner {
     app_channel: Option<OutboundSender>,
     next_window_id: WindowId,
     windows: HashMap<WindowId, Window>,
+    clipboard: Clipboard,
+    keymap: Rc<Keymap>,
     reactor: reactor::Handle,
 }
 
@@ -35,6 +41,8 @@ impl App {
                 next_window_id: 1,
                 app_channel: None,
                 windows: HashMap::new(),
+                clipboard: Clipboard::new(),
+                keymap: Rc::new(load_keymap()),
                 reactor,
             })),
         }
@@ -85,7 +93,13 @@ impl App {
             return;
         }
 
-        inner_borrow.app_channel = Some(tx);
+        inner_borrow.app_channel = Some(tx.clone());
+
+        let send_clipboard_updates = inner_borrow.clipboard.text_updates().for_each(move |text| {
+            tx.unbounded_send(OutgoingMessage::SetClipboard { text })
+                .map_err(|_| ())
+        });
+        inner_borrow.reactor.spawn(send_clipboard_updates);
 
         let receive_incoming = Self::handle_app_messages(inner.clone(), incoming);
         let send_outgoing = outgoing
@@ -167,8 +181,8 @@ impl App {
 impl Inner {
     fn handle_app_message(&mut self, message: IncomingMessage) {
         match message {
-            IncomingMessage::OpenWorkspace { paths } => {
-                self.open_workspace(paths);
+            IncomingMessage::OpenWorkspace { paths, ignore_globs } => {
+                self.open_workspace(paths, ignore_globs);
             }
             _ => {
                 eprintln!("Unexpected message {:?}", message);
@@ -181,24 +195,32 @@ impl Inner {
             IncomingMessage::Action { view_id, action } => {
                 self.dispatch_action(window_id, view_id, action);
             }
+            IncomingMessage::KeyDown { keystroke, context } => {
+                self.dispatch_keystroke(window_id, &keystroke, context);
+            }
+            IncomingMessage::Focus { view_id } => {
+                self.focus(window_id, view_id);
+            }
             _ => {
                 eprintln!("Unexpected message {:?}", message);
             }
         };
     }
 
-    fn open_workspace(&mut self, paths: Vec<PathBuf>) {
+    fn open_workspace(&mut self, paths: Vec<PathBuf>, ignore_globs: Vec<String>) {
         let window_id = self.next_window_id;
         self.next_window_id += 1;
 
         let background_executor = Box::new(CpuPool::new_num_cpus());
         let mut window = Window::new(Some(background_executor), 0.0);
+        window.set_keymap(self.keymap.clone());
 
         let roots = paths.iter()
-            .map(|path| Box::new(fs::Tree::new(path)) as Box<xray_core::fs::Tree>)
+            .map(|path| Box::new(fs::Tree::new(path, &ignore_globs)) as Box<xray_core::fs::Tree>)
             .collect();
 
-        let workspace_view_handle = window.handle().add_view(WorkspaceView::new(roots));
+        let workspace_view = WorkspaceView::new(roots, self.clipboard.clone());
+        let workspace_view_handle = window.handle().add_view(workspace_view);
         window.set_root_view(workspace_view_handle);
         self.windows.insert(window_id, window);
 
@@ -209,10 +231,67 @@ impl Inner {
         }
     }
 
-    fn dispatch_action(&mut self, window_id: WindowId, view_id: ViewId, action: serde_json::Value) {
+    fn dispatch_action(
+        &mut self,
+        window_id: WindowId,
+        view_id: Option<ViewId>,
+        action: serde_json::Value,
+    ) {
+        match self.windows.get_mut(&window_id) {
+            Some(ref mut window) => match view_id {
+                Some(view_id) => window.dispatch_action(view_id, action),
+                None => window.dispatch_focused_action(action),
+            },
+            None => eprintln!("Action for unknown window {}", window_id),
+        };
+    }
+
+    fn dispatch_keystroke(&mut self, window_id: WindowId, keystroke: &str, context: Vec<String>) {
+        let keystroke = match Keystroke::parse(keystroke) {
+            Ok(keystroke) => keystroke,
+            Err(error) => {
+                eprintln!("Invalid keystroke: {:?}", error);
+                return;
+            }
+        };
+        match self.windows.get_mut(&window_id) {
+            Some(ref mut window) => window.dispatch_keystroke(keystroke, context),
+            None => eprintln!("Keystroke for unknown window {}", window_id),
+        };
+    }
+
+    fn focus(&mut self, window_id: WindowId, view_id: ViewId) {
         match self.windows.get_mut(&window_id) {
-            Some(ref mut window) => window.dispatch_action(view_id, action),
-            None => unimplemented!(),
+            Some(ref mut window) => window.focus(view_id),
+            None => eprintln!("Focus for unknown window {}", window_id),
         };
     }
 }
+
+/// Loads the built-in bindings along with any user bindings, which are read from the file at
+/// `XRAY_KEYMAP_PATH` or otherwise from `~/.xray/keymap.json`.
+fn load_keymap() -> Keymap {
+    let mut keymap = Keymap::new();
+    let path = env::var_os("XRAY_KEYMAP_PATH")
+        .map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home_dir| home_dir.join(".xray").join("keymap.json")));
+    let source = match path.map(|path| read_to_string(&path)) {
+        Some(Ok(source)) => source,
+        _ => return keymap,
+    };
+    match serde_json::from_str(&source) {
+        Ok(bindings) => {
+            if let Err(error) = keymap.add_bindings(&bindings) {
+                eprintln!("Invalid keymap: {:?}", error);
+            }
+        }
+        Err(error) => eprintln!("Error parsing keymap: {}", error),
+    }
+    keymap
+}
+
+fn read_to_string(path: &Path) -> io::Result<String> {
+    let mut source = String::new();
+    std_fs::File::open(path)?.read_to_string(&mut source)?;
+    Ok(source)
+}
@@ -1,4 +1,13 @@
-use futures::{Future, Stream};
+use futures::Stream;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+#[cfg(target_os = "linux")]
+use inotify::{Event, EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+#[cfg(target_os = "linux")]
+use std::ffi::OsString;
+#[cfg(target_os = "linux")]
+use std::fs as std_fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
@@ -6,48 +15,449 @@ use walkdir::WalkDir;
 use xray_core::fs;
 use xray_core::notify_cell::NotifyCell;
 
+const GIT_DIR_NAME: &str = ".git";
+const GITIGNORE_FILE_NAME: &str = ".gitignore";
+
 pub struct Tree {
     path: PathBuf,
     root: fs::Entry,
-    updates: Arc<NotifyCell<()>>
+    updates: Arc<NotifyCell<()>>,
+}
+
+/// Applies the changes inotify reports to a tree's entries. Inotify is only available on Linux, so
+/// trees on other platforms are populated once and not updated afterward.
+#[cfg(target_os = "linux")]
+struct Watcher {
+    watches: Watches,
+    ignore_rules: IgnoreRules,
+    pending_moves: HashMap<u32, (WatchDescriptor, OsString)>,
+    updates: Arc<NotifyCell<()>>,
+}
+
+#[cfg(target_os = "linux")]
+struct Watches {
+    inotify: Inotify,
+    dirs: HashMap<WatchDescriptor, WatchedDir>,
+}
+
+#[cfg(target_os = "linux")]
+struct WatchedDir {
+    path: PathBuf,
+    entry: fs::Entry,
+}
+
+/// Determines which paths in a tree are ignored. Rules are consulted in order of precedence:
+/// the user's configured globs, then `.gitignore` files from the deepest directory upward, then
+/// the repository's `.git/info/exclude` file and finally the global git excludes file.
+struct IgnoreRules {
+    root: PathBuf,
+    configured: Gitignore,
+    gitignores: HashMap<PathBuf, Gitignore>,
+    exclude: Gitignore,
+    global: Gitignore,
 }
 
 impl Tree {
-    pub fn new<T: Into<PathBuf>>(path: T) -> Self {
+    pub fn new<T: Into<PathBuf>>(path: T, ignore_globs: &[String]) -> Self {
         let path = path.into();
-        let root = fs::Entry::dir(false);
+        let root = fs::Entry::dir(false, false);
         let updates = Arc::new(NotifyCell::new(()));
-        Self::populate(path.clone(), root.clone(), updates.clone());
-        Self { path, root, updates }
+        let ignore_rules = IgnoreRules::new(&path, ignore_globs);
+        Self::watch(path.clone(), root.clone(), updates.clone(), ignore_rules);
+        Self {
+            path,
+            root,
+            updates,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn watch(
+        path: PathBuf,
+        root: fs::Entry,
+        updates: Arc<NotifyCell<()>>,
+        mut ignore_rules: IgnoreRules,
+    ) {
+        thread::spawn(move || match Inotify::init() {
+            Ok(inotify) => {
+                let mut watches = Watches::new(inotify);
+                populate(&path, &root, &updates, &mut ignore_rules, |path, dir| {
+                    watches.add(path, dir)
+                });
+                Watcher::new(watches, ignore_rules, updates).run();
+            }
+            Err(error) => {
+                eprintln!(
+                    "Error initializing inotify, file tree will not be updated: {}",
+                    error
+                );
+                populate(&path, &root, &updates, &mut ignore_rules, |_, _| {});
+            }
+        });
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn watch(
+        path: PathBuf,
+        root: fs::Entry,
+        updates: Arc<NotifyCell<()>>,
+        mut ignore_rules: IgnoreRules,
+    ) {
+        thread::spawn(move || populate(&path, &root, &updates, &mut ignore_rules, |_, _| {}));
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    fn new(watches: Watches, ignore_rules: IgnoreRules, updates: Arc<NotifyCell<()>>) -> Self {
+        Self {
+            watches,
+            ignore_rules,
+            pending_moves: HashMap::new(),
+            updates,
+        }
+    }
+
+    fn run(&mut self) {
+        while self.process_events() {}
     }
 
-    fn populate(path: PathBuf, root: fs::Entry, updates: Arc<NotifyCell<()>>) {
-        thread::spawn(move || {
-            let mut stack = vec![root];
+    /// Blocks until inotify reports events and then applies them. Returns false if events can no
+    /// longer be read.
+    fn process_events(&mut self) -> bool {
+        let mut buffer = [0; 4096];
+        let events = match self.watches.inotify.read_events_blocking(&mut buffer) {
+            Ok(events) => events,
+            Err(error) => {
+                eprintln!(
+                    "Error reading inotify events, file tree will not be updated: {}",
+                    error
+                );
+                return false;
+            }
+        };
+
+        for event in events {
+            self.handle_event(event);
+        }
 
-            let entries = WalkDir::new(path.clone())
-                .follow_links(true)
-                .into_iter()
-                .skip(1)
-                .filter_map(|e| e.ok());
+        // A move without a matching MOVED_TO event means the entry left the tree.
+        let pending_moves = self.pending_moves.drain().collect::<Vec<_>>();
+        for (_, (wd, name)) in pending_moves {
+            self.remove(&wd, &name);
+        }
 
-            for entry in entries {
-                stack.truncate(entry.depth());
+        self.updates.set(());
+        true
+    }
 
-                let file_type = entry.file_type();
-                let file_name = entry.file_name();
+    fn handle_event(&mut self, event: Event<&OsStr>) {
+        if event.mask.contains(EventMask::Q_OVERFLOW) {
+            eprintln!("Inotify queue overflowed, file tree may be out of date");
+            return;
+        }
 
-                if file_type.is_dir() {
-                    let dir = fs::Entry::dir(file_type.is_symlink());
-                    stack.last_mut().unwrap().insert(file_name, dir.clone());
-                    stack.push(dir);
-                } else if file_type.is_file() {
-                    let file = fs::Entry::file(file_type.is_symlink());
-                    stack.last_mut().unwrap().insert(file_name, file);
+        if event.mask.contains(EventMask::IGNORED) {
+            self.watches.dirs.remove(&event.wd);
+            return;
+        }
+
+        let name = match event.name {
+            Some(name) => name.to_owned(),
+            None => return,
+        };
+
+        if name == GITIGNORE_FILE_NAME {
+            // Entries that were already indexed keep their ignored status, but new entries will
+            // respect the updated rules.
+            if let Some(dir) = self.watches.dirs.get(&event.wd) {
+                self.ignore_rules.forget_gitignore(&dir.path);
+            }
+        }
+
+        if event.mask.contains(EventMask::CREATE) {
+            self.insert(&event.wd, name);
+        } else if event.mask.contains(EventMask::DELETE) {
+            self.remove(&event.wd, &name);
+        } else if event.mask.contains(EventMask::MOVED_FROM) {
+            self.pending_moves.insert(event.cookie, (event.wd, name));
+        } else if event.mask.contains(EventMask::MOVED_TO) {
+            match self.pending_moves.remove(&event.cookie) {
+                Some((old_wd, old_name)) => self.rename(&old_wd, &old_name, &event.wd, name),
+                None => self.insert(&event.wd, name),
+            }
+        }
+    }
+
+    fn insert(&mut self, wd: &WatchDescriptor, name: OsString) {
+        // Replace any existing entry, since the new one may be of a different type.
+        self.remove(wd, &name);
+
+        let (parent, path) = match self.watches.parent_and_path(wd, &name) {
+            Some(parent_and_path) => parent_and_path,
+            None => return,
+        };
+        let metadata = match std_fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => return, // The entry was removed before we could inspect it
+        };
+        let is_symlink = std_fs::symlink_metadata(&path)
+            .map(|metadata| metadata.file_type().is_symlink())
+            .unwrap_or(false);
+
+        let is_dir = metadata.is_dir();
+        let is_ignored = parent.is_ignored() || self.ignore_rules.is_ignored(&path, is_dir);
+
+        if is_dir {
+            let dir = fs::Entry::dir(is_symlink, is_ignored);
+            if parent.insert(name, dir.clone()).is_ok() && !is_ignored {
+                let watches = &mut self.watches;
+                populate(
+                    &path,
+                    &dir,
+                    &self.updates,
+                    &mut self.ignore_rules,
+                    |path, dir| watches.add(path, dir),
+                );
+            }
+        } else if metadata.is_file() {
+            let _ = parent.insert(name, fs::Entry::file(is_symlink, is_ignored));
+        }
+    }
+
+    fn remove(&mut self, wd: &WatchDescriptor, name: &OsStr) {
+        if let Some((parent, path)) = self.watches.parent_and_path(wd, name) {
+            if let Ok(entry) = parent.remove(name) {
+                if entry.is_dir() {
+                    self.watches.remove_within(&path);
                 }
-                updates.set(());
             }
+        }
+    }
+
+    fn rename(
+        &mut self,
+        old_wd: &WatchDescriptor,
+        old_name: &OsStr,
+        new_wd: &WatchDescriptor,
+        new_name: OsString,
+    ) {
+        let (old_parent, old_path) = match self.watches.parent_and_path(old_wd, old_name) {
+            Some(parent_and_path) => parent_and_path,
+            None => return self.insert(new_wd, new_name),
+        };
+        let (new_parent, new_path) = match self.watches.parent_and_path(new_wd, &new_name) {
+            Some(parent_and_path) => parent_and_path,
+            None => return self.remove(old_wd, old_name),
+        };
+
+        // Entries are only indexed and watched beneath directories that aren't ignored, so a move
+        // that changes whether the entry is ignored is handled as a removal and an insertion.
+        let (is_dir, was_ignored) = match old_parent.child(old_name) {
+            Some(entry) => (entry.is_dir(), entry.is_ignored()),
+            None => return self.insert(new_wd, new_name),
+        };
+        let is_ignored = new_parent.is_ignored() || self.ignore_rules.is_ignored(&new_path, is_dir);
+        if is_ignored != was_ignored {
+            self.remove(old_wd, old_name);
+            return self.insert(new_wd, new_name);
+        }
+
+        if new_parent.child(&new_name).map(|entry| entry.is_dir()) == Some(true) {
+            self.watches.remove_within(&new_path);
+        }
+
+        if old_parent
+            .rename(old_name, &new_parent, new_name.clone())
+            .is_ok()
+        {
+            // Watches follow the directory itself, so only the paths we associate with them need
+            // to be updated to reflect the new location.
+            for dir in self.watches.dirs.values_mut() {
+                if let Some(suffix) = dir.path.strip_prefix(&old_path).ok().map(Path::to_owned) {
+                    dir.path = if suffix.as_os_str().is_empty() {
+                        new_path.clone()
+                    } else {
+                        new_path.join(suffix)
+                    };
+                }
+            }
+            self.ignore_rules.forget_gitignores_within(&old_path);
+        } else {
+            self.insert(new_wd, new_name);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Watches {
+    fn new(inotify: Inotify) -> Self {
+        Self {
+            inotify,
+            dirs: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, path: &Path, entry: &fs::Entry) {
+        let mask = WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVE | WatchMask::ONLYDIR;
+        match self.inotify.add_watch(path, mask) {
+            Ok(wd) => {
+                self.dirs.insert(
+                    wd,
+                    WatchedDir {
+                        path: path.to_owned(),
+                        entry: entry.clone(),
+                    },
+                );
+            }
+            Err(error) => eprintln!("Error watching {}: {}", path.display(), error),
+        }
+    }
+
+    fn remove_within(&mut self, path: &Path) {
+        let wds = self
+            .dirs
+            .iter()
+            .filter(|&(_, dir)| dir.path.starts_with(path))
+            .map(|(wd, _)| wd.clone())
+            .collect::<Vec<_>>();
+        for wd in wds {
+            self.dirs.remove(&wd);
+            // The watch is already gone if the directory was deleted.
+            let _ = self.inotify.rm_watch(wd);
+        }
+    }
+
+    fn parent_and_path(&self, wd: &WatchDescriptor, name: &OsStr) -> Option<(fs::Entry, PathBuf)> {
+        self.dirs
+            .get(wd)
+            .map(|dir| (dir.entry.clone(), dir.path.join(name)))
+    }
+}
+
+impl IgnoreRules {
+    fn new(root: &Path, ignore_globs: &[String]) -> Self {
+        let mut configured = GitignoreBuilder::new(root);
+        for glob in ignore_globs {
+            if let Err(error) = configured.add_line(None, glob) {
+                eprintln!("Invalid ignore glob {:?}: {}", glob, error);
+            }
+        }
+        let configured = configured.build().unwrap_or_else(|error| {
+            eprintln!("Error building ignore globs: {}", error);
+            Gitignore::empty()
         });
+
+        let mut exclude = GitignoreBuilder::new(root);
+        exclude.add(root.join(".git/info/exclude"));
+        let exclude = exclude.build().unwrap_or_else(|_| Gitignore::empty());
+
+        let (global, error) = Gitignore::global();
+        if let Some(error) = error {
+            eprintln!("Error reading global git excludes: {}", error);
+        }
+
+        Self {
+            root: root.to_owned(),
+            configured,
+            gitignores: HashMap::new(),
+            exclude,
+            global,
+        }
+    }
+
+    fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let relative_path = match path.strip_prefix(&self.root) {
+            Ok(relative_path) => relative_path,
+            Err(_) => return false,
+        };
+
+        if relative_path.file_name() == Some(OsStr::new(GIT_DIR_NAME)) {
+            return true;
+        }
+        if self.configured.matched(relative_path, is_dir).is_ignore() {
+            return true;
+        }
+
+        let root = &self.root;
+        let mut parent = relative_path.parent();
+        while let Some(dir) = parent {
+            parent = dir.parent();
+            let gitignore = self
+                .gitignores
+                .entry(dir.to_owned())
+                .or_insert_with(|| Gitignore::new(root.join(dir).join(GITIGNORE_FILE_NAME)).0);
+            let path_in_dir = relative_path.strip_prefix(dir).unwrap();
+            let matched = gitignore.matched(path_in_dir, is_dir);
+            if !matched.is_none() {
+                return matched.is_ignore();
+            }
+        }
+
+        let matched = self
+            .exclude
+            .matched(relative_path, is_dir)
+            .or(self.global.matched(relative_path, is_dir));
+        matched.is_ignore()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn forget_gitignore(&mut self, dir: &Path) {
+        if let Ok(relative_dir) = dir.strip_prefix(&self.root) {
+            self.gitignores.remove(relative_dir);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn forget_gitignores_within(&mut self, dir: &Path) {
+        if let Ok(relative_dir) = dir.strip_prefix(&self.root) {
+            self.gitignores
+                .retain(|path, _| !path.starts_with(relative_dir));
+        }
+    }
+}
+
+fn populate<F>(
+    path: &Path,
+    root: &fs::Entry,
+    updates: &NotifyCell<()>,
+    ignore_rules: &mut IgnoreRules,
+    mut did_add_dir: F,
+) where
+    F: FnMut(&Path, &fs::Entry),
+{
+    did_add_dir(path, root);
+    let mut stack = vec![root.clone()];
+
+    let mut entries = WalkDir::new(path).follow_links(true).into_iter();
+    entries.next(); // Skip the root, which is already in the stack
+    while let Some(entry) = entries.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        stack.truncate(entry.depth());
+
+        let file_type = entry.file_type();
+        let file_name = entry.file_name();
+        let is_ignored = ignore_rules.is_ignored(entry.path(), file_type.is_dir());
+
+        if file_type.is_dir() {
+            let dir = fs::Entry::dir(file_type.is_symlink(), is_ignored);
+            stack.last_mut().unwrap().insert(file_name, dir.clone());
+            // The contents of ignored directories such as .git or node_modules are rarely useful
+            // and can be huge, so we record the directory itself but don't descend into it.
+            if is_ignored {
+                entries.skip_current_dir();
+            } else {
+                did_add_dir(entry.path(), &dir);
+                stack.push(dir);
+            }
+        } else if file_type.is_file() {
+            let file = fs::Entry::file(file_type.is_symlink(), is_ignored);
+            stack.last_mut().unwrap().insert(file_name, file);
+        }
+        updates.set(());
     }
 }
 
@@ -64,3 +474,178 @@ impl fs::Tree for Tree {
         Box::new(self.updates.observe())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_os = "linux")]
+    use futures::{future, Async, Future};
+    use rand::{self, Rng};
+    use std::fs as std_fs;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_watcher() {
+        let dir = temp_dir();
+        let path = dir.join("tree");
+        std_fs::create_dir_all(path.join("a/b")).unwrap();
+        std_fs::File::create(path.join("a/b/c.txt")).unwrap();
+        std_fs::File::create(path.join("d.txt")).unwrap();
+
+        let root = fs::Entry::dir(false, false);
+        let updates = Arc::new(NotifyCell::new(()));
+        let mut ignore_rules = IgnoreRules::new(&path, &["ignored".to_string()]);
+        let mut watches = Watches::new(Inotify::init().unwrap());
+        populate(&path, &root, &updates, &mut ignore_rules, |path, dir| {
+            watches.add(path, dir)
+        });
+        let mut watcher = Watcher::new(watches, ignore_rules, updates.clone());
+        let mut observer = updates.observe();
+        assert_eq!(file_paths(&root), vec!["a/b/c.txt", "d.txt"]);
+
+        std_fs::File::create(path.join("a/e.txt")).unwrap();
+        assert!(watcher.process_events());
+        assert_eq!(file_paths(&root), vec!["a/b/c.txt", "a/e.txt", "d.txt"]);
+        assert_eq!(poll(&mut observer), Async::Ready(Some(())));
+        assert_eq!(poll(&mut observer), Async::NotReady);
+
+        // Files created in a renamed directory are found through its new path.
+        std_fs::rename(path.join("a"), path.join("f")).unwrap();
+        assert!(watcher.process_events());
+        assert_eq!(file_paths(&root), vec!["d.txt", "f/b/c.txt", "f/e.txt"]);
+        std_fs::File::create(path.join("f/b/g.txt")).unwrap();
+        assert!(watcher.process_events());
+        assert_eq!(
+            file_paths(&root),
+            vec!["d.txt", "f/b/c.txt", "f/b/g.txt", "f/e.txt"]
+        );
+        assert_eq!(poll(&mut observer), Async::Ready(Some(())));
+
+        // Directories moved in from outside the tree are populated, and ones moved out of it are
+        // removed.
+        std_fs::create_dir_all(dir.join("h/i")).unwrap();
+        std_fs::File::create(dir.join("h/i/j.txt")).unwrap();
+        std_fs::rename(dir.join("h"), path.join("h")).unwrap();
+        assert!(watcher.process_events());
+        std_fs::rename(path.join("f/b"), dir.join("b")).unwrap();
+        assert!(watcher.process_events());
+        assert_eq!(file_paths(&root), vec!["d.txt", "f/e.txt", "h/i/j.txt"]);
+        assert!(root.entry_for_path("h/i").unwrap().is_dir());
+        assert!(root.entry_for_path("f/b").is_none());
+
+        std_fs::remove_file(path.join("d.txt")).unwrap();
+        assert!(watcher.process_events());
+        std_fs::remove_dir_all(path.join("h")).unwrap();
+        assert!(watcher.process_events());
+        assert_eq!(file_paths(&root), vec!["f/e.txt"]);
+        assert!(root.entry_for_path("h").is_none());
+        assert_eq!(poll(&mut observer), Async::Ready(Some(())));
+
+        // Ignored directories aren't watched, but are populated once they're no longer ignored.
+        std_fs::create_dir(path.join("ignored")).unwrap();
+        assert!(watcher.process_events());
+        std_fs::File::create(path.join("ignored/k.txt")).unwrap();
+        std_fs::File::create(path.join("l.txt")).unwrap();
+        assert!(watcher.process_events());
+        assert!(root.entry_for_path("ignored").unwrap().is_ignored());
+        assert_eq!(file_paths(&root), vec!["f/e.txt", "l.txt"]);
+        std_fs::rename(path.join("ignored"), path.join("m")).unwrap();
+        assert!(watcher.process_events());
+        assert_eq!(file_paths(&root), vec!["f/e.txt", "l.txt", "m/k.txt"]);
+        std_fs::File::create(path.join("m/n.txt")).unwrap();
+        assert!(watcher.process_events());
+        assert_eq!(
+            file_paths(&root),
+            vec!["f/e.txt", "l.txt", "m/k.txt", "m/n.txt"]
+        );
+
+        std_fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let path = temp_dir();
+        std_fs::create_dir_all(path.join(".git/info")).unwrap();
+        std_fs::create_dir_all(path.join("a")).unwrap();
+        write_file(
+            &path.join(".gitignore"),
+            "*.log\n!keep.log\nnode_modules/\n",
+        );
+        write_file(&path.join("a/.gitignore"), "!debug.log\n");
+        write_file(&path.join(".git/info/exclude"), "secrets.txt\n");
+
+        let mut ignore_rules = IgnoreRules::new(&path, &["*.tmp".to_string()]);
+        assert!(ignore_rules.is_ignored(&path.join(".git"), true));
+        assert!(ignore_rules.is_ignored(&path.join("node_modules"), true));
+        assert!(!ignore_rules.is_ignored(&path.join("node_modules"), false));
+        assert!(ignore_rules.is_ignored(&path.join("debug.log"), false));
+        assert!(!ignore_rules.is_ignored(&path.join("keep.log"), false));
+        assert!(ignore_rules.is_ignored(&path.join("b/debug.log"), false));
+        assert!(!ignore_rules.is_ignored(&path.join("a/debug.log"), false));
+        assert!(ignore_rules.is_ignored(&path.join("secrets.txt"), false));
+        assert!(ignore_rules.is_ignored(&path.join("a/scratch.tmp"), false));
+        assert!(!ignore_rules.is_ignored(&path.join("a/main.rs"), false));
+
+        std_fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_populate_skips_ignored_dirs() {
+        let path = temp_dir();
+        std_fs::create_dir_all(path.join(".git/objects")).unwrap();
+        std_fs::create_dir_all(path.join("node_modules/a")).unwrap();
+        std_fs::create_dir_all(path.join("src")).unwrap();
+        write_file(&path.join(".gitignore"), "node_modules/\n");
+        write_file(&path.join("node_modules/a/index.js"), "");
+        write_file(&path.join("src/main.rs"), "");
+
+        let root = fs::Entry::dir(false, false);
+        let updates = NotifyCell::new(());
+        let mut ignore_rules = IgnoreRules::new(&path, &[]);
+        let mut added_dirs = Vec::new();
+        populate(&path, &root, &updates, &mut ignore_rules, |dir_path, _| {
+            added_dirs.push(dir_path.strip_prefix(&path).unwrap().to_owned())
+        });
+
+        assert_eq!(added_dirs, vec![PathBuf::new(), PathBuf::from("src")]);
+        assert_eq!(file_paths(&root), vec![".gitignore", "src/main.rs"]);
+        for name in &[".git", "node_modules"] {
+            let dir = root.entry_for_path(name).unwrap();
+            assert!(dir.is_dir() && dir.is_ignored());
+            assert_eq!(file_paths(&dir), Vec::<String>::new());
+        }
+        assert!(root.entry_for_path("node_modules/a").is_none());
+
+        std_fs::remove_dir_all(path).unwrap();
+    }
+
+    fn temp_dir() -> PathBuf {
+        ::std::env::temp_dir().join(format!("xray-fs-test-{}", rand::thread_rng().gen::<u64>()))
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        use std::io::Write;
+        std_fs::File::create(path)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+    }
+
+    fn file_paths(root: &fs::Entry) -> Vec<String> {
+        let mut paths = root
+            .file_paths(true)
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[cfg(target_os = "linux")]
+    fn poll<S: Stream>(stream: &mut S) -> Async<Option<S::Item>> {
+        future::poll_fn(|| Ok::<_, ()>(Async::Ready(stream.poll())))
+            .wait()
+            .unwrap()
+            .ok()
+            .unwrap()
+    }
+}
@@ -6,6 +6,11 @@ mod messages;
 extern crate bytes;
 extern crate futures;
 extern crate futures_cpupool;
+extern crate ignore;
+#[cfg(target_os = "linux")]
+extern crate inotify;
+#[cfg(test)]
+extern crate rand;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -14,11 +14,23 @@ pub enum IncomingMessage {
     },
     OpenWorkspace {
         paths: Vec<PathBuf>,
+        #[serde(default)]
+        ignore_globs: Vec<String>,
     },
     Action {
-        view_id: ViewId,
+        /// Actions without a view are dispatched to the focused view.
+        #[serde(default)]
+        view_id: Option<ViewId>,
         action: serde_json::Value,
     },
+    KeyDown {
+        keystroke: String,
+        #[serde(default)]
+        context: Vec<String>,
+    },
+    Focus {
+        view_id: ViewId,
+    },
 }
 
 #[derive(Serialize, Debug)]
@@ -26,4 +38,5 @@ pub enum IncomingMessage {
 pub enum OutgoingMessage {
     OpenWindow { window_id: WindowId },
     UpdateWindow(window::WindowUpdate),
+    SetClipboard { text: String },
 }